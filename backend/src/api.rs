@@ -1,19 +1,45 @@
 use axum::{
+    body::Bytes,
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE, LOCATION, SET_COOKIE},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
+    middleware,
     response::{IntoResponse, Response},
-    routing::{get, post},
-    Json, Router,
+    routing::{any, delete, get, patch, post, put},
+    Extension, Json, Router,
 };
 use chrono::Utc;
 use serde::Serialize;
+use std::{net::IpAddr, time::Instant};
 
 use crate::{
+    audit::{Actor, AuditAction, AuditEvent},
+    auth::{self, Caller, Scope},
+    bookmarks,
+    bundle::{ConfigBundle, ImportReport},
+    catalog::{self, CatalogApp},
+    forward_auth,
+    golinks::{self, GoLink},
+    groups::Locale,
+    host,
+    importers::{self, ImportSource},
     models::{
-        CreateServiceRequest, DiscoveryRunResponse, DiscoveryStatusInfo, ServiceEntry,
-        ServiceQuery, UpdateServiceRequest,
+        AuditQuery, Capabilities, CatalogQuery, CreateGroupRequest, CreateServiceRequest,
+        CreateTokenRequest, CreatedToken, DiscoveryRunResponse, DiscoveryStatusInfo, ExportQuery,
+        GroupChange, GroupInfo, HostResponse, ImportQuery, ListedRule, LoginRequest,
+        ReorderRequest, RuleTestQuery, RuleTestResult, SearchHit, SearchQuery, ServiceEntry,
+        ServiceQuery, SessionInfo, UpdateGroupRequest, UpdateServiceRequest,
     },
+    networks::{self, Network, RequestNetwork},
+    ratelimit::{self, Ban},
+    read_only::{self, Restrictions},
+    rules::{self, RuleSource, Subject},
+    security,
     state::AppState,
+    tls,
+    tokens::TokenInfo,
 };
 
 #[derive(Debug, Serialize)]
@@ -38,10 +64,61 @@ pub struct HealthResponse {
 pub fn create_router(state: AppState) -> Router {
     Router::new()
         .route("/api/health", get(health))
+        .route("/api/capabilities", get(capabilities))
+        .route("/api/host", get(host_info))
         .route("/api/services", get(list_services).post(create_service))
+        .route("/api/services/order", put(reorder_services))
         .route("/api/services/{id}", get(get_service).patch(update_service))
+        .route("/api/services/{id}/open", post(open_service))
+        .route("/api/search", get(search_services))
         .route("/api/discovery/run", post(run_discovery))
         .route("/api/discovery/status", get(get_discovery_status))
+        .route("/api/ignored", get(get_ignored).put(put_ignored))
+        .route(
+            "/api/groups",
+            get(list_groups).post(create_group).put(reorder_groups),
+        )
+        .route(
+            "/api/groups/{key}",
+            patch(update_group).delete(delete_group),
+        )
+        .route("/api/catalog", get(list_catalog))
+        .route("/api/catalog/{id}", get(get_catalog_app))
+        .route("/api/rules", get(list_rules))
+        .route("/api/rules/test", get(test_rules))
+        .route("/api/export", get(export_bundle))
+        .route("/api/export/bookmarks", get(export_bookmarks))
+        .route("/api/import", post(import_bundle))
+        .route("/api/import/{source}", post(import_foreign))
+        .route("/api/auth/login", post(login))
+        .route("/api/auth/logout", post(logout))
+        .route("/api/auth/session", get(session))
+        .route("/api/auth/verify", any(verify))
+        .route("/api/tokens", get(list_tokens).post(create_token))
+        .route("/api/tokens/{id}", delete(revoke_token))
+        .route("/api/rate-limit/bans", get(list_bans).delete(clear_bans))
+        .route("/api/rate-limit/bans/{address}", delete(lift_ban))
+        .route("/api/audit", get(list_audit))
+        .route("/api/audit/export", get(export_audit))
+        .route("/go", get(go_search))
+        .route("/go/{*terms}", get(go_link))
+        .route("/opensearch.xml", get(opensearch))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            auth::require_login,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            security::csrf_guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only::guard,
+        ))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            ratelimit::limit,
+        ))
         .with_state(state)
 }
 
@@ -57,64 +134,909 @@ async fn health(State(state): State<AppState>) -> Json<HealthResponse> {
 
 async fn list_services(
     State(state): State<AppState>,
-    Query(query): Query<ServiceQuery>,
+    caller: Caller,
+    restrictions: Restrictions,
+    RequestNetwork(network): RequestNetwork,
+    Query(mut query): Query<ServiceQuery>,
 ) -> Json<Vec<ServiceEntry>> {
-    Json(state.list_services(query).await)
+    if restrictions.hide_hidden {
+        query.include_hidden = None;
+    }
+    let services = state.list_services(query, caller.visibility()).await;
+    Json(
+        services
+            .into_iter()
+            .map(|service| service.with_open_urls(network.as_ref()))
+            .collect(),
+    )
+}
+
+async fn search_services(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    RequestNetwork(network): RequestNetwork,
+    Query(mut query): Query<SearchQuery>,
+) -> Json<Vec<SearchHit>> {
+    if restrictions.hide_hidden {
+        query.include_hidden = None;
+    }
+    let hits = state.search(&query, caller.visibility()).await;
+    Json(
+        hits.into_iter()
+            .map(|hit| SearchHit {
+                service: hit.service.with_open_urls(network.as_ref()),
+                ..hit
+            })
+            .collect(),
+    )
+}
+
+/// `/go?q=<terms>`, the form browser search engines use.
+async fn go_search(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    RequestNetwork(network): RequestNetwork,
+    Query(query): Query<SearchQuery>,
+) -> Response {
+    let terms = query.q.unwrap_or_default();
+    go(
+        &state,
+        &caller,
+        &restrictions,
+        network.as_ref(),
+        &terms,
+        None,
+    )
+    .await
+}
+
+/// `/go/<alias>/<path>` redirects to the service with that alias, passing
+/// the path and query string through.
+async fn go_link(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    RequestNetwork(network): RequestNetwork,
+    Path(terms): Path<String>,
+    uri: Uri,
+) -> Response {
+    go(
+        &state,
+        &caller,
+        &restrictions,
+        network.as_ref(),
+        &terms,
+        uri.query(),
+    )
+    .await
+}
+
+/// Redirects to the service `terms` name, or to the dashboard searching for
+/// them when no service has that alias. Being a GET, this records no launch;
+/// that is left to `POST /api/services/{id}/open`.
+async fn go(
+    state: &AppState,
+    caller: &Caller,
+    restrictions: &Restrictions,
+    network: Option<&Network>,
+    terms: &str,
+    query: Option<&str>,
+) -> Response {
+    if let Err(denied) = caller.require(Scope::Read) {
+        return denied.into();
+    }
+    let visibility = caller.visibility();
+    let location = {
+        let services = state.services.read().await;
+        let visible = services.iter().filter(|service| {
+            visibility.map_or(true, |visibility| visibility.allows(service))
+                && !(service.hidden && restrictions.hide_hidden)
+        });
+        match golinks::resolve(visible, terms) {
+            GoLink::Service { service, rest } => service
+                .resolved_url(network)
+                .map(|url| golinks::target(&url, rest, query)),
+            GoLink::Search => None,
+        }
+    }
+    .unwrap_or_else(|| golinks::search_page(terms));
+    let location = HeaderValue::from_str(&location)
+        .or_else(|_| HeaderValue::from_str(&golinks::search_page(terms)))
+        .unwrap_or(HeaderValue::from_static("/"));
+    (StatusCode::FOUND, [(LOCATION, location)]).into_response()
+}
+
+async fn opensearch(
+    headers: HeaderMap,
+    uri: Uri,
+    encrypted: Option<Extension<tls::Encrypted>>,
+) -> Response {
+    let Some(host) = networks::request_host(&headers, &uri) else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .filter(|scheme| matches!(*scheme, "http" | "https"))
+        .unwrap_or(if encrypted.is_some() { "https" } else { "http" });
+    (
+        [(CONTENT_TYPE, "application/opensearchdescription+xml")],
+        golinks::opensearch_description(&format!("{scheme}://{host}")),
+    )
+        .into_response()
+}
+
+/// The service, unless it is missing or hidden from the caller.
+async fn visible_service(state: &AppState, caller: &Caller, id: &str) -> Option<ServiceEntry> {
+    let visibility = caller.visibility();
+    state
+        .get_service(id)
+        .await
+        .filter(|service| visibility.map_or(true, |visibility| visibility.allows(service)))
 }
 
 async fn get_service(
     State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    RequestNetwork(network): RequestNetwork,
     Path(id): Path<String>,
 ) -> Result<Json<ServiceEntry>, StatusCode> {
-    match state.get_service(&id).await {
-        Some(service) => Ok(Json(service)),
-        None => Err(StatusCode::NOT_FOUND),
+    match visible_service(&state, &caller, &id).await {
+        Some(service) if !(service.hidden && restrictions.hide_hidden) => {
+            Ok(Json(service.with_open_urls(network.as_ref())))
+        }
+        _ => Err(StatusCode::NOT_FOUND),
     }
 }
 
 async fn create_service(
     State(state): State<AppState>,
-    Json(request): Json<CreateServiceRequest>,
-) -> Result<Json<ServiceEntry>, ApiError> {
+    caller: Caller,
+    actor: Actor,
+    RequestNetwork(network): RequestNetwork,
+    Json(mut request): Json<CreateServiceRequest>,
+) -> Result<Json<ServiceEntry>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    if let Some(id) = request.catalog.take() {
+        let Some(app) = catalog::get(&id) else {
+            return Err(ApiError {
+                message: format!("unknown catalog app: {id}"),
+            }
+            .into_response());
+        };
+        app.prefill(&mut request);
+    }
     if request.service_name.trim().is_empty() {
         return Err(ApiError {
             message: "service_name is required".to_string(),
-        });
+        }
+        .into_response());
     }
 
     state
-        .create_service(request)
+        .create_service(request, &actor)
         .await
-        .map(Json)
-        .map_err(|error| ApiError {
-            message: format!("failed to create service: {error}"),
+        .map(|service| Json(service.with_open_urls(network.as_ref())))
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to create service: {error}"),
+            }
+            .into_response()
         })
 }
 
 async fn update_service(
     State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    RequestNetwork(network): RequestNetwork,
     Path(id): Path<String>,
     Json(request): Json<UpdateServiceRequest>,
-) -> Result<Json<ServiceEntry>, StatusCode> {
-    match state.update_service(&id, request).await {
-        Ok(Some(service)) => Ok(Json(service)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
-        Err(_) => Err(StatusCode::BAD_REQUEST),
+) -> Result<Json<ServiceEntry>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    if visible_service(&state, &caller, &id).await.is_none() {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    match state.update_service(&id, request, &actor).await {
+        Ok(Some(service)) => Ok(Json(service.with_open_urls(network.as_ref()))),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(error) => Err(ApiError {
+            message: format!("failed to update service: {error}"),
+        }
+        .into_response()),
+    }
+}
+
+async fn reorder_services(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Json(request): Json<ReorderRequest>,
+) -> Result<Json<Vec<ServiceEntry>>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    for id in &request.ids {
+        if visible_service(&state, &caller, id).await.is_none() {
+            return Err(StatusCode::NOT_FOUND.into_response());
+        }
+    }
+    match state.reorder_services(request, &actor).await {
+        Ok(Some(services)) => Ok(Json(services)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(error) => Err(ApiError {
+            message: format!("failed to reorder services: {error}"),
+        }
+        .into_response()),
+    }
+}
+
+/// Called by the dashboard when a service is opened, for `sort=last_used`.
+async fn open_service(
+    State(state): State<AppState>,
+    caller: Caller,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    caller.require(Scope::Read)?;
+    if visible_service(&state, &caller, &id).await.is_none() {
+        return Err(StatusCode::NOT_FOUND.into_response());
+    }
+    match state.record_use(&id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(error) => Err(ApiError {
+            message: format!("failed to record use: {error}"),
+        }
+        .into_response()),
     }
 }
 
 async fn run_discovery(
     State(state): State<AppState>,
-) -> Result<Json<DiscoveryRunResponse>, ApiError> {
+    caller: Caller,
+    actor: Actor,
+) -> Result<Json<DiscoveryRunResponse>, Response> {
+    caller.require(Scope::DiscoveryRun)?;
     state
-        .run_discovery()
+        .run_discovery(&actor)
         .await
         .map(|summary| Json(DiscoveryRunResponse { summary }))
-        .map_err(|error| ApiError {
-            message: format!("failed to run discovery: {error}"),
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to run discovery: {error}"),
+            }
+            .into_response()
         })
 }
 
 async fn get_discovery_status(State(state): State<AppState>) -> Json<DiscoveryStatusInfo> {
     Json(state.discovery_status().await)
 }
+
+async fn list_groups(
+    State(state): State<AppState>,
+    caller: Caller,
+    locale: Locale,
+) -> Json<Vec<GroupInfo>> {
+    Json(state.list_groups(caller.visibility(), locale).await)
+}
+
+async fn create_group(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    locale: Locale,
+    Json(request): Json<CreateGroupRequest>,
+) -> Result<Json<GroupInfo>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    let key = state.create_group(request, &actor).await.map_err(|error| {
+        ApiError {
+            message: format!("failed to create group: {error}"),
+        }
+        .into_response()
+    })?;
+    Ok(Json(state.group(&key, caller.visibility(), locale).await))
+}
+
+async fn reorder_groups(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    locale: Locale,
+    Json(keys): Json<Vec<String>>,
+) -> Result<Json<Vec<GroupInfo>>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    state.reorder_groups(keys, &actor).await.map_err(|error| {
+        ApiError {
+            message: format!("failed to reorder groups: {error}"),
+        }
+        .into_response()
+    })?;
+    Ok(Json(state.list_groups(caller.visibility(), locale).await))
+}
+
+async fn update_group(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    locale: Locale,
+    Path(key): Path<String>,
+    Json(request): Json<UpdateGroupRequest>,
+) -> Result<Json<GroupChange>, Response> {
+    // Renames touch services the caller might not see.
+    caller.require_everything(Scope::ServicesWrite)?;
+    match state.update_group(&key, request, &actor).await {
+        Ok(Some((key, mut change))) => {
+            change.group = Some(state.group(&key, caller.visibility(), locale).await);
+            Ok(Json(change))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(error) => Err(ApiError {
+            message: format!("failed to update group: {error}"),
+        }
+        .into_response()),
+    }
+}
+
+async fn delete_group(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Path(key): Path<String>,
+) -> Result<Json<GroupChange>, Response> {
+    caller.require_everything(Scope::ServicesWrite)?;
+    match state.delete_group(&key, &actor).await {
+        Ok(Some(change)) => Ok(Json(change)),
+        Ok(None) => Err(StatusCode::NOT_FOUND.into_response()),
+        Err(error) => Err(ApiError {
+            message: format!("failed to delete group: {error}"),
+        }
+        .into_response()),
+    }
+}
+
+async fn list_catalog(Query(query): Query<CatalogQuery>) -> Json<Vec<CatalogApp>> {
+    Json(catalog::search(&query))
+}
+
+async fn get_catalog_app(Path(id): Path<String>) -> Result<Json<CatalogApp>, StatusCode> {
+    catalog::get(&id)
+        .cloned()
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn list_rules(State(state): State<AppState>) -> Json<Vec<ListedRule>> {
+    let settings = state.settings.read().await;
+    let listed = [
+        (RuleSource::Config, settings.classification.as_slice()),
+        (RuleSource::File, settings.rules.as_slice()),
+        (RuleSource::Builtin, rules::builtin()),
+    ]
+    .into_iter()
+    .flat_map(|(source, rules)| {
+        rules
+            .iter()
+            .enumerate()
+            .map(move |(index, rule)| ListedRule {
+                source,
+                index: index + 1,
+                rule: rule.clone(),
+            })
+    })
+    .collect();
+    Json(listed)
+}
+
+/// Which rule discovery would apply to a unit, or to a known service.
+async fn test_rules(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    Query(query): Query<RuleTestQuery>,
+) -> Result<Json<RuleTestResult>, Response> {
+    let service = match &query.service_id {
+        Some(id) => match visible_service(&state, &caller, id).await {
+            Some(service) if !(service.hidden && restrictions.hide_hidden) => Some(service),
+            _ => return Err(StatusCode::NOT_FOUND.into_response()),
+        },
+        None => None,
+    };
+    let Some(unit) = query
+        .unit
+        .or_else(|| service.as_ref().map(|service| service.service_name.clone()))
+    else {
+        return Err(ApiError {
+            message: "unit or service_id is required".to_string(),
+        }
+        .into_response());
+    };
+    let subject = Subject {
+        unit: &unit,
+        port: query
+            .port
+            .or_else(|| service.as_ref().and_then(|service| service.port)),
+        process: query.process.as_deref(),
+        image: query.image.as_deref(),
+    };
+    let settings = state.settings.read().await;
+    let found = rules::find(&settings, &subject);
+    Ok(Json(RuleTestResult {
+        matched: found.as_ref().map(|(found, _)| found.clone()),
+        rule: found.map(|(_, rule)| rule.into_owned()),
+        unit,
+    }))
+}
+
+async fn get_ignored(State(state): State<AppState>) -> Json<Vec<String>> {
+    Json(state.ignored().await)
+}
+
+async fn put_ignored(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Json(ignored): Json<Vec<String>>,
+) -> Result<Json<Vec<String>>, Response> {
+    caller.require(Scope::ServicesWrite)?;
+    state
+        .set_ignored(ignored, &actor)
+        .await
+        .map(Json)
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to save ignore list: {error}"),
+            }
+            .into_response()
+        })
+}
+
+async fn export_bundle(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    Query(query): Query<ExportQuery>,
+) -> Result<Response, Response> {
+    caller.require_everything(Scope::Read)?;
+    if restrictions.hide_hidden {
+        return Err(auth::deny(
+            StatusCode::FORBIDDEN,
+            "export includes hidden services",
+        ));
+    }
+    let format = query.format.unwrap_or_default();
+    let body = state
+        .export_bundle()
+        .await
+        .render(format)
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to export: {error}"),
+            }
+            .into_response()
+        })?;
+    let disposition = format!(
+        "attachment; filename=\"navigator-export.{}\"",
+        format.extension()
+    );
+    Ok((
+        [
+            (CONTENT_TYPE, format.content_type().to_string()),
+            (CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+async fn export_bookmarks(
+    State(state): State<AppState>,
+    caller: Caller,
+    restrictions: Restrictions,
+    locale: Locale,
+    RequestNetwork(network): RequestNetwork,
+    Query(mut query): Query<ServiceQuery>,
+) -> Response {
+    if restrictions.hide_hidden {
+        query.include_hidden = None;
+    }
+    let services = state.list_services(query, caller.visibility()).await;
+    (
+        [
+            (CONTENT_TYPE, "text/html; charset=utf-8"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"navigator-bookmarks.html\"",
+            ),
+        ],
+        bookmarks::render(&services, locale, network.as_ref()),
+    )
+        .into_response()
+}
+
+async fn import_bundle(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<ImportReport>, Response> {
+    caller.require_everything(Scope::ServicesWrite)?;
+    let bundle = ConfigBundle::parse(&body, query.format.unwrap_or_default()).map_err(|error| {
+        ApiError {
+            message: format!("invalid bundle: {error:#}"),
+        }
+        .into_response()
+    })?;
+    import(&state, &actor, bundle, &query).await
+}
+
+async fn import_foreign(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Path(source): Path<ImportSource>,
+    Query(query): Query<ImportQuery>,
+    body: Bytes,
+) -> Result<Json<ImportReport>, Response> {
+    caller.require_everything(Scope::ServicesWrite)?;
+    let requests = importers::parse(source, &body, query.folder.as_deref()).map_err(|error| {
+        ApiError {
+            message: format!("invalid config: {error:#}"),
+        }
+        .into_response()
+    })?;
+    let bundle = ConfigBundle::from_requests(requests, &state.default_host().await);
+    import(&state, &actor, bundle, &query).await
+}
+
+async fn import(
+    state: &AppState,
+    actor: &Actor,
+    bundle: ConfigBundle,
+    query: &ImportQuery,
+) -> Result<Json<ImportReport>, Response> {
+    state
+        .import_bundle(
+            bundle,
+            query.mode.unwrap_or_default(),
+            query.dry_run.unwrap_or(false),
+            actor,
+        )
+        .await
+        .map(Json)
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to import: {error}"),
+            }
+            .into_response()
+        })
+}
+
+async fn login(
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> Result<Response, Response> {
+    let users = state.users.clone();
+    let LoginRequest { username, password } = request;
+    let checked =
+        tokio::task::spawn_blocking(move || auth::check_credentials(&users, &username, &password))
+            .await;
+
+    let user = match checked {
+        Ok(Ok(Some(user))) => user,
+        Ok(Ok(None)) => {
+            return Err((
+                StatusCode::UNAUTHORIZED,
+                Json(ApiError {
+                    message: "invalid username or password".to_string(),
+                }),
+            )
+                .into_response())
+        }
+        Ok(Err(error)) => {
+            return Err(ApiError {
+                message: format!("failed to read users: {error:#}"),
+            }
+            .into_response())
+        }
+        Err(error) => {
+            return Err(ApiError {
+                message: format!("failed to check credentials: {error}"),
+            }
+            .into_response())
+        }
+    };
+
+    let auth = state.settings.read().await.auth.clone();
+    let ttl = chrono::Duration::hours(auth.session_ttl_hours as i64);
+    let (token, _) = state.sessions.create(&user, ttl).await;
+    let cookie = auth::session_cookie(&token, ttl.num_seconds(), &auth);
+    Ok((
+        [(SET_COOKIE, cookie)],
+        Json(SessionInfo {
+            auth_enabled: auth.enabled,
+            anonymous_read: auth.anonymous_read,
+            authenticated: true,
+            username: Some(user.username),
+            role: Some(user.role),
+        }),
+    )
+        .into_response())
+}
+
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(token) = auth::session_token(&headers) {
+        state.sessions.remove(&token).await;
+    }
+    let auth = state.settings.read().await.auth.clone();
+    (
+        StatusCode::NO_CONTENT,
+        [(SET_COOKIE, auth::session_cookie("", 0, &auth))],
+    )
+        .into_response()
+}
+
+/// Forward-auth check for nginx `auth_request`, Caddy `forward_auth` and
+/// Traefik `forwardAuth`: 200 with identity headers when the session's user
+/// may open the service at `X-Forwarded-Host` + `X-Forwarded-Uri`.
+async fn verify(State(state): State<AppState>, caller: Caller, headers: HeaderMap) -> Response {
+    let identity = match &caller {
+        Caller::Unrestricted => return StatusCode::OK.into_response(),
+        Caller::Anonymous => {
+            return (
+                StatusCode::UNAUTHORIZED,
+                Json(ApiError {
+                    message: "login required".to_string(),
+                }),
+            )
+                .into_response()
+        }
+        Caller::Identified(identity) => identity,
+    };
+    let forwarded = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    let Some(host) = forwarded("x-forwarded-host") else {
+        return ApiError {
+            message: "X-Forwarded-Host is required".to_string(),
+        }
+        .into_response();
+    };
+    let uri = forwarded("x-forwarded-uri").unwrap_or("/");
+
+    let services = state.services.read().await;
+    let service = forward_auth::find_service(&services, host, uri);
+    // Restricted users only get through to services they can see
+    let allowed = match (&identity.visibility, service) {
+        (None, _) => true,
+        (Some(visibility), Some(service)) => visibility.allows(service),
+        (Some(_), None) => false,
+    };
+    if !allowed {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(ApiError {
+                message: "no access to this service".to_string(),
+            }),
+        )
+            .into_response();
+    }
+
+    let mut response = StatusCode::OK.into_response();
+    let response_headers = response.headers_mut();
+    let values = [
+        ("remote-user", Some(identity.username.as_str())),
+        ("remote-role", Some(identity.role.as_str())),
+        (
+            "x-navigator-service",
+            service.map(|service| service.id.as_str()),
+        ),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value.and_then(|value| HeaderValue::from_str(value).ok()) {
+            response_headers.insert(name, value);
+        }
+    }
+    response
+}
+
+async fn session(State(state): State<AppState>, caller: Caller) -> Json<SessionInfo> {
+    let auth = state.settings.read().await.auth.clone();
+    let identity = caller.identity();
+    Json(SessionInfo {
+        auth_enabled: auth.enabled,
+        anonymous_read: auth.anonymous_read,
+        authenticated: identity.is_some(),
+        username: identity.map(|identity| identity.username.clone()),
+        role: identity.map(|identity| identity.role),
+    })
+}
+
+async fn host_info(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<HostResponse>, Response> {
+    caller.require(Scope::Read)?;
+    let settings = state.settings.read().await;
+    Ok(Json(HostResponse {
+        default_host: settings.default_host.clone(),
+        default_host_strategy: settings.default_host_strategy,
+        detected: host::info().clone(),
+    }))
+}
+
+async fn capabilities(caller: Caller, restrictions: Restrictions) -> Json<Capabilities> {
+    let writable = !restrictions.read_only;
+    Json(Capabilities {
+        read_only: restrictions.read_only,
+        can_edit: writable && caller.require(Scope::ServicesWrite).is_ok(),
+        can_run_discovery: writable && caller.require(Scope::DiscoveryRun).is_ok(),
+        can_import: writable && caller.require_everything(Scope::ServicesWrite).is_ok(),
+        can_admin: writable && caller.require(Scope::Admin).is_ok(),
+        // Anonymous readers may only send GETs.
+        can_record_use: writable
+            && !matches!(caller, Caller::Anonymous)
+            && caller.require(Scope::Read).is_ok(),
+        show_hidden: !restrictions.hide_hidden,
+    })
+}
+
+async fn list_tokens(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<TokenInfo>>, Response> {
+    caller.require(Scope::Admin)?;
+    state.tokens.list().map(Json).map_err(|error| {
+        ApiError {
+            message: format!("failed to read tokens: {error:#}"),
+        }
+        .into_response()
+    })
+}
+
+async fn create_token(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Json(request): Json<CreateTokenRequest>,
+) -> Result<Json<CreatedToken>, Response> {
+    caller.require(Scope::Admin)?;
+    let expires_at = request
+        .expires_in_days
+        .map(|days| Utc::now() + chrono::Duration::days(days.into()));
+    let (token, info) = state
+        .tokens
+        .create(&request.name, &request.scopes, expires_at, caller.actor())
+        .map_err(|error| {
+            ApiError {
+                message: format!("failed to create token: {error:#}"),
+            }
+            .into_response()
+        })?;
+    state
+        .audit
+        .record(vec![actor
+            .event(AuditAction::TokenCreated)
+            .detail(format!("{} ({})", info.name, info.id))])
+        .await;
+    Ok(Json(CreatedToken { token, info }))
+}
+
+async fn revoke_token(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<StatusCode, Response> {
+    caller.require(Scope::Admin)?;
+    match state.tokens.revoke(&id) {
+        Ok(true) => {
+            state
+                .audit
+                .record(vec![actor.event(AuditAction::TokenRevoked).detail(id)])
+                .await;
+            Ok(StatusCode::NO_CONTENT)
+        }
+        Ok(false) => Ok(StatusCode::NOT_FOUND),
+        Err(error) => Err(ApiError {
+            message: format!("failed to revoke token: {error:#}"),
+        }
+        .into_response()),
+    }
+}
+
+async fn list_bans(
+    State(state): State<AppState>,
+    caller: Caller,
+) -> Result<Json<Vec<Ban>>, Response> {
+    caller.require(Scope::Admin)?;
+    Ok(Json(state.rate_limiter.bans(Instant::now())))
+}
+
+async fn clear_bans(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+) -> Result<StatusCode, Response> {
+    caller.require(Scope::Admin)?;
+    state.rate_limiter.unban_all();
+    state
+        .audit
+        .record(vec![actor.event(AuditAction::BansLifted).detail("all")])
+        .await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn lift_ban(
+    State(state): State<AppState>,
+    caller: Caller,
+    actor: Actor,
+    Path(address): Path<String>,
+) -> Result<StatusCode, Response> {
+    caller.require(Scope::Admin)?;
+    let address: IpAddr = address.parse().map_err(|_| {
+        ApiError {
+            message: format!("invalid address: {address}"),
+        }
+        .into_response()
+    })?;
+    if state.rate_limiter.unban(address) {
+        state
+            .audit
+            .record(vec![actor
+                .event(AuditAction::BansLifted)
+                .detail(address.to_string())])
+            .await;
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Ok(StatusCode::NOT_FOUND)
+    }
+}
+
+async fn audit_events(
+    state: &AppState,
+    caller: &Caller,
+    query: &AuditQuery,
+) -> Result<Vec<AuditEvent>, Response> {
+    caller.require(Scope::Admin)?;
+    state.audit.query(query).await.map_err(|error| {
+        ApiError {
+            message: format!("failed to read audit log: {error:#}"),
+        }
+        .into_response()
+    })
+}
+
+async fn list_audit(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEvent>>, Response> {
+    audit_events(&state, &caller, &query).await.map(Json)
+}
+
+async fn export_audit(
+    State(state): State<AppState>,
+    caller: Caller,
+    Query(query): Query<AuditQuery>,
+) -> Result<Response, Response> {
+    let events = audit_events(&state, &caller, &query).await?;
+    let mut body = String::new();
+    for event in &events {
+        body.push_str(&serde_json::to_string(event).unwrap_or_default());
+        body.push('\n');
+    }
+    Ok((
+        [
+            (CONTENT_TYPE, "application/x-ndjson"),
+            (
+                CONTENT_DISPOSITION,
+                "attachment; filename=\"navigator-audit.jsonl\"",
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
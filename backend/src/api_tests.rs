@@ -5,17 +5,26 @@ use axum::{
 use chrono::Utc;
 use tower::ServiceExt;
 
-use crate::{api::create_router, models::UpdateServiceRequest, state::AppState};
+use crate::{
+    api::create_router,
+    audit::Actor,
+    auth::{Role, Scope, Visibility},
+    config::Settings,
+    models::{
+        CreateGroupRequest, CreateServiceRequest, ServiceEntry, ServiceLink, UpdateServiceRequest,
+    },
+    networks::NetworkConfig,
+    state::AppState,
+};
 
 async fn create_state() -> AppState {
-    let temp = std::env::temp_dir();
-    let data_file = temp.join(format!("navigator-test-{}.json", uuid_like()));
-    AppState::new(
-        "localhost".to_string(),
-        data_file.to_string_lossy().to_string(),
-    )
-    .await
-    .expect("state init should succeed")
+    // A directory per test, since users and tokens live beside the data file.
+    let data_file = std::env::temp_dir()
+        .join(format!("navigator-test-{}", uuid_like()))
+        .join("services.json");
+    AppState::with_settings(Settings::default(), data_file.to_string_lossy().to_string())
+        .await
+        .expect("state init should succeed")
 }
 
 fn uuid_like() -> String {
@@ -71,6 +80,9 @@ async fn patch_unknown_service_returns_404() {
                         icon: None,
                         hidden: None,
                         favorite: None,
+                        links: None,
+                        aliases: None,
+                        network_urls: None,
                         locked_fields: None,
                         auto_lock: None,
                     })
@@ -83,3 +95,1248 @@ async fn patch_unknown_service_returns_404() {
 
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
+
+#[tokio::test]
+async fn import_dry_run_leaves_services_untouched() {
+    let state = create_state().await;
+    let app = create_router(state.clone());
+    let bundle = "version: 1\nservices:\n  - id: jellyfin\n    service_name: jellyfin.service\n    display_name: Jellyfin\n    port: 8096\n    protocol: http\n";
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/import?format=yaml&dry_run=true")
+                .method("POST")
+                .body(Body::from(bundle))
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let report: serde_json::Value = serde_json::from_slice(&body).expect("json");
+    assert_eq!(report["added"], serde_json::json!(["jellyfin"]));
+    assert!(state.get_service("jellyfin").await.is_none());
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/import?format=yaml")
+                .method("POST")
+                .body(Body::from(bundle))
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let imported = state.get_service("jellyfin").await.expect("imported");
+    assert_eq!(imported.host, "localhost");
+}
+
+#[tokio::test]
+async fn patch_declared_field_is_rejected() {
+    let dir = std::env::temp_dir().join(format!("navigator-services-d-{}", uuid_like()));
+    std::fs::create_dir_all(&dir).expect("services dir");
+    std::fs::write(
+        dir.join("router.yaml"),
+        "- id: router\n  display_name: Router\n  url: https://192.168.1.1\n",
+    )
+    .expect("declaration");
+    let state = create_state()
+        .await
+        .with_services_dir(&dir)
+        .await
+        .expect("declarations should load");
+    let app = create_router(state);
+
+    let patch = |body: &'static str| {
+        Request::builder()
+            .uri("/api/services/router")
+            .method("PATCH")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .expect("request should be built")
+    };
+
+    let response = app
+        .clone()
+        .oneshot(patch(r#"{"url": "https://evil.example"}"#))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = app
+        .oneshot(patch(
+            r#"{"url": "https://192.168.1.1", "description": "Gateway"}"#,
+        ))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn writes_require_login_when_auth_enabled() {
+    let state = create_state().await;
+    state
+        .users
+        .set_password("alice", "correct horse", Role::Editor)
+        .expect("user should be added");
+    state
+        .users
+        .set_password("kid", "correct horse", Role::Viewer)
+        .expect("user should be added");
+    let mut settings = state.settings.read().await.clone();
+    settings.auth.enabled = true;
+    settings.auth.anonymous_read = true;
+    state.apply_settings(settings).await;
+    let app = create_router(state.clone());
+
+    let put_ignored = |cookie: Option<&str>| {
+        let mut builder = Request::builder().uri("/api/ignored").method("PUT");
+        if let Some(cookie) = cookie {
+            builder = builder
+                .header("cookie", cookie)
+                .header("x-requested-with", "navigator");
+        }
+        builder
+            .header("content-type", "application/json")
+            .body(Body::from("[]"))
+            .expect("request should be built")
+    };
+
+    let response = app
+        .clone()
+        .oneshot(put_ignored(None))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/services")
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let login = |username: &str, password: &str| {
+        Request::builder()
+            .uri("/api/auth/login")
+            .method("POST")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(
+                r#"{{"username": "{username}", "password": "{password}"}}"#
+            )))
+            .expect("request should be built")
+    };
+    let session_cookie = |response: &axum::response::Response| {
+        let cookie = response
+            .headers()
+            .get("set-cookie")
+            .and_then(|value| value.to_str().ok())
+            .expect("session cookie");
+        assert!(cookie.contains("HttpOnly"));
+        cookie.split(';').next().expect("cookie pair").to_string()
+    };
+    let response = app
+        .clone()
+        .oneshot(login("alice", "wrong password"))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(login("kid", "correct horse"))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = session_cookie(&response);
+    let response = app
+        .clone()
+        .oneshot(put_ignored(Some(&cookie)))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let response = app
+        .clone()
+        .oneshot(login("alice", "correct horse"))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let cookie = session_cookie(&response);
+    let response = app
+        .clone()
+        .oneshot(put_ignored(Some(&cookie)))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // A page on another site can send the cookie but not the CSRF header
+    let forged = Request::builder()
+        .uri("/api/discovery/run")
+        .method("POST")
+        .header("cookie", &cookie)
+        .body(Body::empty())
+        .expect("request should be built");
+    let response = app
+        .clone()
+        .oneshot(forged)
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let mut cross_origin = put_ignored(Some(&cookie));
+    cross_origin.headers_mut().insert(
+        "origin",
+        "https://evil.example".parse().expect("header value"),
+    );
+    let response = app
+        .oneshot(cross_origin)
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn bearer_tokens_are_limited_to_their_scopes() {
+    let state = create_state().await;
+    let (discovery_token, _) = state
+        .tokens
+        .create("cron", &[Scope::DiscoveryRun], None, None)
+        .expect("token should be created");
+    let (admin_token, _) = state
+        .tokens
+        .create("ansible", &[Scope::Admin], None, None)
+        .expect("token should be created");
+    let mut settings = state.settings.read().await.clone();
+    settings.auth.enabled = true;
+    state.apply_settings(settings).await;
+    let app = create_router(state.clone());
+
+    let request = |method: &str, uri: &str, token: &str| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("authorization", format!("Bearer {token}"))
+            .header("content-type", "application/json")
+            .body(Body::from("[]"))
+            .expect("request should be built")
+    };
+    let status = |request: Request<Body>| {
+        let app = app.clone();
+        async move {
+            app.oneshot(request)
+                .await
+                .expect("response should succeed")
+                .status()
+        }
+    };
+
+    assert_eq!(
+        status(request("GET", "/api/services", &discovery_token)).await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status(request("PUT", "/api/ignored", &discovery_token)).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(request("GET", "/api/tokens", &discovery_token)).await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(request("PUT", "/api/ignored", "hsn_revoked")).await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    let listed = app
+        .clone()
+        .oneshot(request("GET", "/api/tokens", &admin_token))
+        .await
+        .expect("response should succeed");
+    assert_eq!(listed.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(listed.into_body(), usize::MAX)
+        .await
+        .expect("body should be readable");
+    assert!(!String::from_utf8_lossy(&body).contains(&discovery_token));
+
+    let id = state.tokens.list().expect("tokens")[0].id.clone();
+    assert_eq!(
+        status(request(
+            "DELETE",
+            &format!("/api/tokens/{id}"),
+            &admin_token
+        ))
+        .await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        status(request("GET", "/api/services", &discovery_token)).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
+
+#[tokio::test]
+async fn restricted_users_only_see_allowed_groups() {
+    let state = create_state().await;
+    for (name, group) in [("router", "Network"), ("jellyfin", "Media")] {
+        state
+            .create_service(
+                CreateServiceRequest {
+                    service_name: name.to_string(),
+                    url: Some(format!("https://{name}.home.lan")),
+                    group: Some(group.to_string()),
+                    ..Default::default()
+                },
+                &Actor::default(),
+            )
+            .await
+            .expect("service should be created");
+    }
+    state
+        .users
+        .set_password("kid", "correct horse", Role::Editor)
+        .expect("user should be added");
+    let mut settings = state.settings.read().await.clone();
+    settings.auth.enabled = true;
+    settings.auth.visibility.insert(
+        "kid".to_string(),
+        Visibility {
+            exclude_groups: vec!["network".to_string()],
+            ..Default::default()
+        },
+    );
+    state.apply_settings(settings).await;
+    let app = create_router(state.clone());
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/api/auth/login")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    r#"{"username": "kid", "password": "correct horse"}"#,
+                ))
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    let cookie = response
+        .headers()
+        .get("set-cookie")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .expect("session cookie")
+        .to_string();
+    let request = |method: &str, uri: &str, body: &'static str| {
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("cookie", &cookie)
+            .header("x-requested-with", "navigator")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .expect("request should be built")
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("GET", "/api/services", ""))
+        .await
+        .expect("response should succeed");
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let services: Vec<ServiceEntry> = serde_json::from_slice(&body).expect("json");
+    let ids: Vec<&str> = services.iter().map(|entry| entry.id.as_str()).collect();
+    assert_eq!(ids, vec!["jellyfin"]);
+
+    for (method, uri, body, expected) in [
+        ("GET", "/api/services/router", "", StatusCode::NOT_FOUND),
+        ("PATCH", "/api/services/router", "{}", StatusCode::NOT_FOUND),
+        ("PATCH", "/api/services/jellyfin", "{}", StatusCode::OK),
+        ("GET", "/api/export", "", StatusCode::FORBIDDEN),
+        ("POST", "/api/groups", r#"{"key": "Kids"}"#, StatusCode::OK),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(request(method, uri, body))
+            .await
+            .expect("response should succeed");
+        assert_eq!(response.status(), expected, "{method} {uri}");
+    }
+
+    let verify = |host: &str| {
+        Request::builder()
+            .uri("/api/auth/verify")
+            .header("cookie", &cookie)
+            .header("x-forwarded-host", host)
+            .header("x-forwarded-uri", "/web/")
+            .body(Body::empty())
+            .expect("request should be built")
+    };
+    let response = app
+        .clone()
+        .oneshot(verify("jellyfin.home.lan"))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response
+            .headers()
+            .get("remote-user")
+            .and_then(|value| value.to_str().ok()),
+        Some("kid")
+    );
+    let response = app
+        .clone()
+        .oneshot(verify("router.home.lan"))
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // Forward-auth checks carry the proxied request's method and origin
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/auth/verify")
+                .method("POST")
+                .header("cookie", &cookie)
+                .header("origin", "https://jellyfin.home.lan")
+                .header("x-forwarded-host", "jellyfin.home.lan")
+                .header("x-forwarded-uri", "/Users/AuthenticateByName")
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn repeat_writers_are_limited_then_banned() {
+    let state = create_state().await;
+    {
+        let mut settings = state.settings.write().await;
+        settings.rate_limit.writes_per_minute = 2;
+        settings.rate_limit.ban_after = 2;
+    }
+    let app = create_router(state);
+    let request = |method: &str, uri: &str, peer: &str| {
+        let peer: std::net::SocketAddr = peer.parse().expect("peer address");
+        Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("content-type", "application/json")
+            .extension(axum::extract::ConnectInfo(peer))
+            .body(Body::from(if method == "PUT" { "[]" } else { "" }))
+            .expect("request should be built")
+    };
+    let send = |request: Request<Body>| {
+        let app = app.clone();
+        async move { app.oneshot(request).await.expect("response") }
+    };
+    let iot = "192.168.1.66:40000";
+
+    for _ in 0..2 {
+        let response = send(request("PUT", "/api/ignored", iot)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let limited = send(request("PUT", "/api/ignored", iot)).await;
+    assert_eq!(limited.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        limited.headers().get("retry-after").expect("retry-after"),
+        "30"
+    );
+    assert_eq!(
+        send(request("GET", "/api/services", iot)).await.status(),
+        StatusCode::OK
+    );
+
+    let banned = send(request("PUT", "/api/ignored", iot)).await;
+    assert_eq!(banned.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(
+        banned.headers().get("retry-after").expect("retry-after"),
+        "900"
+    );
+    assert_eq!(
+        send(request("GET", "/api/services", iot)).await.status(),
+        StatusCode::TOO_MANY_REQUESTS
+    );
+
+    let admin = "192.168.1.10:50000";
+    let bans = send(request("GET", "/api/rate-limit/bans", admin)).await;
+    let body = axum::body::to_bytes(bans.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let bans: serde_json::Value = serde_json::from_slice(&body).expect("json");
+    assert_eq!(bans[0]["address"], "192.168.1.66");
+
+    assert_eq!(
+        send(request(
+            "DELETE",
+            "/api/rate-limit/bans/192.168.1.66",
+            admin
+        ))
+        .await
+        .status(),
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        send(request("GET", "/api/services", iot)).await.status(),
+        StatusCode::OK
+    );
+}
+
+#[tokio::test]
+async fn edits_are_recorded_in_the_audit_log() {
+    let state = create_state().await;
+    let created = state
+        .create_service(
+            CreateServiceRequest {
+                service_name: "grafana".to_string(),
+                port: Some(3000),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("service should be created");
+    let app = create_router(state);
+    let peer: std::net::SocketAddr = "192.168.1.5:40000".parse().expect("peer address");
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/api/services/{}", created.id))
+                .method("PATCH")
+                .header("content-type", "application/json")
+                .extension(axum::extract::ConnectInfo(peer))
+                .body(Body::from(r#"{"port": 3001}"#))
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!(
+                    "/api/audit?service_id={}&action=service_updated",
+                    created.id
+                ))
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let events: serde_json::Value = serde_json::from_slice(&body).expect("json");
+    assert_eq!(events.as_array().map(Vec::len), Some(1));
+    assert_eq!(events[0]["fields"], serde_json::json!(["port"]));
+    assert_eq!(events[0]["ip"], "192.168.1.5");
+}
+
+#[tokio::test]
+async fn read_only_clients_cannot_write_or_see_hidden_services() {
+    let state = create_state().await;
+    for (name, hidden) in [("grafana", false), ("backup", true)] {
+        state
+            .create_service(
+                CreateServiceRequest {
+                    service_name: name.to_string(),
+                    hidden: Some(hidden),
+                    ..Default::default()
+                },
+                &Actor::default(),
+            )
+            .await
+            .expect("service should be created");
+    }
+    {
+        let mut settings = state.settings.write().await;
+        settings.read_only.clients = vec!["192.168.1.40".to_string()];
+        settings.read_only.hide_hidden = true;
+        settings.auth.proxy = Some(crate::auth::ProxyAuthConfig {
+            trusted_proxies: vec!["10.0.0.2".to_string()],
+            ..Default::default()
+        });
+    }
+    let app = create_router(state);
+    let send = |method: &str, uri: &str, peer: &str| {
+        let peer: std::net::SocketAddr = peer.parse().expect("peer address");
+        let request = Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("content-type", "application/json")
+            .extension(axum::extract::ConnectInfo(peer))
+            .body(Body::from(if method == "GET" { "" } else { "[]" }))
+            .expect("request should be built");
+        let app = app.clone();
+        async move { app.oneshot(request).await.expect("response should succeed") }
+    };
+    let json = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        serde_json::from_slice::<serde_json::Value>(&body).expect("json")
+    };
+    let tablet = "192.168.1.40:50000";
+    let laptop = "192.168.1.20:50000";
+
+    assert_eq!(
+        send("PUT", "/api/ignored", tablet).await.status(),
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        send("PUT", "/api/ignored", laptop).await.status(),
+        StatusCode::OK
+    );
+
+    // Behind the SSO proxy, the tablet is named by X-Forwarded-For
+    let proxied = Request::builder()
+        .uri("/api/ignored")
+        .method("PUT")
+        .header("content-type", "application/json")
+        .header("x-forwarded-for", "192.168.1.40")
+        .extension(axum::extract::ConnectInfo(
+            "10.0.0.2:40000"
+                .parse::<std::net::SocketAddr>()
+                .expect("peer address"),
+        ))
+        .body(Body::from("[]"))
+        .expect("request should be built");
+    assert_eq!(
+        app.clone()
+            .oneshot(proxied)
+            .await
+            .expect("response should succeed")
+            .status(),
+        StatusCode::FORBIDDEN
+    );
+
+    let listed = json(send("GET", "/api/services?include_hidden=true", tablet).await).await;
+    assert_eq!(listed.as_array().map(Vec::len), Some(1));
+    let listed = json(send("GET", "/api/services?include_hidden=true", laptop).await).await;
+    assert_eq!(listed.as_array().map(Vec::len), Some(2));
+
+    let capabilities = json(send("GET", "/api/capabilities", tablet).await).await;
+    assert_eq!(capabilities["read_only"], true);
+    assert_eq!(capabilities["can_edit"], false);
+    assert_eq!(capabilities["can_record_use"], false);
+    assert_eq!(capabilities["show_hidden"], false);
+    let capabilities = json(send("GET", "/api/capabilities", laptop).await).await;
+    assert_eq!(capabilities["can_edit"], true);
+    assert_eq!(capabilities["can_record_use"], true);
+}
+
+#[tokio::test]
+async fn rule_test_reports_the_matching_rule() {
+    let state = create_state().await;
+    {
+        let mut settings = state.settings.write().await;
+        settings.rules = vec![crate::rules::Rule {
+            name: Some("exporters".to_string()),
+            unit: vec!["*-exporter.service".to_string()],
+            group: Some("Metrics".to_string()),
+            ..Default::default()
+        }];
+    }
+    let created = state
+        .create_service(
+            CreateServiceRequest {
+                service_name: "jellyfin.service".to_string(),
+                port: Some(8096),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("service should be created");
+    let app = create_router(state);
+
+    let test = |uri: String| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .body(Body::empty())
+                        .expect("request should be built"),
+                )
+                .await
+                .expect("response should succeed");
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            serde_json::from_slice::<serde_json::Value>(&body).expect("json")
+        }
+    };
+
+    let result = test("/api/rules/test?unit=node-exporter.service&port=9100".to_string()).await;
+    assert_eq!(result["matched"]["source"], "file");
+    assert_eq!(result["rule"]["group"], "Metrics");
+
+    let result = test(format!("/api/rules/test?service_id={}", created.id)).await;
+    assert_eq!(result["matched"]["source"], "catalog");
+    assert_eq!(result["matched"]["name"], "jellyfin");
+}
+
+#[tokio::test]
+async fn services_can_be_created_from_the_catalog() {
+    let app = create_router(create_state().await);
+    let create = |body: &'static str| {
+        app.clone().oneshot(
+            Request::builder()
+                .uri("/api/services")
+                .method("POST")
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .expect("request should be built"),
+        )
+    };
+
+    let response = create(r#"{"service_name": "", "catalog": "vaultwarden", "port": 8222}"#)
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let service: ServiceEntry = serde_json::from_slice(&body).expect("json");
+    assert_eq!(service.service_name, "vaultwarden");
+    assert_eq!(service.display_name, "Vaultwarden");
+    assert_eq!(service.port, Some(8222));
+    assert_eq!(service.icon.as_deref(), Some("🔑"));
+    assert!(service.description.is_some());
+
+    let response = create(r#"{"service_name": "x", "catalog": "no-such-app"}"#)
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn groups_are_labelled_in_the_requested_locale() {
+    let state = create_state().await;
+    for (name, group) in [("jellyfin", "影音"), ("wiki", "Homelab")] {
+        state
+            .create_service(
+                CreateServiceRequest {
+                    service_name: name.to_string(),
+                    port: Some(8080),
+                    group: Some(group.to_string()),
+                    ..Default::default()
+                },
+                &Actor::default(),
+            )
+            .await
+            .expect("service should be created");
+    }
+    let app = create_router(state);
+    let groups = |uri: &'static str, language: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("accept-language", language)
+                        .body(Body::empty())
+                        .expect("request should be built"),
+                )
+                .await
+                .expect("response should succeed");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            serde_json::from_slice::<Vec<serde_json::Value>>(&body).expect("json")
+        }
+    };
+    let find = |groups: &[serde_json::Value], key: &str| {
+        groups
+            .iter()
+            .find(|group| group["key"] == key)
+            .cloned()
+            .expect("group should be listed")
+    };
+
+    let listed = groups("/api/groups", "en-US,en;q=0.9").await;
+    let media = find(&listed, "media");
+    assert_eq!(media["label"], "Media");
+    assert_eq!(media["services"], 1);
+    let custom = find(&listed, "Homelab");
+    assert_eq!(custom["builtin"], false);
+    assert_eq!(custom["label"], "Homelab");
+
+    let listed = groups("/api/groups", "zh-CN").await;
+    assert_eq!(find(&listed, "media")["label"], "影音");
+    let listed = groups("/api/groups?locale=en", "zh-CN").await;
+    assert_eq!(find(&listed, "media")["label"], "Media");
+}
+
+#[tokio::test]
+async fn groups_can_be_renamed_merged_and_ordered() {
+    let state = create_state().await;
+    for (name, group) in [
+        ("jellyfin", "Movies"),
+        ("plex", "Movies"),
+        ("emby", "media"),
+    ] {
+        state
+            .create_service(
+                CreateServiceRequest {
+                    service_name: name.to_string(),
+                    port: Some(8080),
+                    group: Some(group.to_string()),
+                    ..Default::default()
+                },
+                &Actor::default(),
+            )
+            .await
+            .expect("service should be created");
+    }
+    {
+        let mut services = state.services.write().await;
+        let plex = services
+            .iter_mut()
+            .find(|entry| entry.id == "plex")
+            .expect("plex");
+        plex.declared_fields.push("group".to_string());
+    }
+    let app = create_router(state.clone());
+    let send = |method: &'static str, uri: &'static str, body: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .expect("request should be built"),
+                )
+                .await
+                .expect("response should succeed");
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            (
+                status,
+                serde_json::from_slice::<serde_json::Value>(&body).ok(),
+            )
+        }
+    };
+
+    let (status, _) = send("POST", "/api/groups", r#"{"key": "Homelab", "icon": "🏠"}"#).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send("POST", "/api/groups", r#"{"key": "Homelab"}"#).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    // Merging moves members into the existing group, except declared ones.
+    let (status, change) = send(
+        "PATCH",
+        "/api/groups/Movies",
+        r#"{"key": "media", "collapsed": true}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    let change = change.expect("json");
+    assert_eq!(change["moved"], serde_json::json!(["jellyfin"]));
+    assert_eq!(change["skipped"], serde_json::json!(["plex"]));
+    assert_eq!(change["group"]["services"], 2);
+    assert_eq!(change["group"]["collapsed"], true);
+    let jellyfin = state.get_service("jellyfin").await.expect("jellyfin");
+    assert_eq!(jellyfin.group.as_deref(), Some("media"));
+    assert!(jellyfin.is_locked("group"));
+
+    let (status, listed) = send("PUT", "/api/groups", r#"["Homelab", "media"]"#).await;
+    assert_eq!(status, StatusCode::OK);
+    let listed = listed.expect("json");
+    assert_eq!(listed[0]["key"], "Homelab");
+    assert_eq!(listed[0]["icon"], "🏠");
+    assert_eq!(listed[1]["key"], "media");
+
+    let (status, _) = send("DELETE", "/api/groups/Homelab", "").await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = send("PATCH", "/api/groups/Homelab", "{}").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn services_keep_their_manual_order() {
+    let state = create_state().await;
+    for name in ["jellyfin", "jackett", "grafana"] {
+        state
+            .create_service(
+                CreateServiceRequest {
+                    service_name: name.to_string(),
+                    port: Some(8080),
+                    group: Some("media".to_string()),
+                    ..Default::default()
+                },
+                &Actor::default(),
+            )
+            .await
+            .expect("service should be created");
+    }
+    let app = create_router(state);
+    let send = |method: &'static str, uri: &'static str, body: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .method(method)
+                        .uri(uri)
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .expect("request should be built"),
+                )
+                .await
+                .expect("response should succeed");
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            (status, body)
+        }
+    };
+    let ids = |uri: &'static str| {
+        let send = &send;
+        async move {
+            let (_, body) = send("GET", uri, "").await;
+            serde_json::from_slice::<Vec<ServiceEntry>>(&body)
+                .expect("json")
+                .into_iter()
+                .map(|service| service.id)
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // New services are appended; `name` sorts alphabetically.
+    assert_eq!(
+        ids("/api/services").await,
+        vec!["jellyfin", "jackett", "grafana"]
+    );
+    assert_eq!(
+        ids("/api/services?sort=name").await,
+        vec!["grafana", "jackett", "jellyfin"]
+    );
+
+    let (status, _) = send(
+        "PUT",
+        "/api/services/order",
+        r#"{"group": "media", "ids": ["grafana", "jellyfin"]}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        ids("/api/services").await,
+        vec!["grafana", "jackett", "jellyfin"]
+    );
+    let (status, _) = send(
+        "PUT",
+        "/api/services/order",
+        r#"{"group": "sync", "ids": ["jackett"]}"#,
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, _) = send("POST", "/api/services/jackett/open", "").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    assert_eq!(
+        ids("/api/services?sort=last_used").await,
+        vec!["jackett", "grafana", "jellyfin"]
+    );
+}
+
+#[tokio::test]
+async fn urls_follow_the_network_of_the_request() {
+    let state = create_state().await;
+    state
+        .create_service(
+            CreateServiceRequest {
+                service_name: "jellyfin".to_string(),
+                port: Some(8096),
+                links: Some(vec![ServiceLink {
+                    name: "Metrics".to_string(),
+                    port: Some(9090),
+                    ..Default::default()
+                }]),
+                network_urls: Some(
+                    [(
+                        "public".to_string(),
+                        "https://jellyfin.example.com".to_string(),
+                    )]
+                    .into(),
+                ),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("service should be created");
+    state.settings.write().await.networks = vec![
+        NetworkConfig {
+            name: "tailscale".to_string(),
+            subnets: vec!["100.64.0.0/10".to_string()],
+            host: Some("100.100.1.2".to_string()),
+            ..Default::default()
+        },
+        NetworkConfig {
+            name: "public".to_string(),
+            hosts: vec!["nav.example.com".to_string()],
+            ..Default::default()
+        },
+    ];
+    let app = create_router(state);
+    let urls = |host: &'static str, peer: &'static str| {
+        let peer: std::net::SocketAddr = peer.parse().expect("peer address");
+        let request = Request::builder()
+            .uri("/api/services/jellyfin")
+            .header("host", host)
+            .extension(axum::extract::ConnectInfo(peer))
+            .body(Body::empty())
+            .expect("request should be built");
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.expect("response should succeed");
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .expect("body");
+            let service: serde_json::Value = serde_json::from_slice(&body).expect("json");
+            (
+                service["open_url"].as_str().map(str::to_string),
+                service["links"][0]["open_url"].as_str().map(str::to_string),
+            )
+        }
+    };
+
+    assert_eq!(
+        urls("localhost:8080", "192.168.1.20:50000").await,
+        (
+            Some("http://localhost:8096".to_string()),
+            Some("http://localhost:9090".to_string())
+        )
+    );
+    assert_eq!(
+        urls("localhost:8080", "100.64.1.2:50000").await,
+        (
+            Some("http://100.100.1.2:8096".to_string()),
+            Some("http://100.100.1.2:9090".to_string())
+        )
+    );
+    assert_eq!(
+        urls("nav.example.com", "100.64.1.2:50000").await.0,
+        Some("https://jellyfin.example.com".to_string())
+    );
+}
+
+#[tokio::test]
+async fn host_endpoint_reports_the_default_host() {
+    let state = create_state().await;
+    let app = create_router(state);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/api/host")
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let host: serde_json::Value = serde_json::from_slice(&body).expect("json");
+    assert_eq!(host["default_host"], "localhost");
+    assert!(host["default_host_strategy"].is_null());
+    assert!(host["addresses"].is_array());
+}
+
+#[tokio::test]
+async fn go_links_redirect_to_services_or_search() {
+    let state = create_state().await;
+    state
+        .create_service(
+            CreateServiceRequest {
+                service_name: "grafana".to_string(),
+                port: Some(3000),
+                aliases: Some(vec!["Graf".to_string()]),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("service should be created");
+    let app = create_router(state.clone());
+    let location = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri(uri)
+                        .header("host", "nav.lan")
+                        .body(Body::empty())
+                        .expect("request should be built"),
+                )
+                .await
+                .expect("response should succeed");
+            assert_eq!(response.status(), StatusCode::FOUND);
+            response.headers()["location"]
+                .to_str()
+                .expect("location")
+                .to_string()
+        }
+    };
+
+    assert_eq!(
+        location("/go/graf/d/abc?orgId=1").await,
+        "http://localhost:3000/d/abc?orgId=1"
+    );
+    assert_eq!(location("/go?q=graf").await, "http://localhost:3000");
+    assert_eq!(location("/go/unknown%20thing").await, "/?q=unknown%20thing");
+    let grafana = state.get_service("grafana").await.expect("grafana");
+    assert_eq!(grafana.launch_count, 0);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/opensearch.xml")
+                .header("host", "nav.lan")
+                .header("x-forwarded-proto", "https")
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let description = String::from_utf8(body.to_vec()).expect("utf-8");
+    assert!(description.contains(r#"template="https://nav.lan/go?q={searchTerms}""#));
+}
+
+#[tokio::test]
+async fn group_settings_survive_export_and_import() {
+    let source = create_state().await;
+    source
+        .create_service(
+            CreateServiceRequest {
+                service_name: "grafana".to_string(),
+                port: Some(3000),
+                group: Some("Dashboards".to_string()),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("service should be created");
+    source
+        .create_group(
+            CreateGroupRequest {
+                key: "Dashboards".to_string(),
+                icon: Some("📊".to_string()),
+                collapsed: Some(true),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("group should be created");
+    source
+        .reorder_groups(
+            vec!["monitoring".to_string(), "Dashboards".to_string()],
+            &Actor::default(),
+        )
+        .await
+        .expect("groups should be reordered");
+    let response = create_router(source)
+        .oneshot(
+            Request::builder()
+                .uri("/api/export")
+                .body(Body::empty())
+                .expect("request should be built"),
+        )
+        .await
+        .expect("response should succeed");
+    let bundle = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+
+    let target = create_state().await;
+    target
+        .create_group(
+            CreateGroupRequest {
+                key: "Stale".to_string(),
+                ..Default::default()
+            },
+            &Actor::default(),
+        )
+        .await
+        .expect("group should be created");
+    let import = |mode: &str| {
+        let app = create_router(target.clone());
+        let request = Request::builder()
+            .uri(format!("/api/import?mode={mode}"))
+            .method("POST")
+            .body(Body::from(bundle.clone()))
+            .expect("request should be built");
+        async move {
+            let response = app.oneshot(request).await.expect("response should succeed");
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    };
+    let settings = || async {
+        target
+            .meta
+            .read()
+            .await
+            .groups
+            .iter()
+            .map(|group| (group.key.clone(), group.icon.clone(), group.collapsed))
+            .collect::<Vec<_>>()
+    };
+
+    import("merge").await;
+    assert_eq!(
+        settings().await,
+        vec![
+            ("Stale".to_string(), None, false),
+            ("monitoring".to_string(), None, false),
+            ("Dashboards".to_string(), Some("📊".to_string()), true),
+        ]
+    );
+    import("replace").await;
+    assert_eq!(
+        settings().await,
+        vec![
+            ("monitoring".to_string(), None, false),
+            ("Dashboards".to_string(), Some("📊".to_string()), true),
+        ]
+    );
+}
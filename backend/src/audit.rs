@@ -0,0 +1,371 @@
+use crate::{
+    auth::Caller,
+    bundle,
+    models::{AuditQuery, ServiceEntry},
+    ratelimit::ClientIp,
+};
+use anyhow::{Context, Result};
+use axum::{extract::FromRequestParts, http::request::Parts};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, net::IpAddr, path::PathBuf};
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+use tracing::warn;
+
+/// Bookkeeping fields that are not reported as changes.
+const UNTRACKED_FIELDS: &[&str] = &["updated_by", "last_seen_at", "locked_fields"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ActorKind {
+    User,
+    Token,
+    Discovery,
+    Import,
+    /// The config file or services directory.
+    Config,
+    #[default]
+    Anonymous,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    ServiceCreated,
+    ServiceUpdated,
+    ServiceRemoved,
+    LocksChanged,
+    DiscoveryRun,
+    Imported,
+    IgnoredChanged,
+    GroupChanged,
+    SettingsChanged,
+    TokenCreated,
+    TokenRevoked,
+    BansLifted,
+}
+
+/// Who made a change and from where.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Actor {
+    pub kind: ActorKind,
+    pub name: Option<String>,
+    pub ip: Option<IpAddr>,
+}
+
+impl Actor {
+    pub fn system(kind: ActorKind) -> Self {
+        Self {
+            kind,
+            ..Default::default()
+        }
+    }
+
+    pub fn from_caller(caller: &Caller, ip: Option<IpAddr>) -> Self {
+        let kind = match caller.identity() {
+            Some(identity) if identity.scopes.is_some() => ActorKind::Token,
+            Some(_) => ActorKind::User,
+            None => ActorKind::Anonymous,
+        };
+        Self {
+            kind,
+            name: caller.actor().map(str::to_string),
+            ip,
+        }
+    }
+
+    pub fn event(&self, action: AuditAction) -> AuditEvent {
+        AuditEvent {
+            at: Utc::now(),
+            actor_kind: self.kind,
+            actor: self.name.clone(),
+            ip: self.ip,
+            action,
+            service_id: None,
+            fields: Vec::new(),
+            detail: None,
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Actor {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let caller = Caller::from_request_parts(parts, state).await?;
+        let ip = parts
+            .extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(address)| *address);
+        Ok(Self::from_caller(&caller, ip))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub at: DateTime<Utc>,
+    pub actor_kind: ActorKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip: Option<IpAddr>,
+    pub action: AuditAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn service(mut self, id: &str) -> Self {
+        self.service_id = Some(id.to_string());
+        self
+    }
+
+    pub fn fields(mut self, fields: Vec<String>) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    fn matches(&self, query: &AuditQuery) -> bool {
+        let actor_matches = query.actor.as_deref().map_or(true, |actor| {
+            self.actor.as_deref() == Some(actor)
+                || serde_json::to_value(self.actor_kind)
+                    .is_ok_and(|kind| kind.as_str() == Some(actor))
+        });
+        actor_matches
+            && query.action.map_or(true, |action| action == self.action)
+            && query
+                .service_id
+                .as_deref()
+                .map_or(true, |id| self.service_id.as_deref() == Some(id))
+            && query.since.map_or(true, |since| self.at >= since)
+            && query.until.map_or(true, |until| self.at < until)
+    }
+}
+
+/// Events for one service edited in place: its field changes, plus a
+/// separate entry when its locks changed.
+pub fn service_update(
+    actor: &Actor,
+    before: &ServiceEntry,
+    after: &ServiceEntry,
+) -> Vec<AuditEvent> {
+    let mut events = Vec::new();
+    let fields = changed_fields(before, after);
+    if !fields.is_empty() {
+        events.push(
+            actor
+                .event(AuditAction::ServiceUpdated)
+                .service(&after.id)
+                .fields(fields),
+        );
+    }
+    let locked: Vec<&String> = after
+        .locked_fields
+        .iter()
+        .filter(|field| !before.locked_fields.contains(field))
+        .collect();
+    let unlocked: Vec<&String> = before
+        .locked_fields
+        .iter()
+        .filter(|field| !after.locked_fields.contains(field))
+        .collect();
+    if !locked.is_empty() || !unlocked.is_empty() {
+        let list = |fields: &[&String]| {
+            fields
+                .iter()
+                .map(|field| field.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        events.push(
+            actor
+                .event(AuditAction::LocksChanged)
+                .service(&after.id)
+                .fields(
+                    locked
+                        .iter()
+                        .chain(&unlocked)
+                        .map(|field| field.to_string())
+                        .collect(),
+                )
+                .detail(format!(
+                    "locked: {}; unlocked: {}",
+                    list(&locked),
+                    list(&unlocked)
+                )),
+        );
+    }
+    events
+}
+
+/// Events for every service added, removed or changed between two lists.
+pub fn service_changes(
+    actor: &Actor,
+    before: &[ServiceEntry],
+    after: &[ServiceEntry],
+) -> Vec<AuditEvent> {
+    let mut events = Vec::new();
+    for entry in after {
+        match before.iter().find(|previous| previous.id == entry.id) {
+            Some(previous) => events.extend(service_update(actor, previous, entry)),
+            None => events.push(actor.event(AuditAction::ServiceCreated).service(&entry.id)),
+        }
+    }
+    for entry in before {
+        if !after.iter().any(|current| current.id == entry.id) {
+            events.push(actor.event(AuditAction::ServiceRemoved).service(&entry.id));
+        }
+    }
+    events
+}
+
+fn changed_fields(before: &ServiceEntry, after: &ServiceEntry) -> Vec<String> {
+    bundle::changed_fields(before, after)
+        .into_iter()
+        .filter(|field| !UNTRACKED_FIELDS.contains(&field.as_str()))
+        .collect()
+}
+
+/// Append-only JSON lines file of changes.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Appends `events`. Failures are logged rather than failing the change
+    /// that was already saved.
+    pub async fn record(&self, events: Vec<AuditEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        if let Err(error) = self.append(&events).await {
+            warn!(path = %self.path.display(), "failed writing audit log: {error:#}");
+        }
+    }
+
+    async fn append(&self, events: &[AuditEvent]) -> Result<()> {
+        let mut lines = String::new();
+        for event in events {
+            lines.push_str(&serde_json::to_string(event)?);
+            lines.push('\n');
+        }
+        let _guard = self.lock.lock().await;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .with_context(|| format!("failed opening {}", self.path.display()))?;
+        file.write_all(lines.as_bytes()).await?;
+        file.flush().await?;
+        Ok(())
+    }
+
+    /// Matching events, oldest first. Unreadable lines are skipped.
+    pub async fn query(&self, query: &AuditQuery) -> Result<Vec<AuditEvent>> {
+        let content = {
+            let _guard = self.lock.lock().await;
+            match fs::read_to_string(&self.path).await {
+                Ok(content) => content,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(error) => {
+                    return Err(error)
+                        .with_context(|| format!("failed reading {}", self.path.display()))
+                }
+            }
+        };
+        let mut events: Vec<AuditEvent> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str::<AuditEvent>(line).ok())
+            .filter(|event| event.matches(query))
+            .collect();
+        if let Some(limit) = query.limit {
+            let skip = events.len().saturating_sub(limit);
+            events.drain(..skip);
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateServiceRequest;
+
+    #[tokio::test]
+    async fn records_and_filters_service_changes() {
+        let before = CreateServiceRequest {
+            service_name: "grafana".to_string(),
+            port: Some(3000),
+            ..Default::default()
+        }
+        .into_entry("server.lan");
+        let mut after = before.clone();
+        after.port = Some(3001);
+        after.locked_fields = vec!["port".to_string()];
+        after.updated_by = Some("alice".to_string());
+
+        let actor = Actor {
+            kind: ActorKind::User,
+            name: Some("alice".to_string()),
+            ip: "192.168.1.5".parse().ok(),
+        };
+        let events = service_changes(&actor, &[before], &[after]);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].fields, vec!["port".to_string()]);
+        assert_eq!(events[1].action, AuditAction::LocksChanged);
+
+        let path = std::env::temp_dir().join(format!(
+            "navigator-audit-{}.jsonl",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let log = AuditLog::new(&path);
+        log.record(events).await;
+        log.record(vec![Actor::system(ActorKind::Discovery)
+            .event(AuditAction::DiscoveryRun)
+            .detail("added 0")])
+            .await;
+
+        let all = log.query(&AuditQuery::default()).await.expect("query");
+        assert_eq!(all.len(), 3);
+        let query = |actor: &str| AuditQuery {
+            actor: Some(actor.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(log.query(&query("alice")).await.expect("query").len(), 2);
+        assert_eq!(
+            log.query(&query("discovery")).await.expect("query").len(),
+            1
+        );
+        let latest = log
+            .query(&AuditQuery {
+                limit: Some(1),
+                ..Default::default()
+            })
+            .await
+            .expect("query");
+        assert_eq!(latest[0].action, AuditAction::DiscoveryRun);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,910 @@
+use crate::{api::ApiError, groups, models::ServiceEntry, state::AppState};
+use anyhow::{bail, Context, Result};
+use argon2::{password_hash::SaltString, Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE},
+        request::Parts,
+        HeaderMap, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
+use ipnet::IpNet;
+use rand_core::{OsRng, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+use tokio::sync::RwLock;
+
+pub const SESSION_COOKIE: &str = "hsn_session";
+const USERS_VERSION: u32 = 1;
+
+/// Endpoints reachable without a session even when auth is enabled.
+const PUBLIC_PATHS: &[&str] = &[
+    "/api/health",
+    "/api/auth/login",
+    "/api/auth/logout",
+    "/api/auth/session",
+    "/api/auth/verify",
+    "/opensearch.xml",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Require a login for the API. Off by default so existing installs keep working.
+    pub enabled: bool,
+    /// Defaults to `users.json` next to the data file.
+    pub users_file: Option<PathBuf>,
+    /// Defaults to `tokens.json` next to the data file.
+    pub tokens_file: Option<PathBuf>,
+    /// Let anonymous visitors use GET endpoints; writes still need a login.
+    pub anonymous_read: bool,
+    pub session_ttl_hours: u64,
+    /// Mark the session cookie `Secure`; enable when served over HTTPS.
+    pub secure_cookie: bool,
+    /// Share the session cookie with subdomains, e.g. `home.lan`, so
+    /// `/api/auth/verify` sees it on requests for other services.
+    pub cookie_domain: Option<String>,
+    /// Trust identity headers set by an SSO proxy (`[auth.proxy]`).
+    pub proxy: Option<ProxyAuthConfig>,
+    /// Per-username limits on which services are shown (`[auth.visibility.<name>]`).
+    pub visibility: BTreeMap<String, Visibility>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            users_file: None,
+            tokens_file: None,
+            anonymous_read: false,
+            session_ttl_hours: 24 * 7,
+            secure_cookie: false,
+            cookie_domain: None,
+            proxy: None,
+            visibility: BTreeMap::new(),
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.session_ttl_hours == 0 {
+            bail!("auth.session_ttl_hours must be at least 1");
+        }
+        if let Some(domain) = &self.cookie_domain {
+            let valid = !domain.is_empty()
+                && domain
+                    .chars()
+                    .all(|value| value.is_ascii_alphanumeric() || matches!(value, '.' | '-'));
+            if !valid {
+                bail!("auth.cookie_domain must be a plain domain name");
+            }
+        }
+        if let Some(proxy) = &self.proxy {
+            proxy.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn users_path(&self, data_file: &str) -> PathBuf {
+        self.users_file
+            .clone()
+            .unwrap_or_else(|| beside(data_file, "users.json"))
+    }
+
+    pub fn tokens_path(&self, data_file: &str) -> PathBuf {
+        self.tokens_file
+            .clone()
+            .unwrap_or_else(|| beside(data_file, "tokens.json"))
+    }
+}
+
+pub(crate) fn beside(data_file: &str, name: &str) -> PathBuf {
+    Path::new(data_file)
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(name)
+}
+
+/// Which services a user may see. Services in `groups` or carrying one of
+/// `tags` are shown (both empty shows everything), minus the exclusions.
+/// Names compare case-insensitively.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Visibility {
+    pub groups: Vec<String>,
+    pub tags: Vec<String>,
+    pub exclude_groups: Vec<String>,
+    pub exclude_tags: Vec<String>,
+}
+
+impl Visibility {
+    pub fn allows(&self, entry: &ServiceEntry) -> bool {
+        let contains = |names: &[String], value: &str| {
+            names.iter().any(|name| name.eq_ignore_ascii_case(value))
+        };
+        // Legacy Chinese names in the config still match migrated groups.
+        let has_group = |names: &[String]| {
+            let group = entry.group.as_deref().unwrap_or_default();
+            names
+                .iter()
+                .any(|name| groups::canonical(name).eq_ignore_ascii_case(group))
+        };
+        let has_tag = |names: &[String]| entry.tags.iter().any(|tag| contains(names, tag));
+
+        let included = (self.groups.is_empty() && self.tags.is_empty())
+            || has_group(&self.groups)
+            || has_tag(&self.tags);
+        included && !has_group(&self.exclude_groups) && !has_tag(&self.exclude_tags)
+    }
+}
+
+/// Parses addresses or CIDR ranges; `what` names the setting in errors.
+pub(crate) fn parse_networks(values: &[String], what: &str) -> Result<Vec<IpNet>> {
+    values
+        .iter()
+        .map(|value| {
+            let value = value.trim();
+            value
+                .parse::<IpNet>()
+                .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+                .with_context(|| format!("invalid {what} address: {value}"))
+        })
+        .collect()
+}
+
+/// Unwraps IPv4-mapped IPv6 addresses so they match IPv4 ranges.
+pub(crate) fn canonical_ip(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(address),
+        IpAddr::V4(_) => address,
+    }
+}
+
+/// Authelia, Authentik, oauth2-proxy and similar forward the logged-in user
+/// in request headers. Those headers are only believed when the request
+/// comes straight from one of `trusted_proxies`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyAuthConfig {
+    /// Proxy addresses or CIDR ranges, e.g. `127.0.0.1` or `172.18.0.0/16`.
+    pub trusted_proxies: Vec<String>,
+    pub user_header: String,
+    /// Comma-separated group list.
+    pub groups_header: String,
+    /// Proxy group name to navigator role; the highest matching role wins.
+    pub group_roles: BTreeMap<String, Role>,
+    /// Role for users in none of `group_roles`; unset rejects them.
+    pub default_role: Option<Role>,
+}
+
+impl Default for ProxyAuthConfig {
+    fn default() -> Self {
+        Self {
+            trusted_proxies: Vec::new(),
+            user_header: "Remote-User".to_string(),
+            groups_header: "Remote-Groups".to_string(),
+            group_roles: BTreeMap::new(),
+            default_role: None,
+        }
+    }
+}
+
+impl ProxyAuthConfig {
+    fn validate(&self) -> Result<()> {
+        if self.trusted_proxies.is_empty() {
+            bail!("auth.proxy.trusted_proxies must list at least one address");
+        }
+        self.trusted_networks()?;
+        if self.user_header.trim().is_empty() {
+            bail!("auth.proxy.user_header must not be empty");
+        }
+        Ok(())
+    }
+
+    fn trusted_networks(&self) -> Result<Vec<IpNet>> {
+        parse_networks(&self.trusted_proxies, "trusted proxy")
+    }
+
+    fn trusts(&self, peer: IpAddr) -> bool {
+        let peer = canonical_ip(peer);
+        self.trusted_networks()
+            .map(|networks| networks.iter().any(|network| network.contains(&peer)))
+            .unwrap_or(false)
+    }
+
+    fn role_for(&self, groups: &str) -> Option<Role> {
+        groups
+            .split(',')
+            .filter_map(|group| self.group_roles.get(group.trim()))
+            .copied()
+            .max()
+            .or(self.default_role)
+    }
+}
+
+/// Ordered from least to most privileged.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize, ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Browse services.
+    Viewer,
+    /// Also edit services, import and run discovery.
+    Editor,
+    /// Everything.
+    #[default]
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::Editor => "editor",
+            Self::Admin => "admin",
+        }
+    }
+
+    pub fn grants(self, scope: Scope) -> bool {
+        match scope {
+            Scope::Read => true,
+            Scope::ServicesWrite | Scope::DiscoveryRun => self >= Role::Editor,
+            Scope::Admin => self == Role::Admin,
+        }
+    }
+}
+
+/// What an API token may do. Every token can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum Scope {
+    #[serde(rename = "read")]
+    #[value(name = "read")]
+    Read,
+    /// Create, edit and import services and edit the ignore list.
+    #[serde(rename = "services:write")]
+    #[value(name = "services:write")]
+    ServicesWrite,
+    #[serde(rename = "discovery:run")]
+    #[value(name = "discovery:run")]
+    DiscoveryRun,
+    /// Everything, including managing tokens.
+    #[serde(rename = "admin")]
+    #[value(name = "admin")]
+    Admin,
+}
+
+impl Scope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::ServicesWrite => "services:write",
+            Self::DiscoveryRun => "discovery:run",
+            Self::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub username: String,
+    pub password_hash: String,
+    /// Accounts created before roles existed had full access.
+    #[serde(default)]
+    pub role: Role,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UsersDocument {
+    version: u32,
+    users: Vec<User>,
+}
+
+type Fingerprint = Option<(u64, Option<SystemTime>)>;
+
+/// A JSON document readable only by its owner. Reads are cached until the
+/// file changes, so edits made from the CLI apply without a restart.
+pub struct SecretFile<D> {
+    path: PathBuf,
+    cache: Mutex<Option<(Fingerprint, D)>>,
+}
+
+impl<D: Clone + Default + Serialize + DeserializeOwned> SecretFile<D> {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            cache: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// A missing file reads as the empty document.
+    pub fn load(&self) -> Result<D> {
+        let fingerprint = std::fs::metadata(&self.path)
+            .ok()
+            .map(|metadata| (metadata.len(), metadata.modified().ok()));
+        let mut cache = self.cache.lock().expect("secret file cache poisoned");
+        if let Some((cached, document)) = cache.as_ref() {
+            if *cached == fingerprint {
+                return Ok(document.clone());
+            }
+        }
+
+        let document = match std::fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("failed parsing {}", self.path.display()))?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => D::default(),
+            Err(error) => {
+                return Err(error)
+                    .with_context(|| format!("failed reading {}", self.path.display()))
+            }
+        };
+        *cache = Some((fingerprint, document.clone()));
+        Ok(document)
+    }
+
+    pub fn save(&self, document: &D) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("failed creating {}", parent.display()))?;
+        }
+        let content = serde_json::to_string_pretty(document)?;
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, content)
+            .with_context(|| format!("failed writing {}", temp.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&temp, &self.path)
+            .with_context(|| format!("failed writing {}", self.path.display()))?;
+        *self.cache.lock().expect("secret file cache poisoned") = None;
+        Ok(())
+    }
+}
+
+/// Accounts in a small JSON file that both the server and the `users`
+/// subcommand use.
+pub struct UserStore {
+    file: SecretFile<UsersDocument>,
+}
+
+impl UserStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: SecretFile::new(path),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn load(&self) -> Result<Vec<User>> {
+        Ok(self.file.load()?.users)
+    }
+
+    pub fn find(&self, username: &str) -> Result<Option<User>> {
+        Ok(self
+            .load()?
+            .into_iter()
+            .find(|user| user.username == username))
+    }
+
+    pub fn save(&self, users: &[User]) -> Result<()> {
+        self.file.save(&UsersDocument {
+            version: USERS_VERSION,
+            users: users.to_vec(),
+        })
+    }
+
+    /// Adds a user or replaces an existing user's password. New users get
+    /// `role`; existing users keep theirs.
+    pub fn set_password(&self, username: &str, password: &str, role: Role) -> Result<bool> {
+        validate_username(username)?;
+        if password.chars().count() < 8 {
+            bail!("password must be at least 8 characters");
+        }
+        let password_hash = hash_password(password)?;
+        let mut users = self.load()?;
+        let created = match users.iter_mut().find(|user| user.username == username) {
+            Some(user) => {
+                user.password_hash = password_hash;
+                false
+            }
+            None => {
+                users.push(User {
+                    username: username.to_string(),
+                    password_hash,
+                    role,
+                    created_at: Utc::now(),
+                });
+                true
+            }
+        };
+        users.sort_by(|left, right| left.username.cmp(&right.username));
+        self.save(&users)?;
+        Ok(created)
+    }
+
+    pub fn set_role(&self, username: &str, role: Role) -> Result<bool> {
+        let mut users = self.load()?;
+        let Some(user) = users.iter_mut().find(|user| user.username == username) else {
+            return Ok(false);
+        };
+        user.role = role;
+        self.save(&users)?;
+        Ok(true)
+    }
+
+    pub fn remove(&self, username: &str) -> Result<bool> {
+        let mut users = self.load()?;
+        let before = users.len();
+        users.retain(|user| user.username != username);
+        if users.len() == before {
+            return Ok(false);
+        }
+        self.save(&users)?;
+        Ok(true)
+    }
+}
+
+fn validate_username(username: &str) -> Result<()> {
+    let valid = !username.is_empty()
+        && username.len() <= 64
+        && username
+            .chars()
+            .all(|value| value.is_ascii_alphanumeric() || matches!(value, '.' | '_' | '-' | '@'));
+    if !valid {
+        bail!("username must be 1-64 characters of letters, digits, '.', '_', '-' or '@'");
+    }
+    Ok(())
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|error| anyhow::anyhow!("failed hashing password: {error}"))
+}
+
+pub fn verify_password(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+/// Returns the user if the credentials match. Unknown users still pay for a
+/// hash so response times don't reveal which usernames exist.
+pub fn check_credentials(
+    users: &UserStore,
+    username: &str,
+    password: &str,
+) -> Result<Option<User>> {
+    match users.find(username)? {
+        Some(user) if verify_password(&user.password_hash, password) => Ok(Some(user)),
+        Some(_) => Ok(None),
+        None => {
+            let _ = hash_password(password);
+            Ok(None)
+        }
+    }
+}
+
+/// The authenticated caller, available to handlers as a request extension.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub username: String,
+    pub role: Role,
+    /// Set for API tokens, which are limited to these scopes.
+    pub scopes: Option<Vec<Scope>>,
+    /// Filled in from `[auth.visibility]` for restricted users.
+    pub visibility: Option<Visibility>,
+}
+
+impl Identity {
+    pub fn allows(&self, scope: Scope) -> bool {
+        match &self.scopes {
+            Some(scopes) => {
+                scope == Scope::Read || scopes.contains(&scope) || scopes.contains(&Scope::Admin)
+            }
+            None => self.role.grants(scope),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    username: String,
+    /// Hash at login time; changing the password ends existing sessions.
+    password_hash: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// In-memory sessions. A restart logs everybody out.
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: RwLock<HashMap<String, Session>>,
+}
+
+impl SessionStore {
+    pub async fn create(&self, user: &User, ttl: Duration) -> (String, DateTime<Utc>) {
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        let expires_at = Utc::now() + ttl;
+
+        let mut sessions = self.sessions.write().await;
+        let now = Utc::now();
+        sessions.retain(|_, session| session.expires_at > now);
+        sessions.insert(
+            token.clone(),
+            Session {
+                username: user.username.clone(),
+                password_hash: user.password_hash.clone(),
+                expires_at,
+            },
+        );
+        (token, expires_at)
+    }
+
+    pub async fn remove(&self, token: &str) {
+        self.sessions.write().await.remove(token);
+    }
+
+    async fn resolve(&self, token: &str, users: &UserStore) -> Option<Identity> {
+        let session = self.sessions.read().await.get(token).cloned()?;
+        if session.expires_at <= Utc::now() {
+            self.remove(token).await;
+            return None;
+        }
+        let user = users.find(&session.username).ok().flatten()?;
+        if user.password_hash != session.password_hash {
+            self.remove(token).await;
+            return None;
+        }
+        Some(Identity {
+            username: user.username,
+            role: user.role,
+            scopes: None,
+            visibility: None,
+        })
+    }
+}
+
+pub fn session_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+pub fn session_cookie(token: &str, max_age_secs: i64, auth: &AuthConfig) -> String {
+    let mut cookie =
+        format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age_secs}");
+    if let Some(domain) = &auth.cookie_domain {
+        cookie.push_str(&format!("; Domain={domain}"));
+    }
+    if auth.secure_cookie {
+        cookie.push_str("; Secure");
+    }
+    cookie
+}
+
+/// Identity from the proxy's headers, if the peer is a trusted proxy and
+/// sent a user. `Err` means the user has no navigator role.
+fn proxy_identity(
+    proxy: &ProxyAuthConfig,
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+) -> Result<Option<Identity>, String> {
+    if !peer.is_some_and(|peer| proxy.trusts(peer)) {
+        return Ok(None);
+    }
+    let Some(username) = headers
+        .get(proxy.user_header.as_str())
+        .and_then(|value| value.to_str().ok())
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+    else {
+        return Ok(None);
+    };
+    let groups = headers
+        .get(proxy.groups_header.as_str())
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    match proxy.role_for(groups) {
+        Some(role) => Ok(Some(Identity {
+            username: username.to_string(),
+            role,
+            scopes: None,
+            visibility: None,
+        })),
+        None => Err(format!("{username} has no navigator role")),
+    }
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Resolves the caller from an API token, a session cookie or trusted proxy
+/// headers. `Err` carries the status to answer with.
+pub async fn identify(
+    state: &AppState,
+    auth: &AuthConfig,
+    headers: &HeaderMap,
+    peer: Option<IpAddr>,
+) -> Result<Option<Identity>, (StatusCode, String)> {
+    if let Some(token) = bearer_token(headers) {
+        return match state.tokens.authenticate(token) {
+            Ok(Some(token)) => Ok(Some(token.identity())),
+            Ok(None) => Err((
+                StatusCode::UNAUTHORIZED,
+                "invalid or expired token".to_string(),
+            )),
+            Err(error) => Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to read tokens: {error:#}"),
+            )),
+        };
+    }
+    if let Some(token) = session_token(headers) {
+        if let Some(identity) = state.sessions.resolve(&token, &state.users).await {
+            return Ok(Some(identity));
+        }
+    }
+    match &auth.proxy {
+        Some(proxy) => {
+            proxy_identity(proxy, headers, peer).map_err(|message| (StatusCode::FORBIDDEN, message))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn deny(status: StatusCode, message: impl Into<String>) -> Response {
+    (
+        status,
+        Json(ApiError {
+            message: message.into(),
+        }),
+    )
+        .into_response()
+}
+
+/// A 401 or 403 from a permission check.
+#[derive(Debug)]
+pub struct AccessDenied {
+    status: StatusCode,
+    message: String,
+}
+
+impl AccessDenied {
+    fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AccessDenied> for Response {
+    fn from(denied: AccessDenied) -> Self {
+        deny(denied.status, denied.message)
+    }
+}
+
+/// Who is making an API request. Attached by [`require_login`]; handlers
+/// extract it to check scopes and filter services.
+#[derive(Debug, Clone)]
+pub enum Caller {
+    /// Auth is disabled, so everything is allowed.
+    Unrestricted,
+    /// Not logged in; only reached for public endpoints and anonymous reads.
+    Anonymous,
+    Identified(Identity),
+}
+
+impl Caller {
+    pub fn identity(&self) -> Option<&Identity> {
+        match self {
+            Self::Identified(identity) => Some(identity),
+            _ => None,
+        }
+    }
+
+    /// Username to record for a change, when the caller is logged in.
+    pub fn actor(&self) -> Option<&str> {
+        self.identity().map(|identity| identity.username.as_str())
+    }
+
+    pub fn visibility(&self) -> Option<&Visibility> {
+        self.identity()
+            .and_then(|identity| identity.visibility.as_ref())
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), AccessDenied> {
+        match self {
+            Self::Unrestricted => Ok(()),
+            Self::Anonymous if scope == Scope::Read => Ok(()),
+            Self::Anonymous => Err(AccessDenied::new(
+                StatusCode::UNAUTHORIZED,
+                "login required",
+            )),
+            Self::Identified(identity) if identity.allows(scope) => Ok(()),
+            Self::Identified(identity) => {
+                let message = match (&identity.scopes, scope) {
+                    (Some(_), _) => format!("token lacks the {} scope", scope.as_str()),
+                    (None, Scope::Admin) => "admin role required".to_string(),
+                    (None, _) => "editor role required".to_string(),
+                };
+                Err(AccessDenied::new(StatusCode::FORBIDDEN, message))
+            }
+        }
+    }
+
+    /// Like [`Caller::require`], for operations on the whole configuration
+    /// that a user with limited visibility must not see or replace.
+    pub fn require_everything(&self, scope: Scope) -> Result<(), AccessDenied> {
+        self.require(scope)?;
+        match self.visibility() {
+            Some(_) => Err(AccessDenied::new(
+                StatusCode::FORBIDDEN,
+                "not available to users with limited visibility",
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Caller>()
+            .cloned()
+            .unwrap_or(Caller::Anonymous))
+    }
+}
+
+/// Enforces `[auth]` on API routes and attaches the [`Caller`]. Handlers
+/// check the scope they need.
+pub async fn require_login(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let auth = state.settings.read().await.auth.clone();
+    if !auth.enabled {
+        request.extensions_mut().insert(Caller::Unrestricted);
+        return next.run(request).await;
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip());
+    let identity = match identify(&state, &auth, request.headers(), peer).await {
+        Ok(identity) => identity,
+        Err((status, message)) => return deny(status, message),
+    };
+    let is_read = matches!(*request.method(), Method::GET | Method::HEAD);
+    let is_public = PUBLIC_PATHS.contains(&request.uri().path());
+
+    let caller = match identity {
+        Some(mut identity) => {
+            identity.visibility = auth.visibility.get(&identity.username).cloned();
+            Caller::Identified(identity)
+        }
+        None if is_public || (is_read && auth.anonymous_read) => Caller::Anonymous,
+        None => return deny(StatusCode::UNAUTHORIZED, "login required"),
+    };
+    request.extensions_mut().insert(caller);
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_file_round_trip_and_password_change() {
+        let path = std::env::temp_dir().join(format!(
+            "navigator-users-{}.json",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = UserStore::new(&path);
+        assert!(store
+            .set_password("alice", "correct horse", Role::Admin)
+            .expect("add"));
+        let valid = |name: &str, password: &str| {
+            check_credentials(&store, name, password)
+                .expect("check")
+                .is_some()
+        };
+        assert!(valid("alice", "correct horse"));
+        assert!(!valid("alice", "wrong password"));
+        assert!(!valid("bob", "correct horse"));
+
+        assert!(!store
+            .set_password("alice", "battery staple", Role::Viewer)
+            .expect("update"));
+        assert!(valid("alice", "battery staple"));
+        assert_eq!(
+            store.find("alice").expect("find").map(|user| user.role),
+            Some(Role::Admin)
+        );
+        assert!(store
+            .set_password("bad name", "long enough", Role::Viewer)
+            .is_err());
+        assert!(store.set_password("carol", "short", Role::Viewer).is_err());
+
+        assert!(store.remove("alice").expect("remove"));
+        assert!(store.load().expect("load").is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn proxy_headers_only_trusted_from_configured_peers() {
+        let proxy = ProxyAuthConfig {
+            trusted_proxies: vec!["10.0.0.0/8".to_string(), "::1".to_string()],
+            group_roles: BTreeMap::from([
+                ("admins".to_string(), Role::Admin),
+                ("family".to_string(), Role::Viewer),
+            ]),
+            ..Default::default()
+        };
+        proxy.validate().expect("valid proxy config");
+        let mut headers = HeaderMap::new();
+        headers.insert("remote-user", "bob".parse().expect("header"));
+        headers.insert("remote-groups", "family, admins".parse().expect("header"));
+
+        let trusted = proxy_identity(&proxy, &headers, Some("10.1.2.3".parse().expect("ip")));
+        assert_eq!(
+            trusted,
+            Ok(Some(Identity {
+                username: "bob".to_string(),
+                role: Role::Admin,
+                scopes: None,
+                visibility: None,
+            }))
+        );
+        let spoofed = proxy_identity(&proxy, &headers, Some("192.168.1.5".parse().expect("ip")));
+        assert_eq!(spoofed, Ok(None));
+
+        headers.insert("remote-groups", "guests".parse().expect("header"));
+        assert!(proxy_identity(&proxy, &headers, Some("::1".parse().expect("ip"))).is_err());
+    }
+}
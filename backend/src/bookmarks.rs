@@ -0,0 +1,268 @@
+use crate::{
+    groups::{self, Locale},
+    importers::{link_request, Link},
+    models::{CreateServiceRequest, ServiceEntry, ServiceLink},
+    networks::Network,
+};
+use regex::Regex;
+
+/// One group's bookmarks: a service, or one of its links, and the URL.
+type Folder<'a> = (
+    Option<&'a str>,
+    Vec<(&'a ServiceEntry, Option<&'a ServiceLink>, String)>,
+);
+
+const HEADER: &str = "<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV=\"Content-Type\" CONTENT=\"text/html; charset=UTF-8\">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks</H1>
+";
+
+/// Renders services as a Netscape bookmarks file with one folder per group,
+/// named in `locale`, with URLs as opened from `network`. Each link of a
+/// service gets its own bookmark after the service's; services without any
+/// resolvable URL are skipped.
+pub fn render(services: &[ServiceEntry], locale: Locale, network: Option<&Network>) -> String {
+    let mut folders: Vec<Folder> = Vec::new();
+    for service in services {
+        let main = service
+            .resolved_url(network)
+            .map(|url| (service, None, url));
+        let links = service
+            .links
+            .iter()
+            .filter_map(|link| Some((service, Some(link), service.link_url(link, network)?)));
+        let bookmarks: Vec<_> = main.into_iter().chain(links).collect();
+        if bookmarks.is_empty() {
+            continue;
+        }
+        let group = service
+            .group
+            .as_deref()
+            .map(|group| groups::label(group, locale));
+        match folders.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, members)) => members.extend(bookmarks),
+            None => folders.push((group, bookmarks)),
+        }
+    }
+    // Ungrouped services sit at the top level, after the group folders.
+    folders.sort_by_key(|(name, _)| name.is_none());
+
+    let mut output = String::from(HEADER);
+    output.push_str("<DL><p>\n");
+    for (name, members) in folders {
+        let indent = match name {
+            Some(name) => {
+                output.push_str(&format!("    <DT><H3>{}</H3>\n", escape_html(name)));
+                output.push_str("    <DL><p>\n");
+                "        "
+            }
+            None => "    ",
+        };
+        for (service, link, url) in members {
+            output.push_str(&format!(
+                "{indent}<DT><A HREF=\"{}\" ADD_DATE=\"{}\"",
+                escape_html(&url),
+                service.updated_at.timestamp()
+            ));
+            if !service.tags.is_empty() {
+                output.push_str(&format!(
+                    " TAGS=\"{}\"",
+                    escape_html(&service.tags.join(","))
+                ));
+            }
+            let title = match link {
+                Some(link) => format!("{} - {}", service.display_name, link.name),
+                None => service.display_name.clone(),
+            };
+            output.push_str(&format!(">{}</A>\n", escape_html(&title)));
+            if let (None, Some(description)) = (link, service.description.as_deref()) {
+                output.push_str(&format!("{indent}<DD>{}\n", escape_html(description)));
+            }
+        }
+        if name.is_some() {
+            output.push_str("    </DL><p>\n");
+        }
+    }
+    output.push_str("</DL><p>\n");
+    output
+}
+
+/// Reads http(s) links from a bookmarks file. The innermost folder becomes
+/// the group. With `folder`, only links somewhere below a folder of that
+/// name are read, and that folder itself does not count as a group.
+pub fn parse(content: &str, folder: Option<&str>) -> Vec<CreateServiceRequest> {
+    let token = Regex::new(
+        r#"(?is)<h3[^>]*>(?P<folder>.*?)</h3>|<a\s(?P<attrs>[^>]*)>(?P<title>.*?)</a>|(?P<close></dl>)|<dd>(?P<description>[^<]*)"#,
+    )
+    .expect("bookmark token regex");
+    let href = Regex::new(r#"(?is)\bhref\s*=\s*"([^"]*)""#).expect("href regex");
+    let tags = Regex::new(r#"(?is)\btags\s*=\s*"([^"]*)""#).expect("tags regex");
+
+    let mut stack: Vec<String> = Vec::new();
+    let mut links: Vec<Link> = Vec::new();
+    // Whether the most recent token was a link that a <DD> may describe.
+    let mut last_was_link = false;
+
+    for capture in token.captures_iter(content) {
+        if let Some(name) = capture.name("folder") {
+            stack.push(unescape_html(name.as_str().trim()));
+            last_was_link = false;
+        } else if capture.name("close").is_some() {
+            stack.pop();
+            last_was_link = false;
+        } else if let Some(description) = capture.name("description") {
+            if last_was_link {
+                if let Some(link) = links.last_mut() {
+                    let text = unescape_html(description.as_str().trim());
+                    link.description = Some(text).filter(|value| !value.is_empty());
+                }
+            }
+            last_was_link = false;
+        } else if let (Some(attrs), Some(title)) = (capture.name("attrs"), capture.name("title")) {
+            last_was_link = false;
+            let Some(url) = href
+                .captures(attrs.as_str())
+                .and_then(|value| value.get(1))
+                .map(|value| unescape_html(value.as_str()))
+                .filter(|url| url.starts_with("http://") || url.starts_with("https://"))
+            else {
+                continue;
+            };
+
+            let group = match folder {
+                Some(folder) => match stack.iter().position(|name| name == folder) {
+                    Some(index) => stack[index + 1..].last().cloned(),
+                    None => continue,
+                },
+                None => stack.last().cloned(),
+            };
+            let tags = tags
+                .captures(attrs.as_str())
+                .and_then(|value| value.get(1))
+                .map(|value| {
+                    unescape_html(value.as_str())
+                        .split(',')
+                        .map(|tag| tag.trim().to_string())
+                        .filter(|tag| !tag.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            links.push(Link {
+                name: unescape_html(title.as_str().trim()),
+                url: Some(url),
+                group,
+                description: None,
+                icon: None,
+                tags,
+                unit: None,
+            });
+            last_was_link = true;
+        }
+    }
+
+    links.into_iter().map(link_request).collect()
+}
+
+pub(crate) fn escape_html(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_html(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str, port: u16, group: Option<&str>) -> ServiceEntry {
+        CreateServiceRequest {
+            service_name: name.to_string(),
+            port: Some(port),
+            group: group.map(str::to_string),
+            description: Some(format!("{name} & friends")),
+            tags: Some(vec!["lan".to_string()]),
+            ..Default::default()
+        }
+        .into_entry("server.lan")
+    }
+
+    #[test]
+    fn round_trip_keeps_groups_and_urls() {
+        let mut grafana = service("grafana", 3000, Some("monitoring"));
+        grafana.links.push(ServiceLink {
+            name: "Metrics".to_string(),
+            port: Some(9090),
+            path: Some("metrics".to_string()),
+            ..Default::default()
+        });
+        let services = vec![
+            service("jellyfin", 8096, Some("影音")),
+            grafana,
+            service("router", 80, None),
+            service("ssh", 22, Some("system")),
+        ];
+        let english = render(&services, Locale::En, None);
+        assert!(english.contains("<DT><H3>Media</H3>"));
+        let imported = parse(&english, None)[0].clone().into_entry("server.lan");
+        assert_eq!(imported.group.as_deref(), Some("media"));
+        let html = render(&services, Locale::Zh, None);
+        assert!(html.contains("<DT><H3>影音</H3>"));
+        assert!(html.contains("<DT><H3>监控</H3>"));
+        assert!(!html.contains(":22"));
+        assert!(!html.contains("系统"));
+
+        assert!(html.contains("http://server.lan:9090/metrics\" ADD_DATE"));
+        assert!(html.contains(">Grafana - Metrics</A>"));
+
+        let requests = parse(&html, None);
+        assert_eq!(requests.len(), 4);
+        let jellyfin = &requests[0];
+        assert_eq!(jellyfin.display_name.as_deref(), Some("Jellyfin"));
+        assert_eq!(jellyfin.group.as_deref(), Some("影音"));
+        assert_eq!(jellyfin.port, Some(8096));
+        assert_eq!(jellyfin.description.as_deref(), Some("jellyfin & friends"));
+        assert_eq!(jellyfin.tags.as_deref(), Some(&["lan".to_string()][..]));
+        assert_eq!(requests[3].group, None);
+    }
+
+    #[test]
+    fn folder_filter_selects_subtree() {
+        let html = r#"<DL><p>
+<DT><H3>News</H3>
+<DL><p>
+<DT><A HREF="https://example.com">Example</A>
+</DL><p>
+<DT><H3>Homelab</H3>
+<DL><p>
+<DT><A HREF="http://nas.lan:5000">NAS</A>
+<DT><H3>Media</H3>
+<DL><p>
+<DT><A HREF="http://server.lan:8096/web/">Jellyfin</A>
+<DT><A HREF="place:sort=8">Recent</A>
+</DL><p>
+</DL><p>
+</DL><p>"#;
+
+        let requests = parse(html, Some("Homelab"));
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].group, None);
+        assert_eq!(requests[0].host.as_deref(), Some("nas.lan"));
+        assert_eq!(requests[1].group.as_deref(), Some("Media"));
+        assert_eq!(requests[1].path.as_deref(), Some("/web/"));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::{
+    groups::{self, GroupSettings},
+    models::{
+        assign_positions, normalize_ignored, unique_service_id, CreateServiceRequest, ServiceEntry,
+    },
+    store::StoreMeta,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+
+pub const BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleFormat {
+    #[default]
+    Json,
+    Yaml,
+}
+
+impl BundleFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "json" => Some(Self::Json),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Json => "application/json; charset=utf-8",
+            Self::Yaml => "application/yaml; charset=utf-8",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Yaml => "yaml",
+        }
+    }
+}
+
+/// Portable snapshot of a navigator: services plus the settings needed to
+/// make sense of them on another server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    #[serde(default = "default_bundle_version")]
+    pub version: u32,
+    #[serde(default)]
+    pub exported_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub settings: BundleSettings,
+    /// Group order, icons, descriptions and collapsed state. Bundles from
+    /// earlier versions list bare group names.
+    #[serde(default, deserialize_with = "deserialize_groups")]
+    pub groups: Vec<GroupSettings>,
+    #[serde(default)]
+    pub ignored: Vec<String>,
+    #[serde(default)]
+    pub services: Vec<ServiceEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BundleSettings {
+    /// Host the exporting server used for service URLs. Services pointing at
+    /// it are rewritten to the importing server's default host.
+    #[serde(default)]
+    pub default_host: Option<String>,
+}
+
+fn default_bundle_version() -> u32 {
+    BUNDLE_VERSION
+}
+
+fn deserialize_groups<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<GroupSettings>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BundleGroup {
+        Name(String),
+        Settings(GroupSettings),
+    }
+    let groups = Vec::<BundleGroup>::deserialize(deserializer)?;
+    Ok(groups
+        .into_iter()
+        .map(|group| match group {
+            BundleGroup::Name(key) => GroupSettings {
+                key,
+                ..Default::default()
+            },
+            BundleGroup::Settings(settings) => settings,
+        })
+        .collect())
+}
+
+impl ConfigBundle {
+    pub fn new(services: &[ServiceEntry], meta: &StoreMeta, default_host: &str) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            exported_at: Some(Utc::now()),
+            settings: BundleSettings {
+                default_host: Some(default_host.to_string()),
+            },
+            groups: meta.groups.clone(),
+            ignored: meta.ignored.clone(),
+            services: services.to_vec(),
+        }
+    }
+
+    /// Wraps services read from another dashboard so they go through the
+    /// same merge/replace logic as a native bundle.
+    pub fn from_requests(requests: Vec<CreateServiceRequest>, default_host: &str) -> Self {
+        Self {
+            version: BUNDLE_VERSION,
+            exported_at: None,
+            settings: BundleSettings::default(),
+            groups: Vec::new(),
+            ignored: Vec::new(),
+            services: requests
+                .into_iter()
+                .map(|request| request.into_entry(default_host))
+                .collect(),
+        }
+    }
+
+    pub fn parse(content: &str, format: BundleFormat) -> Result<Self> {
+        let bundle: Self = match format {
+            BundleFormat::Json => {
+                serde_json::from_str(content).context("failed to parse JSON bundle")?
+            }
+            BundleFormat::Yaml => {
+                serde_yaml::from_str(content).context("failed to parse YAML bundle")?
+            }
+        };
+        if bundle.version > BUNDLE_VERSION {
+            bail!(
+                "bundle version {} is newer than supported version {BUNDLE_VERSION}",
+                bundle.version
+            );
+        }
+        Ok(bundle)
+    }
+
+    pub fn render(&self, format: BundleFormat) -> Result<String> {
+        match format {
+            BundleFormat::Json => {
+                serde_json::to_string_pretty(self).context("failed to serialize bundle")
+            }
+            BundleFormat::Yaml => serde_yaml::to_string(self).context("failed to serialize bundle"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// Add new services and update matching ones, keep everything else.
+    #[default]
+    Merge,
+    /// Make the stored state exactly the bundle.
+    Replace,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub dry_run: bool,
+    pub added: Vec<String>,
+    pub updated: Vec<ServiceChange>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    pub renamed: Vec<RenamedService>,
+    pub ignored: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceChange {
+    pub id: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenamedService {
+    pub from: String,
+    pub to: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportPlan {
+    pub services: Vec<ServiceEntry>,
+    pub meta: StoreMeta,
+    pub report: ImportReport,
+}
+
+pub fn plan_import(
+    current: &[ServiceEntry],
+    meta: &StoreMeta,
+    bundle: ConfigBundle,
+    mode: ImportMode,
+    default_host: &str,
+) -> ImportPlan {
+    let mut report = ImportReport {
+        mode,
+        ..Default::default()
+    };
+    let source_host = bundle.settings.default_host.clone();
+    let mut services: Vec<ServiceEntry> = match mode {
+        ImportMode::Merge => current.to_vec(),
+        ImportMode::Replace => Vec::new(),
+    };
+
+    // Positions are the exporting server's: a replace renumbers them in its
+    // manual order, a merge appends new services after the current ones.
+    let mut incoming_services = bundle.services;
+    incoming_services.sort_by_key(|entry| entry.position.unwrap_or(u32::MAX));
+    for (index, entry) in incoming_services.iter_mut().enumerate() {
+        entry.position = match mode {
+            ImportMode::Merge => None,
+            ImportMode::Replace => u32::try_from(index).ok(),
+        };
+    }
+
+    for mut incoming in incoming_services {
+        if incoming.host.trim().is_empty() || Some(&incoming.host) == source_host.as_ref() {
+            incoming.host = default_host.to_string();
+        }
+        // Bundles from earlier versions carry Chinese group names.
+        groups::migrate(&mut incoming.group);
+
+        let slot = services.iter().position(|entry| entry.id == incoming.id);
+        match slot {
+            Some(index) if services[index].service_name == incoming.service_name => {
+                // Only reachable in merge mode or with duplicate ids in the bundle.
+                incoming.position = services[index].position;
+                let fields = changed_fields(&services[index], &incoming);
+                if fields.is_empty() {
+                    report.unchanged.push(incoming.id.clone());
+                } else {
+                    incoming.updated_at = Utc::now();
+                    report.updated.push(ServiceChange {
+                        id: incoming.id.clone(),
+                        fields,
+                    });
+                    services[index] = incoming;
+                }
+            }
+            Some(_) => {
+                let id = unique_service_id(&services, &incoming.id);
+                report.renamed.push(RenamedService {
+                    from: incoming.id.clone(),
+                    to: id.clone(),
+                });
+                incoming.id = id;
+                report.added.push(incoming.id.clone());
+                services.push(incoming);
+            }
+            None => {
+                match current.iter().find(|entry| entry.id == incoming.id) {
+                    Some(existing) if existing.service_name == incoming.service_name => {
+                        let fields = changed_fields(existing, &incoming);
+                        if fields.is_empty() {
+                            report.unchanged.push(incoming.id.clone());
+                        } else {
+                            report.updated.push(ServiceChange {
+                                id: incoming.id.clone(),
+                                fields,
+                            });
+                        }
+                    }
+                    _ => report.added.push(incoming.id.clone()),
+                }
+                services.push(incoming);
+            }
+        }
+    }
+
+    if mode == ImportMode::Replace {
+        report.removed = current
+            .iter()
+            .filter(|entry| !services.iter().any(|value| value.id == entry.id))
+            .map(|entry| entry.id.clone())
+            .collect();
+    }
+
+    let mut ignored = match mode {
+        ImportMode::Merge => meta.ignored.clone(),
+        ImportMode::Replace => Vec::new(),
+    };
+    ignored.extend(bundle.ignored);
+    normalize_ignored(&mut ignored);
+    report.ignored = ignored.clone();
+
+    let mut group_settings = match mode {
+        ImportMode::Merge => meta.groups.clone(),
+        ImportMode::Replace => Vec::new(),
+    };
+    for mut incoming in bundle.groups {
+        incoming.key = groups::canonical(incoming.key.trim()).to_string();
+        if incoming.key.is_empty() {
+            continue;
+        }
+        match group_settings
+            .iter_mut()
+            .find(|group| group.key == incoming.key)
+        {
+            Some(existing) => *existing = incoming,
+            None => group_settings.push(incoming),
+        }
+    }
+
+    assign_positions(&mut services);
+    ImportPlan {
+        services,
+        meta: StoreMeta {
+            ignored,
+            groups: group_settings,
+        },
+        report,
+    }
+}
+
+const BOOKKEEPING_FIELDS: &[&str] = &["updated_at", "created_at", "last_used_at", "launch_count"];
+
+/// Top-level field names that differ between two entries, ignoring
+/// bookkeeping timestamps and usage counts.
+pub(crate) fn changed_fields(before: &ServiceEntry, after: &ServiceEntry) -> Vec<String> {
+    let (Ok(serde_json::Value::Object(before)), Ok(serde_json::Value::Object(after))) =
+        (serde_json::to_value(before), serde_json::to_value(after))
+    else {
+        return Vec::new();
+    };
+
+    let mut fields: Vec<String> = after
+        .iter()
+        .filter(|(key, _)| !BOOKKEEPING_FIELDS.contains(&key.as_str()))
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    fields.sort();
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(name: &str) -> ServiceEntry {
+        CreateServiceRequest {
+            service_name: name.to_string(),
+            port: Some(8080),
+            group: Some("media".to_string()),
+            ..Default::default()
+        }
+        .into_entry("old.lan")
+    }
+
+    fn bundle(services: Vec<ServiceEntry>) -> ConfigBundle {
+        ConfigBundle::new(&services, &StoreMeta::default(), "old.lan")
+    }
+
+    #[test]
+    fn merge_updates_matching_and_renames_conflicts() {
+        let current = vec![service("jellyfin"), service("sonarr")];
+        let mut changed = service("jellyfin");
+        changed.port = Some(8096);
+        let mut conflict = service("other");
+        conflict.id = "sonarr".to_string();
+
+        let plan = plan_import(
+            &current,
+            &StoreMeta::default(),
+            bundle(vec![changed, conflict]),
+            ImportMode::Merge,
+            "new.lan",
+        );
+
+        assert_eq!(plan.services.len(), 3);
+        assert_eq!(plan.report.updated.len(), 1);
+        assert!(plan.report.updated[0].fields.contains(&"port".to_string()));
+        assert!(plan.report.updated[0].fields.contains(&"host".to_string()));
+        assert_eq!(plan.report.renamed.len(), 1);
+        assert_ne!(plan.report.renamed[0].to, "sonarr");
+        let jellyfin = plan
+            .services
+            .iter()
+            .find(|entry| entry.id == "jellyfin")
+            .expect("jellyfin should exist");
+        assert_eq!(jellyfin.host, "new.lan");
+    }
+
+    #[test]
+    fn merge_appends_new_services_in_the_bundle_order() {
+        let mut current = vec![service("sonarr"), service("radarr")];
+        assign_positions(&mut current);
+        let mut exported = vec![service("sonarr"), service("jellyfin"), service("bazarr")];
+        for (entry, position) in exported.iter_mut().zip([5, 1, 0]) {
+            entry.position = Some(position);
+        }
+
+        let plan = plan_import(
+            &current,
+            &StoreMeta::default(),
+            bundle(exported),
+            ImportMode::Merge,
+            "old.lan",
+        );
+
+        let mut order: Vec<(&str, Option<u32>)> = plan
+            .services
+            .iter()
+            .map(|entry| (entry.id.as_str(), entry.position))
+            .collect();
+        order.sort_by_key(|(_, position)| *position);
+        assert_eq!(
+            order,
+            vec![
+                ("sonarr", Some(0)),
+                ("radarr", Some(1)),
+                ("bazarr", Some(2)),
+                ("jellyfin", Some(3)),
+            ]
+        );
+        assert_eq!(plan.report.unchanged, vec!["sonarr".to_string()]);
+    }
+
+    #[test]
+    fn replace_reports_removed_services() {
+        let mut current = vec![service("jellyfin"), service("sonarr")];
+        assign_positions(&mut current);
+        let mut incoming = bundle(vec![service("jellyfin")]);
+        incoming.ignored = vec![" getty ".to_string(), String::new(), "getty".to_string()];
+
+        let plan = plan_import(
+            &current,
+            &StoreMeta::default(),
+            incoming,
+            ImportMode::Replace,
+            "old.lan",
+        );
+
+        assert_eq!(plan.services.len(), 1);
+        assert_eq!(plan.report.removed, vec!["sonarr".to_string()]);
+        assert_eq!(plan.report.unchanged, vec!["jellyfin".to_string()]);
+        assert_eq!(plan.meta.ignored, vec!["getty".to_string()]);
+    }
+
+    #[test]
+    fn yaml_round_trip_keeps_services() {
+        let original = bundle(vec![service("grafana")]);
+        let yaml = original.render(BundleFormat::Yaml).expect("render");
+        let parsed = ConfigBundle::parse(&yaml, BundleFormat::Yaml).expect("parse");
+        assert_eq!(parsed.services, original.services);
+        assert_eq!(parsed.groups, original.groups);
+
+        let legacy: ConfigBundle =
+            serde_json::from_str(r#"{"groups": ["影音", "Homelab"]}"#).expect("legacy bundle");
+        let plan = plan_import(
+            &[],
+            &StoreMeta::default(),
+            legacy,
+            ImportMode::Merge,
+            "new.lan",
+        );
+        let keys: Vec<&str> = plan
+            .meta
+            .groups
+            .iter()
+            .map(|group| group.key.as_str())
+            .collect();
+        assert_eq!(keys, vec!["media", "Homelab"]);
+    }
+}
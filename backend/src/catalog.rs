@@ -0,0 +1,256 @@
+use crate::{
+    models::{
+        humanize_service_name, CatalogQuery, CreateServiceRequest, ServiceEntry, ServiceProtocol,
+    },
+    rules::{Rule, Subject},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+const CATALOG: &str = include_str!("catalog.toml");
+
+/// A well-known self-hosted app.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CatalogApp {
+    pub id: String,
+    pub name: String,
+    /// Other unit or process names, besides `id`.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    #[serde(default)]
+    pub ports: Vec<u16>,
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Only set when the first port does not imply it.
+    #[serde(default)]
+    pub protocol: Option<ServiceProtocol>,
+    pub icon: String,
+    /// A built-in group key.
+    pub category: String,
+    pub description: String,
+    /// Path that answers 2xx while the app is healthy.
+    #[serde(default)]
+    pub health: Option<String>,
+    /// Infrastructure such as databases and proxies, kept off the dashboard.
+    #[serde(default)]
+    pub hidden: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CatalogFile {
+    apps: Vec<CatalogApp>,
+}
+
+impl CatalogApp {
+    /// The rule discovery applies to units of this app.
+    pub fn rule(&self) -> Rule {
+        Rule {
+            name: Some(self.id.clone()),
+            group: Some(self.category.clone()),
+            icon: Some(self.icon.clone()),
+            hidden: Some(self.hidden),
+            path: self.path.clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Replaces the generated display name and fills in the description.
+    pub fn enrich(&self, entry: &mut ServiceEntry) {
+        if entry.display_name == humanize_service_name(&entry.service_name) {
+            entry.display_name = self.name.clone();
+        }
+        if entry.description.is_none() {
+            entry.description = Some(self.description.clone());
+        }
+    }
+
+    /// Fills in every field the request leaves out.
+    pub fn prefill(&self, request: &mut CreateServiceRequest) {
+        if request.service_name.trim().is_empty() {
+            request.service_name = self.id.clone();
+        }
+        request
+            .display_name
+            .get_or_insert_with(|| self.name.clone());
+        request
+            .description
+            .get_or_insert_with(|| self.description.clone());
+        if request.port.is_none() {
+            request.port = self.ports.first().copied();
+        }
+        if request.protocol.is_none() {
+            request.protocol = self.protocol.clone();
+        }
+        if request.path.is_none() {
+            request.path = self.path.clone();
+        }
+        request.icon.get_or_insert_with(|| self.icon.clone());
+        request.group.get_or_insert_with(|| self.category.clone());
+        request.hidden.get_or_insert(self.hidden);
+    }
+
+    /// Length in words of the longest name that appears in `words`.
+    fn score(&self, words: &[Vec<String>]) -> Option<usize> {
+        std::iter::once(&self.id)
+            .chain(&self.aliases)
+            .map(|name| split_words(name))
+            .filter(|name| {
+                words.iter().any(|words| {
+                    words
+                        .windows(name.len())
+                        .any(|window| window == name.as_slice())
+                })
+            })
+            .map(|name| name.len())
+            .max()
+    }
+
+    fn matches_query(&self, query: &CatalogQuery) -> bool {
+        let text = query.q.as_deref().map(str::to_lowercase);
+        query
+            .category
+            .as_deref()
+            .map_or(true, |category| category == self.category)
+            && text.as_deref().map_or(true, |text| {
+                std::iter::once(&self.id)
+                    .chain(std::iter::once(&self.name))
+                    .chain(&self.aliases)
+                    .chain(std::iter::once(&self.description))
+                    .any(|value| value.to_lowercase().contains(text))
+            })
+    }
+}
+
+fn split_words(value: &str) -> Vec<String> {
+    value
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn apps() -> &'static [CatalogApp] {
+    static APPS: OnceLock<Vec<CatalogApp>> = OnceLock::new();
+    APPS.get_or_init(|| {
+        toml::from_str::<CatalogFile>(CATALOG)
+            .expect("catalog should parse")
+            .apps
+    })
+}
+
+pub fn get(id: &str) -> Option<&'static CatalogApp> {
+    apps().iter().find(|app| app.id == id)
+}
+
+/// The app whose id, alias or name is `name`, ignoring case and anything
+/// but letters and digits, so `Home Assistant` and `homeassistant` both find
+/// `home-assistant`.
+pub fn named(name: &str) -> Option<&'static CatalogApp> {
+    let key = |name: &str| -> String {
+        name.chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|character| character.to_ascii_lowercase())
+            .collect()
+    };
+    let wanted = key(name);
+    if wanted.is_empty() {
+        return None;
+    }
+    apps().iter().find(|app| {
+        std::iter::once(&app.id)
+            .chain(&app.aliases)
+            .chain(std::iter::once(&app.name))
+            .any(|name| key(name) == wanted)
+    })
+}
+
+pub fn search(query: &CatalogQuery) -> Vec<CatalogApp> {
+    apps()
+        .iter()
+        .filter(|app| app.matches_query(query))
+        .cloned()
+        .collect()
+}
+
+/// The app a unit or its process is named after, with its 1-based catalog
+/// position. Units without a listening port are skipped, so helpers such as
+/// `nextcloud-cron` stay system services.
+pub fn find(subject: &Subject) -> Option<(usize, &'static CatalogApp)> {
+    subject.port?;
+    let unit = subject.unit.trim().to_lowercase();
+    let words: Vec<Vec<String>> = std::iter::once(unit.trim_end_matches(".service"))
+        .chain(subject.process)
+        .map(split_words)
+        .collect();
+
+    let mut best: Option<(usize, usize, &CatalogApp)> = None;
+    for (index, app) in apps().iter().enumerate() {
+        if let Some(score) = app.score(&words) {
+            if best.map_or(true, |(best_score, _, _)| score > best_score) {
+                best = Some((score, index + 1, app));
+            }
+        }
+    }
+    best.map(|(_, index, app)| (index, app))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups;
+    use std::collections::HashSet;
+
+    #[test]
+    fn catalog_parses_with_unique_ids_and_known_categories() {
+        let apps = apps();
+        assert!(apps.len() >= 200);
+        let mut ids = HashSet::new();
+        for app in apps {
+            assert!(ids.insert(app.id.as_str()), "duplicate id {}", app.id);
+            assert!(
+                groups::is_builtin(&app.category),
+                "unknown category {} for {}",
+                app.category,
+                app.id
+            );
+            assert!(!app.ports.is_empty(), "{} has no ports", app.id);
+        }
+    }
+
+    #[test]
+    fn finds_apps_by_unit_and_process_words() {
+        let subject = |unit, process| Subject {
+            unit,
+            port: Some(80),
+            process,
+            image: None,
+        };
+        let name = |subject| find(&subject).map(|(_, app)| app.id.as_str());
+
+        assert_eq!(
+            name(subject("home-assistant@main.service", None)),
+            Some("home-assistant")
+        );
+        assert_eq!(name(subject("pihole-FTL.service", None)), Some("pihole"));
+        assert_eq!(
+            name(subject("docker-app.service", Some("vaultwarden"))),
+            Some("vaultwarden")
+        );
+        // Whole words only, and the longest name wins.
+        assert_eq!(name(subject("complex.service", None)), None);
+        assert_eq!(
+            name(subject("nginx-proxy-manager.service", None)),
+            Some("nginx-proxy-manager")
+        );
+        assert_eq!(
+            find(&Subject {
+                port: None,
+                ..subject("nextcloud-cron.service", None)
+            }),
+            None
+        );
+    }
+}
@@ -0,0 +1,428 @@
+use crate::{
+    auth::AuthConfig,
+    host::{self, HostStrategy},
+    networks::{self, NetworkConfig},
+    notify::NotifierConfig,
+    ratelimit::RateLimitConfig,
+    read_only::ReadOnlyConfig,
+    rules::{self, Rule},
+    security::SecurityConfig,
+    state::AppState,
+    tls::TlsConfig,
+};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+pub const DEFAULT_LISTEN_HOST: &str = "0.0.0.0";
+pub const DEFAULT_LISTEN_PORT: u16 = 8080;
+pub const DEFAULT_HOST: &str = "localhost";
+pub const DEFAULT_DATA_FILE: &str = "data/services.json";
+pub const MIN_DISCOVERY_INTERVAL: Duration = Duration::from_secs(10);
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Contents of the TOML config file. Every key is optional; CLI flags and
+/// environment variables take precedence over anything set here.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FileConfig {
+    pub listen: ListenConfig,
+    pub default_host: Option<String>,
+    /// How `default_host` is detected when it is not set.
+    pub default_host_strategy: Option<HostStrategy>,
+    pub data_file: Option<String>,
+    pub services_dir: Option<PathBuf>,
+    pub networks: Vec<NetworkConfig>,
+    pub discovery: DiscoveryConfig,
+    pub classification: Vec<Rule>,
+    pub notifiers: Vec<NotifierConfig>,
+    pub auth: AuthConfig,
+    pub security: SecurityConfig,
+    pub rate_limit: RateLimitConfig,
+    pub read_only: ReadOnlyConfig,
+    pub tls: TlsConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ListenConfig {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// Seconds between discovery runs; unset means only on startup and demand.
+    pub interval_secs: Option<u64>,
+    /// Unit name globs to scan (`*` and `?`). Empty means every unit.
+    pub include: Vec<String>,
+    /// Unit name globs to skip, applied after `include`.
+    pub exclude: Vec<String>,
+    /// TOML file of `[[rules]]`, checked after `[[classification]]` and
+    /// before the built-in rules.
+    pub rules_file: Option<PathBuf>,
+}
+
+/// Settings that can change while the server runs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub default_host: String,
+    /// How `default_host` was detected; unset when it was given explicitly.
+    pub default_host_strategy: Option<HostStrategy>,
+    pub networks: Vec<NetworkConfig>,
+    pub discovery_interval: Option<Duration>,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+    /// Rules from `[[classification]]`.
+    pub classification: Vec<Rule>,
+    /// Rules from `discovery.rules_file`.
+    pub rules: Vec<Rule>,
+    pub notifiers: Vec<NotifierConfig>,
+    pub auth: AuthConfig,
+    pub security: SecurityConfig,
+    pub rate_limit: RateLimitConfig,
+    pub read_only: ReadOnlyConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_host: DEFAULT_HOST.to_string(),
+            default_host_strategy: None,
+            networks: Vec::new(),
+            discovery_interval: None,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            classification: Vec::new(),
+            rules: Vec::new(),
+            notifiers: Vec::new(),
+            auth: AuthConfig::default(),
+            security: SecurityConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            read_only: ReadOnlyConfig::default(),
+        }
+    }
+}
+
+/// `default_host` settings from the CLI or environment, which win over the
+/// file.
+#[derive(Debug, Clone, Default)]
+pub struct HostOverrides {
+    pub default_host: Option<String>,
+    pub strategy: Option<HostStrategy>,
+}
+
+impl Settings {
+    /// Builds runtime settings from the file and `overrides`. Without an
+    /// explicit `default_host`, it is detected with the chosen strategy.
+    pub fn resolve(file: &FileConfig, overrides: &HostOverrides) -> Result<Self> {
+        let explicit = overrides
+            .default_host
+            .as_deref()
+            .or(file.default_host.as_deref());
+        let (default_host, default_host_strategy) = match explicit {
+            Some(host) => (host.trim().to_string(), None),
+            None => {
+                let strategy = overrides
+                    .strategy
+                    .or(file.default_host_strategy)
+                    .unwrap_or_default();
+                (host::info().default_host(strategy), Some(strategy))
+            }
+        };
+        if default_host.is_empty() {
+            bail!("default_host must not be empty");
+        }
+
+        networks::validate(&file.networks)?;
+
+        let discovery_interval = file.discovery.interval_secs.map(Duration::from_secs);
+        if discovery_interval.is_some_and(|value| value < MIN_DISCOVERY_INTERVAL) {
+            bail!(
+                "discovery.interval_secs must be at least {}",
+                MIN_DISCOVERY_INTERVAL.as_secs()
+            );
+        }
+
+        for pattern in file.discovery.include.iter().chain(&file.discovery.exclude) {
+            if pattern.trim().is_empty() {
+                bail!("discovery include/exclude patterns must not be empty");
+            }
+        }
+        for (index, rule) in file.classification.iter().enumerate() {
+            rule.validate(&format!("classification rule {}", index + 1))?;
+        }
+        let rules = match &file.discovery.rules_file {
+            Some(path) => rules::load_file(path)?,
+            None => Vec::new(),
+        };
+        for notifier in &file.notifiers {
+            notifier.validate()?;
+        }
+        file.auth.validate()?;
+        file.security.validate()?;
+        file.rate_limit.validate()?;
+        file.read_only.validate()?;
+        file.tls.validate()?;
+
+        Ok(Self {
+            default_host,
+            default_host_strategy,
+            networks: file.networks.clone(),
+            discovery_interval,
+            include: file.discovery.include.clone(),
+            exclude: file.discovery.exclude.clone(),
+            classification: file.classification.clone(),
+            rules,
+            notifiers: file.notifiers.clone(),
+            auth: file.auth.clone(),
+            security: file.security.clone(),
+            rate_limit: file.rate_limit.clone(),
+            read_only: file.read_only.clone(),
+        })
+    }
+
+    /// Names of the sections that differ from `other`, for the audit log.
+    pub fn changed_sections(&self, other: &Self) -> Vec<String> {
+        let sections = [
+            (
+                "default_host",
+                self.default_host != other.default_host
+                    || self.default_host_strategy != other.default_host_strategy,
+            ),
+            ("networks", self.networks != other.networks),
+            (
+                "discovery",
+                self.discovery_interval != other.discovery_interval
+                    || self.include != other.include
+                    || self.exclude != other.exclude,
+            ),
+            (
+                "classification",
+                self.classification != other.classification || self.rules != other.rules,
+            ),
+            ("notifiers", self.notifiers != other.notifiers),
+            ("auth", self.auth != other.auth),
+            ("security", self.security != other.security),
+            ("rate_limit", self.rate_limit != other.rate_limit),
+            ("read_only", self.read_only != other.read_only),
+        ];
+        sections
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Whether discovery should look at `unit` given the include/exclude globs.
+    pub fn scans_unit(&self, unit: &str) -> bool {
+        let included =
+            self.include.is_empty() || self.include.iter().any(|glob| glob_match(glob, unit));
+        included && !self.exclude.iter().any(|glob| glob_match(glob, unit))
+    }
+}
+
+pub fn load_file(path: &Path) -> Result<FileConfig> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed reading {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("failed parsing {}", path.display()))
+}
+
+/// Minimal glob: `*` matches any run of characters, `?` exactly one.
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&value) if value == '?' || value == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|value| *value == '*')
+}
+
+fn fingerprint(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// Resolves when the process receives SIGHUP; never on other platforms.
+struct Hangup {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl Hangup {
+    fn new() -> Self {
+        #[cfg(unix)]
+        {
+            let signal = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .map_err(|error| warn!("SIGHUP reload unavailable: {error}"))
+                .ok();
+            Self { signal }
+        }
+        #[cfg(not(unix))]
+        Self {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut() {
+            if signal.recv().await.is_some() {
+                return;
+            }
+        }
+        std::future::pending::<()>().await
+    }
+}
+
+/// Reloads settings on SIGHUP or when the file or its rules file changes. An
+/// invalid file is logged and the running settings stay in effect. Keys that
+/// only apply at startup are reported when they change but otherwise ignored.
+pub async fn watch(state: AppState, path: PathBuf, startup: FileConfig, overrides: HostOverrides) {
+    let mut hangup = Hangup::new();
+    let mut rules_file = startup.discovery.rules_file.clone();
+    let fingerprints = |rules_file: &Option<PathBuf>| {
+        (
+            fingerprint(&path),
+            rules_file.as_deref().and_then(fingerprint),
+        )
+    };
+    let mut last = fingerprints(&rules_file);
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        let forced = tokio::select! {
+            _ = interval.tick() => false,
+            _ = hangup.recv() => true,
+        };
+        let current = fingerprints(&rules_file);
+        if !forced && current == last {
+            continue;
+        }
+        last = current;
+
+        let loaded = load_file(&path).and_then(|file| {
+            let settings = Settings::resolve(&file, &overrides)?;
+            Ok((file, settings))
+        });
+        match loaded {
+            Ok((file, settings)) => {
+                if file.listen != startup.listen
+                    || file.tls != startup.tls
+                    || file.data_file != startup.data_file
+                    || file.services_dir != startup.services_dir
+                    || file.auth.users_file != startup.auth.users_file
+                    || file.auth.tokens_file != startup.auth.tokens_file
+                {
+                    warn!(
+                        config = %path.display(),
+                        "listen, tls, data_file, services_dir and auth file path changes apply after a restart"
+                    );
+                }
+                if file.discovery.rules_file != rules_file {
+                    rules_file = file.discovery.rules_file.clone();
+                    last = fingerprints(&rules_file);
+                }
+                state.apply_settings(settings).await;
+                info!(config = %path.display(), "reloaded settings");
+            }
+            Err(error) => {
+                warn!(config = %path.display(), "keeping previous settings: {error:#}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_full_file_and_rejects_bad_values() {
+        let file: FileConfig = toml::from_str(
+            r#"
+default_host = "server.lan"
+
+[[networks]]
+name = "tailscale"
+subnets = ["100.64.0.0/10"]
+host = "100.100.1.2"
+
+[listen]
+port = 9090
+
+[discovery]
+interval_secs = 300
+include = ["*.service"]
+exclude = ["systemd-*", "getty@*"]
+
+[[classification]]
+match = ["paperless"]
+group = "Documents"
+icon = "📄"
+
+[[notifiers]]
+url = "https://ntfy.example.com/homelab"
+"#,
+        )
+        .expect("config should parse");
+
+        let settings =
+            Settings::resolve(&file, &HostOverrides::default()).expect("config should be valid");
+        assert_eq!(settings.default_host, "server.lan");
+        assert_eq!(settings.networks[0].name, "tailscale");
+        assert_eq!(settings.discovery_interval, Some(Duration::from_secs(300)));
+        assert!(settings.scans_unit("jellyfin.service"));
+        assert!(!settings.scans_unit("systemd-journald.service"));
+        assert!(settings.classification[0].matches(&crate::rules::Subject {
+            unit: "paperless-ngx.service",
+            ..Default::default()
+        }));
+
+        let overrides = HostOverrides {
+            default_host: Some("nas.lan".to_string()),
+            ..Default::default()
+        };
+        let overridden = Settings::resolve(&file, &overrides).expect("valid");
+        assert_eq!(overridden.default_host, "nas.lan");
+        assert_eq!(overridden.default_host_strategy, None);
+
+        let mut invalid = file.clone();
+        invalid.discovery.interval_secs = Some(1);
+        assert!(Settings::resolve(&invalid, &HostOverrides::default()).is_err());
+        assert!(toml::from_str::<FileConfig>("unknown = 1").is_err());
+    }
+
+    #[test]
+    fn glob_matching() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("getty@*.service", "getty@tty1.service"));
+        assert!(glob_match("app?.service", "app1.service"));
+        assert!(!glob_match("app?.service", "app.service"));
+        assert!(!glob_match("nginx", "nginx.service"));
+    }
+}
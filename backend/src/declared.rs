@@ -0,0 +1,355 @@
+use crate::{
+    groups,
+    models::{
+        assign_positions, normalize_aliases, normalize_links, normalize_network_urls, service_id,
+        CreateServiceRequest, ServiceEntry, ServiceLink, ServiceProtocol, ServiceSource,
+    },
+    state::AppState,
+};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tracing::{info, warn};
+
+pub const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// One service declared in `services.d`. Every field that is set is applied
+/// over discovery and locked against edits; unset fields stay editable.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceDeclaration {
+    #[serde(default)]
+    pub id: Option<String>,
+    #[serde(default)]
+    pub service_name: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub host: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub protocol: Option<ServiceProtocol>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub group: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub hidden: Option<bool>,
+    #[serde(default)]
+    pub favorite: Option<bool>,
+    #[serde(default)]
+    pub links: Option<Vec<ServiceLink>>,
+    #[serde(default)]
+    pub network_urls: Option<BTreeMap<String, String>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DeclarationFile {
+    List(Vec<ServiceDeclaration>),
+    Wrapped { services: Vec<ServiceDeclaration> },
+}
+
+impl ServiceDeclaration {
+    pub fn id(&self) -> Option<String> {
+        self.id
+            .clone()
+            .or_else(|| self.service_name.as_deref().map(service_id))
+            .filter(|id| !id.is_empty())
+    }
+
+    fn declared_fields(&self) -> Vec<String> {
+        let fields = [
+            ("service_name", self.service_name.is_some()),
+            ("display_name", self.display_name.is_some()),
+            ("description", self.description.is_some()),
+            ("host", self.host.is_some()),
+            ("port", self.port.is_some()),
+            ("protocol", self.protocol.is_some()),
+            ("path", self.path.is_some()),
+            ("url", self.url.is_some()),
+            ("group", self.group.is_some()),
+            ("tags", self.tags.is_some()),
+            ("aliases", self.aliases.is_some()),
+            ("icon", self.icon.is_some()),
+            ("hidden", self.hidden.is_some()),
+            ("favorite", self.favorite.is_some()),
+            ("links", self.links.is_some()),
+            ("network_urls", self.network_urls.is_some()),
+        ];
+        fields
+            .into_iter()
+            .filter(|(_, set)| *set)
+            .map(|(field, _)| field.to_string())
+            .collect()
+    }
+
+    fn apply_to(&self, entry: &mut ServiceEntry) {
+        if let Some(value) = &self.service_name {
+            entry.service_name = value.clone();
+        }
+        if let Some(value) = &self.display_name {
+            entry.display_name = value.clone();
+        }
+        if let Some(value) = &self.description {
+            entry.description = Some(value.clone());
+        }
+        if let Some(value) = &self.host {
+            entry.host = value.clone();
+        }
+        if let Some(value) = self.port {
+            entry.port = Some(value);
+        }
+        if let Some(value) = &self.protocol {
+            entry.protocol = value.clone();
+        }
+        if let Some(value) = &self.path {
+            entry.path = Some(value.clone());
+        }
+        if let Some(value) = &self.url {
+            entry.url = Some(value.clone());
+        }
+        if let Some(value) = &self.group {
+            entry.group = Some(groups::canonical(value).to_string());
+        }
+        if let Some(value) = &self.tags {
+            entry.tags = value.clone();
+        }
+        if let Some(value) = &self.aliases {
+            entry.aliases = value.clone();
+        }
+        if let Some(value) = &self.icon {
+            entry.icon = Some(value.clone());
+        }
+        if let Some(value) = self.hidden {
+            entry.hidden = value;
+        }
+        if let Some(value) = self.favorite {
+            entry.favorite = value;
+        }
+        if let Some(value) = &self.links {
+            entry.links = value.clone();
+        }
+        if let Some(value) = &self.network_urls {
+            entry.network_urls = value.clone();
+        }
+
+        for field in self.declared_fields() {
+            if !entry.declared_fields.contains(&field) {
+                entry.declared_fields.push(field);
+            }
+        }
+        entry.declared_fields.sort();
+    }
+
+    fn into_entry(self, id: String, default_host: &str) -> ServiceEntry {
+        let mut entry = CreateServiceRequest {
+            service_name: self.service_name.clone().unwrap_or_else(|| id.clone()),
+            port: self.port,
+            protocol: self.protocol.clone(),
+            locked_fields: Some(Vec::new()),
+            ..Default::default()
+        }
+        .into_entry(default_host);
+        entry.id = id;
+        entry.source = ServiceSource::Declared;
+        self.apply_to(&mut entry);
+        entry
+    }
+}
+
+/// Reads every `*.yaml`/`*.yml` file in `dir`, in file name order. Each file
+/// is a list of declarations or a mapping with a `services` list.
+pub fn load_dir(dir: &Path) -> Result<Vec<ServiceDeclaration>> {
+    let mut declarations = Vec::new();
+    for path in declaration_files(dir)? {
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("failed reading {}", path.display()))?;
+        if content.trim().is_empty() {
+            continue;
+        }
+        let file: DeclarationFile = serde_yaml::from_str(&content)
+            .with_context(|| format!("failed parsing {}", path.display()))?;
+        let entries = match file {
+            DeclarationFile::List(entries) | DeclarationFile::Wrapped { services: entries } => {
+                entries
+            }
+        };
+        for mut declaration in entries {
+            if declaration.id().is_none() {
+                bail!(
+                    "{}: every service needs an id or service_name",
+                    path.display()
+                );
+            }
+            if let Some(links) = &mut declaration.links {
+                normalize_links(links).with_context(|| format!("{}", path.display()))?;
+            }
+            if let Some(aliases) = &mut declaration.aliases {
+                normalize_aliases(aliases);
+            }
+            if let Some(urls) = &mut declaration.network_urls {
+                normalize_network_urls(urls);
+            }
+            declarations.push(declaration);
+        }
+    }
+    Ok(declarations)
+}
+
+/// Lays declarations over `services`: matching entries get the declared
+/// values, missing ones are created, and entries whose declaration was
+/// removed lose their declared locks (or disappear if only declared).
+pub fn apply(
+    services: &mut Vec<ServiceEntry>,
+    declarations: &[ServiceDeclaration],
+    default_host: &str,
+) {
+    for entry in services.iter_mut() {
+        entry.declared_fields.clear();
+    }
+
+    for declaration in declarations {
+        let Some(id) = declaration.id() else {
+            continue;
+        };
+        match services.iter_mut().find(|entry| entry.id == id) {
+            Some(entry) => declaration.apply_to(entry),
+            None => services.push(declaration.clone().into_entry(id, default_host)),
+        }
+    }
+
+    services.retain(|entry| {
+        entry.source != ServiceSource::Declared || !entry.declared_fields.is_empty()
+    });
+    services.sort_by(|left, right| left.display_name.cmp(&right.display_name));
+    assign_positions(services);
+}
+
+fn declaration_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("failed reading {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        let is_yaml = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| extension == "yaml" || extension == "yml");
+        if is_yaml && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Cheap change detection: file names, sizes and modification times.
+fn fingerprint(dir: &Path) -> Vec<(PathBuf, u64, Option<SystemTime>)> {
+    declaration_files(dir)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            let metadata = std::fs::metadata(&path).ok();
+            let len = metadata.as_ref().map(|value| value.len()).unwrap_or(0);
+            let modified = metadata.and_then(|value| value.modified().ok());
+            (path, len, modified)
+        })
+        .collect()
+}
+
+/// Polls the directory and reapplies declarations whenever a file changes.
+/// A broken file is logged and the previous declarations stay in effect.
+pub async fn watch(state: AppState, dir: PathBuf) {
+    let mut last = fingerprint(&dir);
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let current = fingerprint(&dir);
+        if current == last {
+            continue;
+        }
+        last = current;
+
+        match state.reload_declarations().await {
+            Ok(count) => info!(count, dir = %dir.display(), "reloaded declared services"),
+            Err(error) => {
+                warn!(dir = %dir.display(), "keeping previous declared services: {error:#}")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discovered() -> ServiceEntry {
+        let mut entry = CreateServiceRequest {
+            service_name: "jellyfin.service".to_string(),
+            port: Some(8096),
+            ..Default::default()
+        }
+        .into_entry("server.lan");
+        entry.source = ServiceSource::Auto;
+        entry.locked_fields.clear();
+        entry
+    }
+
+    #[test]
+    fn declarations_override_and_create() {
+        let yaml = "services:\n  - service_name: jellyfin.service\n    group: Media\n    port: 8920\n  - id: router\n    display_name: Router\n    url: https://192.168.1.1\n";
+        let file: DeclarationFile = serde_yaml::from_str(yaml).expect("yaml");
+        let DeclarationFile::Wrapped {
+            services: declarations,
+        } = file
+        else {
+            panic!("expected wrapped declarations");
+        };
+
+        let mut services = vec![discovered()];
+        apply(&mut services, &declarations, "server.lan");
+
+        assert_eq!(services.len(), 2);
+        let jellyfin = services
+            .iter()
+            .find(|entry| entry.id == "jellyfin-service")
+            .expect("jellyfin");
+        assert_eq!(jellyfin.port, Some(8920));
+        assert_eq!(jellyfin.group.as_deref(), Some("media"));
+        assert!(jellyfin.is_locked("port"));
+        assert!(!jellyfin.is_locked("display_name"));
+
+        let router = services
+            .iter()
+            .find(|entry| entry.id == "router")
+            .expect("router");
+        assert_eq!(router.source, ServiceSource::Declared);
+        assert_eq!(
+            router.resolved_url(None).as_deref(),
+            Some("https://192.168.1.1")
+        );
+
+        apply(&mut services, &[], "server.lan");
+        assert_eq!(services.len(), 1);
+        assert!(services[0].declared_fields.is_empty());
+        assert!(!services[0].is_locked("port"));
+    }
+}
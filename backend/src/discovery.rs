@@ -1,32 +1,31 @@
-use crate::models::{
-    humanize_service_name, service_id, DiscoveryStatusInfo, ServiceEntry, ServiceProtocol,
-    ServiceSource, ServiceStatus,
+use crate::{
+    audit::{Actor, ActorKind},
+    catalog,
+    config::Settings,
+    models::{
+        assign_positions, humanize_service_name, service_id, DiscoveryStatusInfo, ServiceEntry,
+        ServiceProtocol, ServiceSource, ServiceStatus,
+    },
+    rules::{self, Subject},
+    state::AppState,
 };
 use anyhow::Result;
 use chrono::Utc;
 use regex::Regex;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::time::Duration;
 use tokio::process::Command;
-
-const GROUP_SYSTEM: &str = "系统";
-const GROUP_MEDIA: &str = "影音";
-const GROUP_DOWNLOADS: &str = "下载";
-const GROUP_SYNC: &str = "同步";
-const GROUP_PHOTOS: &str = "照片";
-const GROUP_MONITORING: &str = "监控";
-const GROUP_OTHER: &str = "其他";
+use tracing::warn;
 
 const HTTP_DETECTION_TIMEOUT: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Clone)]
 pub struct DiscoveryEngine {
-    default_host: String,
     http_client: reqwest::Client,
 }
 
 impl DiscoveryEngine {
-    pub fn new(default_host: impl Into<String>) -> Self {
+    pub fn new() -> Self {
         let http_client = reqwest::Client::builder()
             .timeout(HTTP_DETECTION_TIMEOUT)
             .danger_accept_invalid_certs(true) // Self-signed certs are common in home servers
@@ -34,19 +33,20 @@ impl DiscoveryEngine {
             .build()
             .expect("Failed to build HTTP client");
 
-        Self {
-            default_host: default_host.into(),
-            http_client,
-        }
+        Self { http_client }
     }
 
-    pub async fn discover(&self) -> Result<(Vec<ServiceEntry>, DiscoveryStatusInfo)> {
+    pub async fn discover(
+        &self,
+        settings: &Settings,
+    ) -> Result<(Vec<ServiceEntry>, DiscoveryStatusInfo)> {
         let mut summary = DiscoveryStatusInfo {
             last_started_at: Some(Utc::now()),
             ..Default::default()
         };
 
-        let units = list_systemd_services().await?;
+        let mut units = list_systemd_services().await?;
+        units.retain(|unit, _| settings.scans_unit(unit));
         summary.scanned_units = units.len();
         summary.active_units = units
             .values()
@@ -62,19 +62,14 @@ impl DiscoveryEngine {
         for (unit, status) in units {
             let cleaned_name = unit.trim().to_string();
             let key = cleaned_name.trim_end_matches(".service").to_lowercase();
-            let ports = listen_map
+            let (process, ports) = listen_map
                 .iter()
-                .find_map(|(process, ports)| {
-                    if process.contains(&key) || key.contains(process) {
-                        Some(ports.clone())
-                    } else {
-                        None
-                    }
-                })
+                .find(|(process, _)| process.contains(&key) || key.contains(process.as_str()))
+                .map(|(process, ports)| (Some(process.clone()), ports.clone()))
                 .unwrap_or_default();
 
             let primary_port = select_primary_port(&ports);
-            let host = self.default_host.clone();
+            let host = settings.default_host.clone();
             let client = self.http_client.clone();
 
             // Create detection task for each service
@@ -84,7 +79,7 @@ impl DiscoveryEngine {
                 } else {
                     ServiceProtocol::Other
                 };
-                (cleaned_name, status, primary_port, protocol)
+                (cleaned_name, status, primary_port, protocol, process)
             };
             detection_tasks.push(detection);
         }
@@ -94,32 +89,69 @@ impl DiscoveryEngine {
 
         // Build service entries from results
         let mut discovered = Vec::new();
-        for (unit, status, primary_port, protocol) in detection_results {
-            discovered.push(ServiceEntry {
+        let mut health_paths = Vec::new();
+        for (unit, status, primary_port, protocol, process) in detection_results {
+            let mut entry = ServiceEntry {
                 id: service_id(&unit),
                 service_name: unit.clone(),
                 display_name: humanize_service_name(&unit),
                 description: None,
-                host: self.default_host.clone(),
+                host: settings.default_host.clone(),
                 port: primary_port,
                 protocol,
                 path: None,
                 url: None,
+                open_url: None,
                 status,
                 group: None,
                 tags: Vec::new(),
+                aliases: Vec::new(),
                 icon: None,
                 hidden: false,
                 favorite: false,
+                links: Vec::new(),
+                network_urls: BTreeMap::new(),
                 source: ServiceSource::Auto,
                 locked_fields: Vec::new(),
+                declared_fields: Vec::new(),
+                updated_by: None,
                 last_seen_at: Some(Utc::now()),
+                position: None,
+                created_at: Some(Utc::now()),
+                last_used_at: None,
+                launch_count: 0,
                 updated_at: Utc::now(),
-            });
+            };
+            let subject = Subject {
+                unit: &unit,
+                port: primary_port,
+                process: process.as_deref(),
+                image: None,
+            };
+            rules::classify(&mut entry, &subject, settings);
+            let app = catalog::find(&subject).map(|(_, app)| app);
+            if let Some(app) = app {
+                app.enrich(&mut entry);
+            }
+            discovered.push(entry);
+            health_paths.push(app.and_then(|app| app.health.as_deref()));
         }
 
-        for entry in &mut discovered {
-            classify_service(entry);
+        // Running units of catalog apps are only up once their health endpoint answers
+        let checks = discovered
+            .iter()
+            .zip(health_paths)
+            .map(|(entry, path)| async move {
+                let url = path
+                    .filter(|_| entry.status == ServiceStatus::Running)
+                    .and_then(|path| health_url(entry, path))?;
+                Some(check_health(&self.http_client, &url).await)
+            });
+        let statuses = futures::future::join_all(checks).await;
+        for (entry, status) in discovered.iter_mut().zip(statuses) {
+            if let Some(status) = status {
+                entry.status = status;
+            }
         }
 
         summary.discovered_services = discovered.len();
@@ -173,6 +205,56 @@ async fn is_http_response(client: &reqwest::Client, url: &str) -> Result<bool> {
     }
 }
 
+/// The catalog health endpoint `path` on a web service's host and port.
+fn health_url(entry: &ServiceEntry, path: &str) -> Option<String> {
+    let scheme = match entry.protocol {
+        ServiceProtocol::Http => "http",
+        ServiceProtocol::Https => "https",
+        _ => return None,
+    };
+    let port = entry.port?;
+    let host = if entry.host.contains(':') {
+        format!("[{}]", entry.host)
+    } else {
+        entry.host.clone()
+    };
+    Some(format!(
+        "{scheme}://{host}:{port}/{}",
+        path.trim_start_matches('/')
+    ))
+}
+
+/// Running while `url` answers 2xx, stopped otherwise.
+async fn check_health(client: &reqwest::Client, url: &str) -> ServiceStatus {
+    match client.get(url).send().await {
+        Ok(response) if response.status().is_success() => ServiceStatus::Running,
+        _ => ServiceStatus::Stopped,
+    }
+}
+
+/// Runs discovery every `discovery.interval_secs`, rescheduling whenever the
+/// settings change. Does nothing while no interval is configured.
+pub async fn schedule(state: AppState) {
+    loop {
+        let interval = state.settings.read().await.discovery_interval;
+        let Some(interval) = interval else {
+            state.settings_changed.notified().await;
+            continue;
+        };
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {
+                if let Err(error) = state
+                    .run_discovery(&Actor::system(ActorKind::Discovery))
+                    .await
+                {
+                    warn!("scheduled discovery failed: {error:#}");
+                }
+            }
+            _ = state.settings_changed.notified() => {}
+        }
+    }
+}
+
 pub fn merge_services(
     current: &[ServiceEntry],
     discovered: &[ServiceEntry],
@@ -183,11 +265,17 @@ pub fn merge_services(
         .map(|value| (value.id.clone(), value.clone()))
         .collect();
 
+    // Candidates in manual order, so the same entry wins on every run
+    let mut ordered: Vec<&ServiceEntry> = current.iter().collect();
+    ordered.sort_by_key(|entry| (entry.position.unwrap_or(u32::MAX), entry.id.as_str()));
+
     let mut merged_ids = HashSet::new();
 
     for auto in discovered {
-        merged_ids.insert(auto.id.clone());
-        if let Some(existing) = current_map.get_mut(&auto.id) {
+        let id = match_existing(&current_map, &ordered, auto, &merged_ids)
+            .unwrap_or_else(|| auto.id.clone());
+        merged_ids.insert(id.clone());
+        if let Some(existing) = current_map.get_mut(&id) {
             let before = existing.clone();
             merge_single(existing, auto);
             if *existing == before {
@@ -210,9 +298,51 @@ pub fn merge_services(
 
     let mut merged: Vec<ServiceEntry> = current_map.into_values().collect();
     merged.sort_by(|left, right| left.display_name.cmp(&right.display_name));
+    // New services go after the existing ones, in name order.
+    assign_positions(&mut merged);
     (merged, summary)
 }
 
+/// Finds the stored entry a discovered unit belongs to: same id, an entry
+/// already bound to the unit, or an imported/manual entry that has never
+/// been seen running but shares the unit's name or host and port. Among
+/// several such entries, the first in `ordered` wins.
+fn match_existing(
+    current: &HashMap<String, ServiceEntry>,
+    ordered: &[&ServiceEntry],
+    discovered: &ServiceEntry,
+    claimed: &HashSet<String>,
+) -> Option<String> {
+    if current.contains_key(&discovered.id) {
+        return Some(discovered.id.clone());
+    }
+
+    let unclaimed = || {
+        ordered
+            .iter()
+            .copied()
+            .filter(|entry| !claimed.contains(&entry.id))
+    };
+    if let Some(entry) = unclaimed().find(|entry| entry.service_name == discovered.service_name) {
+        return Some(entry.id.clone());
+    }
+
+    let key = unit_key(&discovered.service_name);
+    unclaimed()
+        .filter(|entry| entry.source == ServiceSource::Manual && entry.last_seen_at.is_none())
+        .find(|entry| {
+            unit_key(&entry.service_name) == key
+                || (discovered.port.is_some()
+                    && entry.port == discovered.port
+                    && entry.host == discovered.host)
+        })
+        .map(|entry| entry.id.clone())
+}
+
+fn unit_key(service_name: &str) -> String {
+    service_id(service_name.trim_end_matches(".service"))
+}
+
 fn merge_single(existing: &mut ServiceEntry, discovered: &ServiceEntry) {
     if !existing.is_locked("service_name") {
         existing.service_name = discovered.service_name.clone();
@@ -252,54 +382,6 @@ fn merge_single(existing: &mut ServiceEntry, discovered: &ServiceEntry) {
     existing.updated_at = Utc::now();
 }
 
-fn classify_service(entry: &mut ServiceEntry) {
-    let unit = entry
-        .service_name
-        .trim_end_matches(".service")
-        .to_lowercase();
-
-    if entry.port.is_none() {
-        if entry.group.is_none() {
-            entry.group = Some(GROUP_SYSTEM.to_string());
-        }
-        entry.hidden = true;
-        return;
-    }
-
-    if entry.group.is_some() {
-        entry.hidden = false;
-        return;
-    }
-
-    if contains_any(&unit, &["syncthing"]) {
-        entry.group = Some(GROUP_SYNC.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("🔄".to_string()));
-    } else if contains_any(&unit, &["immich"]) {
-        entry.group = Some(GROUP_PHOTOS.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("📷".to_string()));
-    } else if contains_any(&unit, &["aria2", "ariang", "qbittorrent", "transmission"]) {
-        entry.group = Some(GROUP_DOWNLOADS.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("⬇️".to_string()));
-    } else if contains_any(&unit, &["jellyfin", "plex", "emby"]) {
-        entry.group = Some(GROUP_MEDIA.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("🎬".to_string()));
-    } else if contains_any(&unit, &["grafana", "prometheus", "loki"]) {
-        entry.group = Some(GROUP_MONITORING.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("📈".to_string()));
-    } else if contains_any(&unit, &["nginx", "caddy", "traefik"]) {
-        entry.group = Some(GROUP_SYSTEM.to_string());
-        entry.icon = entry.icon.clone().or_else(|| Some("🌐".to_string()));
-    } else {
-        entry.group = Some(GROUP_OTHER.to_string());
-    }
-
-    entry.hidden = entry.group.as_deref() == Some(GROUP_SYSTEM);
-}
-
-fn contains_any(haystack: &str, needles: &[&str]) -> bool {
-    needles.iter().any(|needle| haystack.contains(needle))
-}
-
 fn select_primary_port(ports: &[u16]) -> Option<u16> {
     if ports.is_empty() {
         return None;
@@ -400,6 +482,7 @@ fn parse_port(line: &str) -> Option<u16> {
 mod tests {
     use super::*;
     use crate::models::{ServiceProtocol, ServiceSource, ServiceStatus};
+    use crate::rules::{Rule, RuleMatch, RuleSource};
 
     fn base_service() -> ServiceEntry {
         ServiceEntry {
@@ -412,15 +495,25 @@ mod tests {
             protocol: ServiceProtocol::Http,
             path: None,
             url: None,
+            open_url: None,
             status: ServiceStatus::Running,
             group: Some("proxy".to_string()),
             tags: vec!["gateway".to_string()],
+            aliases: Vec::new(),
             icon: Some("🌐".to_string()),
             hidden: false,
             favorite: false,
+            links: Vec::new(),
+            network_urls: BTreeMap::new(),
             source: ServiceSource::Manual,
             locked_fields: vec!["display_name".to_string(), "port".to_string()],
+            declared_fields: Vec::new(),
+            updated_by: None,
             last_seen_at: None,
+            position: None,
+            created_at: None,
+            last_used_at: None,
+            launch_count: 0,
             updated_at: Utc::now(),
         }
     }
@@ -463,6 +556,81 @@ mod tests {
         assert!(item.favorite);
     }
 
+    #[test]
+    fn merge_binds_imported_entry_to_unit_by_port() {
+        let mut imported = base_service();
+        imported.id = "media-server".to_string();
+        imported.service_name = "media-server".to_string();
+        imported.port = Some(8096);
+        imported.locked_fields = crate::models::default_locked_fields();
+
+        let mut discovered = base_service();
+        discovered.id = "jellyfin-service".to_string();
+        discovered.service_name = "jellyfin.service".to_string();
+        discovered.port = Some(8096);
+        discovered.last_seen_at = Some(Utc::now());
+        discovered.source = ServiceSource::Auto;
+
+        let (merged, summary) =
+            merge_services(&[imported], &[discovered], DiscoveryStatusInfo::default());
+        assert_eq!(merged.len(), 1);
+        assert_eq!(summary.added, 0);
+        let item = merged.first().expect("service should exist");
+        assert_eq!(item.id, "media-server");
+        assert_eq!(item.service_name, "jellyfin.service");
+        assert_eq!(item.source, ServiceSource::Merged);
+    }
+
+    #[test]
+    fn merge_binds_the_first_of_several_matching_entries() {
+        let imported = |id: &str, position: u32| {
+            let mut entry = base_service();
+            entry.id = id.to_string();
+            entry.service_name = id.to_string();
+            entry.port = Some(8096);
+            entry.position = Some(position);
+            entry
+        };
+        let current = [imported("a-media", 1), imported("z-media", 0)];
+        let mut discovered = base_service();
+        discovered.id = "jellyfin-service".to_string();
+        discovered.service_name = "jellyfin.service".to_string();
+        discovered.port = Some(8096);
+        discovered.source = ServiceSource::Auto;
+
+        // Map iteration order changes between maps, so try a few times
+        for _ in 0..16 {
+            let (merged, _) = merge_services(
+                &current,
+                std::slice::from_ref(&discovered),
+                DiscoveryStatusInfo::default(),
+            );
+            let bound = merged
+                .iter()
+                .find(|entry| entry.service_name == "jellyfin.service")
+                .expect("unit should be bound");
+            assert_eq!(bound.id, "z-media");
+        }
+    }
+
+    #[test]
+    fn health_urls_use_the_detected_protocol() {
+        let mut entry = base_service();
+        entry.port = Some(8096);
+        assert_eq!(
+            health_url(&entry, "/health").as_deref(),
+            Some("http://server.local:8096/health")
+        );
+        entry.protocol = ServiceProtocol::Https;
+        entry.host = "::1".to_string();
+        assert_eq!(
+            health_url(&entry, "identity").as_deref(),
+            Some("https://[::1]:8096/identity")
+        );
+        entry.protocol = ServiceProtocol::Tcp;
+        assert_eq!(health_url(&entry, "/health"), None);
+    }
+
     #[test]
     fn select_primary_prefers_web_ports() {
         assert_eq!(select_primary_port(&[10000, 8080, 9999]), Some(8080));
@@ -475,6 +643,15 @@ mod tests {
         assert_eq!(parse_port(line), Some(8080));
     }
 
+    fn classify_service(entry: &mut ServiceEntry, settings: &Settings) -> Option<RuleMatch> {
+        let subject = Subject {
+            unit: &entry.service_name.clone(),
+            port: entry.port,
+            ..Default::default()
+        };
+        rules::classify(entry, &subject, settings)
+    }
+
     #[test]
     fn classify_system_service_hidden_when_no_port() {
         let mut entry = ServiceEntry {
@@ -487,20 +664,30 @@ mod tests {
             protocol: ServiceProtocol::Other,
             path: None,
             url: None,
+            open_url: None,
             status: ServiceStatus::Running,
             group: None,
             tags: Vec::new(),
+            aliases: Vec::new(),
             icon: None,
             hidden: false,
             favorite: false,
+            links: Vec::new(),
+            network_urls: BTreeMap::new(),
             source: ServiceSource::Auto,
             locked_fields: Vec::new(),
+            declared_fields: Vec::new(),
+            updated_by: None,
             last_seen_at: None,
+            position: None,
+            created_at: None,
+            last_used_at: None,
+            launch_count: 0,
             updated_at: Utc::now(),
         };
 
-        classify_service(&mut entry);
-        assert_eq!(entry.group.as_deref(), Some(GROUP_SYSTEM));
+        classify_service(&mut entry, &Settings::default());
+        assert_eq!(entry.group.as_deref(), Some("system"));
         assert!(entry.hidden);
     }
 
@@ -512,9 +699,32 @@ mod tests {
         entry.icon = None;
         entry.source = ServiceSource::Auto;
 
-        classify_service(&mut entry);
-        assert_eq!(entry.group.as_deref(), Some(GROUP_SYNC));
+        classify_service(&mut entry, &Settings::default());
+        assert_eq!(entry.group.as_deref(), Some("sync"));
         assert_eq!(entry.icon.as_deref(), Some("🔄"));
         assert!(!entry.hidden);
     }
+
+    #[test]
+    fn classify_config_rule_wins_over_builtin() {
+        let mut entry = base_service();
+        entry.service_name = "grafana-agent.service".to_string();
+        entry.group = None;
+        entry.icon = None;
+        let settings = Settings {
+            classification: vec![Rule {
+                contains: vec!["grafana-agent".to_string()],
+                group: Some("Agents".to_string()),
+                hidden: Some(true),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let found = classify_service(&mut entry, &settings).expect("rule should match");
+        assert_eq!(found.source, RuleSource::Config);
+        assert_eq!(entry.group.as_deref(), Some("Agents"));
+        assert_eq!(entry.icon, None);
+        assert!(entry.hidden);
+    }
 }
@@ -0,0 +1,102 @@
+use crate::models::ServiceEntry;
+use reqwest::Url;
+
+/// Finds the service a proxied request is for: same host (and port, when
+/// the proxy sent one) whose URL path is the longest prefix of the request
+/// path. Services' URLs for other networks count as well.
+pub fn find_service<'a>(
+    services: &'a [ServiceEntry],
+    forwarded_host: &str,
+    forwarded_uri: &str,
+) -> Option<&'a ServiceEntry> {
+    let (host, port) = split_host_port(forwarded_host.trim());
+    let path = forwarded_uri.split(['?', '#']).next().unwrap_or_default();
+
+    services
+        .iter()
+        .flat_map(|entry| {
+            entry
+                .resolved_url(None)
+                .into_iter()
+                .chain(entry.network_urls.values().cloned())
+                .map(move |url| (entry, url))
+        })
+        .filter_map(|(entry, url)| {
+            let url = Url::parse(&url).ok()?;
+            let url_host = url
+                .host_str()?
+                .trim_start_matches('[')
+                .trim_end_matches(']');
+            if !url_host.eq_ignore_ascii_case(host) {
+                return None;
+            }
+            if port.is_some() && port != url.port_or_known_default() {
+                return None;
+            }
+            let prefix = url.path().trim_end_matches('/');
+            let within = prefix.is_empty()
+                || path == prefix
+                || path
+                    .strip_prefix(prefix)
+                    .is_some_and(|rest| rest.starts_with('/'));
+            within.then_some((prefix.len(), entry))
+        })
+        .max_by_key(|(length, _)| *length)
+        .map(|(_, entry)| entry)
+}
+
+pub(crate) fn split_host_port(value: &str) -> (&str, Option<u16>) {
+    if let Some((host, rest)) = value
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        return (
+            host,
+            rest.strip_prefix(':').and_then(|port| port.parse().ok()),
+        );
+    }
+    match value.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (host, port.parse().ok()),
+        _ => (value, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateServiceRequest;
+
+    fn service(name: &str, url: &str) -> ServiceEntry {
+        CreateServiceRequest {
+            service_name: name.to_string(),
+            url: Some(url.to_string()),
+            ..Default::default()
+        }
+        .into_entry("server.lan")
+    }
+
+    #[test]
+    fn matches_host_port_and_longest_path() {
+        let services = vec![
+            service("dashboard", "https://apps.home.lan"),
+            service("grafana", "https://apps.home.lan/grafana/"),
+            service("jellyfin", "http://server.lan:8096"),
+        ];
+        let found = |host: &str, uri: &str| {
+            find_service(&services, host, uri).map(|entry| entry.id.as_str())
+        };
+
+        assert_eq!(
+            found("apps.home.lan", "/grafana/d/abc?x=1"),
+            Some("grafana")
+        );
+        assert_eq!(found("APPS.home.lan", "/grafanax"), Some("dashboard"));
+        assert_eq!(found("apps.home.lan:443", "/"), Some("dashboard"));
+        assert_eq!(
+            found("server.lan:8096", "/web/index.html"),
+            Some("jellyfin")
+        );
+        assert_eq!(found("server.lan:9000", "/"), None);
+        assert_eq!(found("other.lan", "/"), None);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::{bookmarks::escape_html, models::ServiceEntry};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+
+/// Characters escaped in a passed-through path; `/` is kept.
+const PATH: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Where `/go/<terms>` leads: a service and the path to append to its URL,
+/// or the dashboard searching for `terms`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoLink<'a> {
+    Service {
+        service: &'a ServiceEntry,
+        rest: &'a str,
+    },
+    Search,
+}
+
+/// Resolves `terms`, such as `jf` or `graf/d/abc`. The part before the first
+/// `/` is matched against aliases, then ids, then display names, ignoring
+/// case; among services sharing it, the first in manual order wins.
+pub fn resolve<'a>(
+    services: impl IntoIterator<Item = &'a ServiceEntry>,
+    terms: &'a str,
+) -> GoLink<'a> {
+    let (alias, rest) = terms.split_once('/').unwrap_or((terms, ""));
+    let alias = alias.trim().to_lowercase();
+    if alias.is_empty() || alias.contains(char::is_whitespace) {
+        return GoLink::Search;
+    }
+    let rank = |entry: &ServiceEntry| {
+        if entry.aliases.contains(&alias) {
+            Some(0)
+        } else if entry.id.eq_ignore_ascii_case(&alias) {
+            Some(1)
+        } else if entry.display_name.to_lowercase() == alias {
+            Some(2)
+        } else {
+            None
+        }
+    };
+    services
+        .into_iter()
+        .filter_map(|entry| Some(((rank(entry)?, entry.position.unwrap_or(u32::MAX)), entry)))
+        .min_by_key(|(key, _)| *key)
+        .map_or(GoLink::Search, |(_, service)| GoLink::Service {
+            service,
+            rest,
+        })
+}
+
+/// `url` with `rest` appended to its path and `query` to its query string.
+pub fn target(url: &str, rest: &str, query: Option<&str>) -> String {
+    let (url, fragment) = match url.split_once('#') {
+        Some((url, fragment)) => (url, Some(fragment)),
+        None => (url, None),
+    };
+    let (path, own_query) = match url.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (url, None),
+    };
+    let mut target = path.to_string();
+    let rest = rest.trim_start_matches('/');
+    if !rest.is_empty() {
+        if !target.ends_with('/') {
+            target.push('/');
+        }
+        target.extend(utf8_percent_encode(rest, PATH));
+    }
+    let queries: Vec<&str> = [own_query, query]
+        .into_iter()
+        .flatten()
+        .filter(|query| !query.is_empty())
+        .collect();
+    if !queries.is_empty() {
+        target.push('?');
+        target.push_str(&queries.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        target.push('#');
+        target.push_str(fragment);
+    }
+    target
+}
+
+/// The dashboard searching for `terms`.
+pub fn search_page(terms: &str) -> String {
+    match terms.trim() {
+        "" => "/".to_string(),
+        terms => format!("/?q={}", utf8_percent_encode(terms, NON_ALPHANUMERIC)),
+    }
+}
+
+/// An OpenSearch description adding the navigator as a browser search
+/// engine at `origin`, e.g. `https://nav.example.com`.
+pub fn opensearch_description(origin: &str) -> String {
+    let origin = escape_html(origin.trim_end_matches('/'));
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<OpenSearchDescription xmlns="http://a9.com/-/spec/opensearch/1.1/" xmlns:moz="http://www.mozilla.org/2006/browser/search/">
+  <ShortName>Navigator</ShortName>
+  <Description>Open home server services by alias or name</Description>
+  <InputEncoding>UTF-8</InputEncoding>
+  <Image type="image/svg+xml">{origin}/favicon.svg</Image>
+  <Url type="text/html" method="get" template="{origin}/go?q={{searchTerms}}"/>
+  <moz:SearchForm>{origin}/</moz:SearchForm>
+</OpenSearchDescription>
+"#
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateServiceRequest;
+
+    fn service(name: &str, aliases: &[&str], position: u32) -> ServiceEntry {
+        let mut entry = CreateServiceRequest {
+            service_name: name.to_string(),
+            port: Some(3000),
+            ..Default::default()
+        }
+        .into_entry("server.lan");
+        entry.aliases = aliases.iter().map(|alias| alias.to_string()).collect();
+        entry.position = Some(position);
+        entry
+    }
+
+    #[test]
+    fn resolves_aliases_and_builds_targets() {
+        let services = [
+            service("grafana", &["graf", "dash"], 1),
+            service("homepage", &["dash"], 0),
+        ];
+        let resolve = |terms| match resolve(&services, terms) {
+            GoLink::Service { service, rest } => Some((service.id.as_str(), rest)),
+            GoLink::Search => None,
+        };
+
+        assert_eq!(resolve("graf/d/abc"), Some(("grafana", "d/abc")));
+        assert_eq!(resolve("GRAFANA"), Some(("grafana", "")));
+        assert_eq!(resolve("dash"), Some(("homepage", "")));
+        assert_eq!(resolve("grafana cpu"), None);
+        assert_eq!(resolve("plex"), None);
+
+        assert_eq!(
+            target("http://server.lan:3000", "d/abc", Some("orgId=1")),
+            "http://server.lan:3000/d/abc?orgId=1"
+        );
+        assert_eq!(
+            target(
+                "https://jf.example.com/web/?a=1#home",
+                "my file",
+                Some("b=2")
+            ),
+            "https://jf.example.com/web/my%20file?a=1&b=2#home"
+        );
+        assert_eq!(
+            target("http://server.lan:3000/", "", None),
+            "http://server.lan:3000/"
+        );
+        assert_eq!(search_page("grafana cpu"), "/?q=grafana%20cpu");
+        assert_eq!(search_page(" "), "/");
+    }
+}
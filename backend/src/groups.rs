@@ -0,0 +1,188 @@
+use axum::{
+    extract::FromRequestParts,
+    http::{header::ACCEPT_LANGUAGE, request::Parts},
+};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+
+pub const SYSTEM: &str = "system";
+
+/// Built-in group keys with their English and Chinese names. Services store
+/// the key; any other group name is user-defined and shown as is.
+const BUILTIN: &[(&str, &str, &str)] = &[
+    ("media", "Media", "影音"),
+    ("downloads", "Downloads", "下载"),
+    ("sync", "Sync", "同步"),
+    ("photos", "Photos", "照片"),
+    ("monitoring", "Monitoring", "监控"),
+    ("network", "Network", "网络"),
+    (SYSTEM, "System", "系统"),
+    ("security", "Security", "安全"),
+    ("home", "Home", "家居"),
+    ("documents", "Documents", "文档"),
+    ("productivity", "Productivity", "效率"),
+    ("communication", "Communication", "通讯"),
+    ("storage", "Storage", "存储"),
+    ("development", "Development", "开发"),
+    ("ai", "AI", "人工智能"),
+    ("books", "Books", "阅读"),
+    ("finance", "Finance", "财务"),
+    ("games", "Games", "游戏"),
+    ("other", "Other", "其他"),
+];
+
+/// How a group is shown, persisted in the data file. Groups are listed in
+/// the order of their settings, then built-in, then custom groups.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupSettings {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Shown collapsed until the user expands it.
+    #[serde(default)]
+    pub collapsed: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    En,
+    Zh,
+}
+
+impl Locale {
+    /// `zh-CN`, `en-US;q=0.8` and similar; `None` for unsupported languages.
+    fn parse(tag: &str) -> Option<Self> {
+        let language = tag.split(';').next()?.trim().to_lowercase();
+        match language.split(['-', '_']).next()? {
+            "en" => Some(Self::En),
+            "zh" => Some(Self::Zh),
+            _ => None,
+        }
+    }
+
+    /// The first supported language of an `Accept-Language` header.
+    fn from_accept_language(header: &str) -> Option<Self> {
+        let mut tags: Vec<(f32, &str)> = header
+            .split(',')
+            .map(|tag| {
+                let quality = tag
+                    .split(";q=")
+                    .nth(1)
+                    .and_then(|value| value.trim().parse().ok())
+                    .unwrap_or(1.0);
+                (quality, tag)
+            })
+            .collect();
+        tags.sort_by(|a, b| b.0.total_cmp(&a.0));
+        tags.into_iter().find_map(|(_, tag)| Self::parse(tag))
+    }
+}
+
+/// The `locale` query parameter, else `Accept-Language`, else English.
+impl<S: Send + Sync> FromRequestParts<S> for Locale {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let from_query = parts.uri.query().and_then(|query| {
+            query
+                .split('&')
+                .filter_map(|pair| pair.split_once('='))
+                .find(|(key, _)| *key == "locale")
+                .and_then(|(_, value)| Self::parse(value))
+        });
+        let from_header = || {
+            parts
+                .headers
+                .get(ACCEPT_LANGUAGE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(Self::from_accept_language)
+        };
+        Ok(from_query.or_else(from_header).unwrap_or_default())
+    }
+}
+
+/// Built-in keys in display order.
+pub fn builtin() -> impl Iterator<Item = &'static str> {
+    BUILTIN.iter().map(|(key, _, _)| *key)
+}
+
+pub fn is_builtin(group: &str) -> bool {
+    BUILTIN.iter().any(|(key, _, _)| *key == group)
+}
+
+/// The name to show for `group`.
+pub fn label(group: &str, locale: Locale) -> &str {
+    BUILTIN
+        .iter()
+        .find(|(key, _, _)| *key == group)
+        .map_or(group, |(_, en, zh)| match locale {
+            Locale::En => en,
+            Locale::Zh => zh,
+        })
+}
+
+/// `group` and its names in every locale, for searching.
+pub fn names(group: &str) -> impl Iterator<Item = &str> {
+    let translations = BUILTIN
+        .iter()
+        .find(|(key, _, _)| *key == group)
+        .map(|(_, en, zh)| [*en, *zh]);
+    std::iter::once(group).chain(translations.into_iter().flatten())
+}
+
+/// Maps a built-in group's key or name in any locale, ignoring case, to its
+/// key, so `Media` from an English bookmarks file and `影音` stored by
+/// earlier versions both become `media`. Anything else is returned unchanged.
+pub fn canonical(group: &str) -> &str {
+    BUILTIN
+        .iter()
+        .find(|(key, en, zh)| {
+            key.eq_ignore_ascii_case(group) || en.eq_ignore_ascii_case(group) || *zh == group
+        })
+        .map_or(group, |(key, _, _)| key)
+}
+
+/// Rewrites a legacy group name in place. Returns whether it changed.
+pub fn migrate(group: &mut Option<String>) -> bool {
+    match group.as_deref().map(canonical) {
+        Some(key) if Some(key) != group.as_deref() => {
+            *group = Some(key.to_string());
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translates_keys_and_migrates_legacy_names() {
+        assert_eq!(label("media", Locale::En), "Media");
+        assert_eq!(label("media", Locale::Zh), "影音");
+        assert_eq!(label("Homelab", Locale::Zh), "Homelab");
+
+        let mut legacy = Some("影音".to_string());
+        assert!(migrate(&mut legacy));
+        assert_eq!(legacy.as_deref(), Some("media"));
+        let mut english = Some("Media".to_string());
+        assert!(migrate(&mut english));
+        assert_eq!(english.as_deref(), Some("media"));
+        assert_eq!(canonical("HOME"), "home");
+        let mut custom = Some("Homelab".to_string());
+        assert!(!migrate(&mut custom));
+        assert_eq!(custom.as_deref(), Some("Homelab"));
+
+        assert_eq!(
+            Locale::from_accept_language("fr-FR, zh-CN;q=0.9, en;q=0.8"),
+            Some(Locale::Zh)
+        );
+        assert_eq!(Locale::from_accept_language("de"), None);
+    }
+}
@@ -0,0 +1,212 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{net::IpAddr, process::Command, sync::OnceLock};
+use tracing::warn;
+
+/// Interfaces of containers, bridges and VPNs, skipped when picking the LAN
+/// address.
+const VIRTUAL_INTERFACES: &[&str] = &[
+    "docker",
+    "br-",
+    "veth",
+    "virbr",
+    "cni",
+    "flannel",
+    "cali",
+    "podman",
+    "tailscale",
+    "wg",
+    "tun",
+    "zt",
+];
+
+/// How `default_host` is chosen when it is not set explicitly. A strategy
+/// that finds nothing falls back to the LAN address, then the hostname,
+/// then `localhost`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum HostStrategy {
+    /// The first private IPv4 address of a physical interface.
+    #[default]
+    #[value(name = "lan_ip")]
+    LanIp,
+    Hostname,
+    Fqdn,
+    /// `<hostname>.local`, for networks with Avahi or Bonjour.
+    Mdns,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InterfaceAddress {
+    pub interface: String,
+    pub address: IpAddr,
+}
+
+/// Names and addresses of the machine, detected once at startup.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct HostInfo {
+    pub hostname: Option<String>,
+    pub fqdn: Option<String>,
+    pub mdns: Option<String>,
+    pub lan_ip: Option<IpAddr>,
+    /// Every non-loopback, non-link-local address.
+    pub addresses: Vec<InterfaceAddress>,
+}
+
+impl HostInfo {
+    fn detect() -> Self {
+        let hostname = ["/proc/sys/kernel/hostname", "/etc/hostname"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok())
+            .or_else(|| std::env::var("HOSTNAME").ok())
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty() && name != "localhost");
+        let fqdn = hostname.as_deref().and_then(detect_fqdn);
+        let addresses = match if_addrs::get_if_addrs() {
+            Ok(interfaces) => interfaces
+                .into_iter()
+                .filter(|interface| !interface.is_loopback() && !interface.is_link_local())
+                .map(|interface| InterfaceAddress {
+                    address: interface.ip(),
+                    interface: interface.name,
+                })
+                .collect(),
+            Err(error) => {
+                warn!("failed listing network interfaces: {error}");
+                Vec::new()
+            }
+        };
+        Self::new(hostname, fqdn, addresses)
+    }
+
+    fn new(
+        hostname: Option<String>,
+        fqdn: Option<String>,
+        addresses: Vec<InterfaceAddress>,
+    ) -> Self {
+        let mdns = hostname.as_deref().map(|name| {
+            let short = name.split('.').next().unwrap_or(name);
+            format!("{short}.local")
+        });
+        Self {
+            lan_ip: lan_ip(&addresses),
+            hostname,
+            fqdn,
+            mdns,
+            addresses,
+        }
+    }
+
+    /// The host `strategy` picks, with the fallbacks described on
+    /// [`HostStrategy`].
+    pub fn default_host(&self, strategy: HostStrategy) -> String {
+        let lan_ip = self.lan_ip.map(|address| address.to_string());
+        let chosen = match strategy {
+            HostStrategy::LanIp => lan_ip.clone(),
+            HostStrategy::Hostname => self.hostname.clone(),
+            HostStrategy::Fqdn => self.fqdn.clone(),
+            HostStrategy::Mdns => self.mdns.clone(),
+        };
+        chosen
+            .or(lan_ip)
+            .or_else(|| self.hostname.clone())
+            .unwrap_or_else(|| "localhost".to_string())
+    }
+}
+
+/// The machine's [`HostInfo`], detected on first use.
+pub fn info() -> &'static HostInfo {
+    static INFO: OnceLock<HostInfo> = OnceLock::new();
+    INFO.get_or_init(HostInfo::detect)
+}
+
+/// `hostname --fqdn`, else a dotted alias of `hostname` in `/etc/hosts`.
+fn detect_fqdn(hostname: &str) -> Option<String> {
+    let from_command = Command::new("hostname")
+        .arg("--fqdn")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    from_command.filter(|name| is_fqdn(name)).or_else(|| {
+        let hosts = std::fs::read_to_string("/etc/hosts").ok()?;
+        fqdn_from_hosts(&hosts, hostname)
+    })
+}
+
+fn is_fqdn(name: &str) -> bool {
+    name.contains('.') && !name.starts_with("localhost")
+}
+
+fn fqdn_from_hosts(hosts: &str, hostname: &str) -> Option<String> {
+    hosts
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default())
+        .map(|line| line.split_whitespace().skip(1).collect::<Vec<_>>())
+        .filter(|names| names.iter().any(|name| name.eq_ignore_ascii_case(hostname)))
+        .flatten()
+        .find(|name| {
+            is_fqdn(name)
+                && name
+                    .split('.')
+                    .next()
+                    .is_some_and(|short| short.eq_ignore_ascii_case(hostname))
+        })
+        .map(str::to_string)
+}
+
+/// The first private IPv4 address of a physical interface, else of any
+/// interface, else the first address.
+fn lan_ip(addresses: &[InterfaceAddress]) -> Option<IpAddr> {
+    let is_physical = |entry: &&InterfaceAddress| {
+        !VIRTUAL_INTERFACES
+            .iter()
+            .any(|prefix| entry.interface.starts_with(prefix))
+    };
+    let is_private = |entry: &&InterfaceAddress| match entry.address {
+        IpAddr::V4(address) => address.is_private(),
+        IpAddr::V6(_) => false,
+    };
+    addresses
+        .iter()
+        .filter(is_physical)
+        .find(is_private)
+        .or_else(|| addresses.iter().find(is_private))
+        .or_else(|| addresses.first())
+        .map(|entry| entry.address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_hosts_by_strategy() {
+        let address = |interface: &str, address: &str| InterfaceAddress {
+            interface: interface.to_string(),
+            address: address.parse().expect("ip"),
+        };
+        let info = HostInfo::new(
+            Some("nas".to_string()),
+            fqdn_from_hosts("127.0.1.1 nas.home.arpa nas # local\n", "nas"),
+            vec![
+                address("docker0", "172.17.0.1"),
+                address("tailscale0", "100.100.1.2"),
+                address("eth0", "fd00::5"),
+                address("eth0", "192.168.1.10"),
+            ],
+        );
+
+        assert_eq!(info.default_host(HostStrategy::LanIp), "192.168.1.10");
+        assert_eq!(info.default_host(HostStrategy::Hostname), "nas");
+        assert_eq!(info.default_host(HostStrategy::Fqdn), "nas.home.arpa");
+        assert_eq!(info.default_host(HostStrategy::Mdns), "nas.local");
+
+        let bare = HostInfo::new(Some("nas".to_string()), None, Vec::new());
+        assert_eq!(bare.default_host(HostStrategy::Fqdn), "nas");
+        assert_eq!(
+            HostInfo::default().default_host(HostStrategy::LanIp),
+            "localhost"
+        );
+    }
+}
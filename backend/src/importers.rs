@@ -0,0 +1,519 @@
+use crate::{
+    bookmarks, catalog,
+    models::{service_id, CreateServiceRequest, ServiceProtocol},
+};
+use anyhow::{bail, Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_yaml::Value;
+use std::path::Path;
+
+const SQLITE_MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// Dashboards we can migrate from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportSource {
+    /// Homer `config.yml`.
+    Homer,
+    /// gethomepage `services.yaml`.
+    Homepage,
+    /// Heimdall `app.sqlite` database or JSON item export.
+    Heimdall,
+    /// Dashy `conf.yml`.
+    Dashy,
+    /// Netscape bookmarks HTML exported by any browser.
+    Bookmarks,
+}
+
+/// `folder` narrows bookmark imports to one folder; other sources ignore it.
+pub fn parse(
+    source: ImportSource,
+    content: &[u8],
+    folder: Option<&str>,
+) -> Result<Vec<CreateServiceRequest>> {
+    match source {
+        ImportSource::Homer => parse_homer(as_text(content)?),
+        ImportSource::Homepage => parse_homepage(as_text(content)?),
+        ImportSource::Heimdall if content.starts_with(SQLITE_MAGIC) => {
+            parse_heimdall_sqlite_bytes(content)
+        }
+        ImportSource::Heimdall => parse_heimdall_json(as_text(content)?),
+        ImportSource::Dashy => parse_dashy(as_text(content)?),
+        ImportSource::Bookmarks => Ok(bookmarks::parse(as_text(content)?, folder)),
+    }
+}
+
+fn as_text(content: &[u8]) -> Result<&str> {
+    std::str::from_utf8(content).context("config file is not valid UTF-8")
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerConfig {
+    #[serde(default)]
+    services: Vec<HomerSection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerSection {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    items: Vec<HomerItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HomerItem {
+    name: String,
+    #[serde(default)]
+    subtitle: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    logo: Option<String>,
+    #[serde(default)]
+    tag: Option<String>,
+    #[serde(default)]
+    keywords: Option<String>,
+}
+
+fn parse_homer(content: &str) -> Result<Vec<CreateServiceRequest>> {
+    let config: HomerConfig =
+        serde_yaml::from_str(content).context("failed to parse Homer config")?;
+
+    let mut requests = Vec::new();
+    for section in config.services {
+        for item in section.items {
+            let mut tags: Vec<String> = item.tag.into_iter().collect();
+            if let Some(keywords) = item.keywords {
+                tags.extend(keywords.split_whitespace().map(str::to_string));
+            }
+            requests.push(link_request(Link {
+                name: item.name,
+                url: item.url,
+                group: section.name.clone(),
+                description: item.subtitle,
+                icon: item
+                    .icon
+                    .into_iter()
+                    .chain(item.logo)
+                    .find_map(|icon| map_icon(&icon)),
+                tags,
+                unit: None,
+            }));
+        }
+    }
+    Ok(requests)
+}
+
+/// gethomepage keys groups and services by name, so it is walked as a
+/// generic YAML tree. Nested groups are flattened into their parent.
+fn parse_homepage(content: &str) -> Result<Vec<CreateServiceRequest>> {
+    let root: Value = serde_yaml::from_str(content).context("failed to parse Homepage config")?;
+    let Value::Sequence(groups) = root else {
+        bail!("Homepage services.yaml must be a list of groups");
+    };
+
+    let mut requests = Vec::new();
+    for group in groups {
+        collect_homepage_group(&group, None, &mut requests);
+    }
+    Ok(requests)
+}
+
+fn collect_homepage_group(
+    value: &Value,
+    parent: Option<&str>,
+    requests: &mut Vec<CreateServiceRequest>,
+) {
+    let Value::Mapping(mapping) = value else {
+        return;
+    };
+
+    for (name, members) in mapping {
+        let Some(name) = name.as_str() else {
+            continue;
+        };
+        let Value::Sequence(members) = members else {
+            continue;
+        };
+        let group = parent.unwrap_or(name);
+
+        for member in members {
+            let Value::Mapping(member) = member else {
+                continue;
+            };
+            for (title, body) in member {
+                let Some(title) = title.as_str() else {
+                    continue;
+                };
+                match body {
+                    // A list under a member name is a nested group.
+                    Value::Sequence(_) => {
+                        let mut nested = serde_yaml::Mapping::new();
+                        nested.insert(Value::from(title), body.clone());
+                        collect_homepage_group(&Value::Mapping(nested), Some(group), requests);
+                    }
+                    Value::Mapping(fields) => {
+                        let text =
+                            |key: &str| fields.get(key).and_then(Value::as_str).map(str::to_string);
+                        requests.push(link_request(Link {
+                            name: title.to_string(),
+                            url: text("href"),
+                            group: Some(group.to_string()),
+                            description: text("description"),
+                            icon: text("icon"),
+                            tags: Vec::new(),
+                            unit: text("container"),
+                        }));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DashyConfig {
+    #[serde(default)]
+    sections: Vec<DashySection>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashySection {
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    items: Vec<DashyItem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DashyItem {
+    title: String,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn parse_dashy(content: &str) -> Result<Vec<CreateServiceRequest>> {
+    let config: DashyConfig =
+        serde_yaml::from_str(content).context("failed to parse Dashy config")?;
+
+    let mut requests = Vec::new();
+    for section in config.sections {
+        for item in section.items {
+            requests.push(link_request(Link {
+                name: item.title,
+                url: item.url,
+                group: section.name.clone(),
+                description: item.description,
+                icon: item.icon,
+                tags: item.tags,
+                unit: None,
+            }));
+        }
+    }
+    Ok(requests)
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum HeimdallExport {
+    Items(Vec<HeimdallItem>),
+    Wrapped { items: Vec<HeimdallItem> },
+}
+
+#[derive(Debug, Deserialize)]
+struct HeimdallItem {
+    title: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+    #[serde(default)]
+    icon: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+fn parse_heimdall_json(content: &str) -> Result<Vec<CreateServiceRequest>> {
+    let export: HeimdallExport =
+        serde_json::from_str(content).context("failed to parse Heimdall JSON export")?;
+    let items = match export {
+        HeimdallExport::Items(items) | HeimdallExport::Wrapped { items } => items,
+    };
+    Ok(items.into_iter().map(heimdall_request).collect())
+}
+
+fn heimdall_request(item: HeimdallItem) -> CreateServiceRequest {
+    // Heimdall tags behave like folders, so the first one becomes the group.
+    link_request(Link {
+        name: item.title,
+        url: item.url,
+        group: item.tags.first().cloned(),
+        description: item.description,
+        icon: item.icon,
+        tags: item.tags,
+        unit: None,
+    })
+}
+
+fn parse_heimdall_sqlite_bytes(content: &[u8]) -> Result<Vec<CreateServiceRequest>> {
+    let path = std::env::temp_dir().join(format!(
+        "navigator-heimdall-{}-{}.sqlite",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or(0)
+    ));
+    std::fs::write(&path, content).with_context(|| format!("failed writing {}", path.display()))?;
+    let result = parse_heimdall_sqlite(&path);
+    let _ = std::fs::remove_file(&path);
+    result
+}
+
+/// Reads apps (`type = 0`) and their tags (`type = 1`) straight from
+/// Heimdall's `app.sqlite`. Tag 0 is Heimdall's home dashboard, not a tag.
+pub fn parse_heimdall_sqlite(path: &Path) -> Result<Vec<CreateServiceRequest>> {
+    let connection =
+        rusqlite::Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("failed opening {}", path.display()))?;
+
+    let mut statement = connection
+        .prepare(
+            "SELECT id, title, url, description, icon FROM items \
+             WHERE type = 0 AND deleted_at IS NULL ORDER BY \"order\", id",
+        )
+        .context("failed reading Heimdall items")?;
+    let mut tag_statement = connection
+        .prepare(
+            "SELECT tags.title FROM item_tag \
+             JOIN items AS tags ON tags.id = item_tag.tag_id \
+             WHERE item_tag.item_id = ?1 AND tags.type = 1 AND tags.deleted_at IS NULL",
+        )
+        .context("failed reading Heimdall tags")?;
+
+    let rows = statement
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                HeimdallItem {
+                    title: row.get(1)?,
+                    url: row.get(2)?,
+                    description: row.get(3)?,
+                    icon: row.get(4)?,
+                    tags: Vec::new(),
+                },
+            ))
+        })
+        .context("failed reading Heimdall items")?;
+
+    let mut requests = Vec::new();
+    for row in rows {
+        let (id, mut item) = row.context("invalid Heimdall item row")?;
+        item.tags = tag_statement
+            .query_map([id], |row| row.get::<_, String>(0))
+            .and_then(Iterator::collect)
+            .context("failed reading Heimdall item tags")?;
+        requests.push(heimdall_request(item));
+    }
+    Ok(requests)
+}
+
+pub struct Link {
+    pub name: String,
+    pub url: Option<String>,
+    pub group: Option<String>,
+    pub description: Option<String>,
+    pub icon: Option<String>,
+    pub tags: Vec<String>,
+    /// Unit or container name, when the source knows it.
+    pub unit: Option<String>,
+}
+
+/// Splits the link URL into host/port/path so discovery can later match the
+/// entry to a running unit by port. Only the full URL is kept when it carries
+/// a query or fragment that the parts cannot express.
+pub fn link_request(link: Link) -> CreateServiceRequest {
+    let mut request = CreateServiceRequest {
+        service_name: link.unit.unwrap_or_else(|| service_id(&link.name)),
+        display_name: Some(link.name),
+        description: link.description,
+        group: link.group,
+        icon: link.icon.as_deref().and_then(map_icon),
+        tags: (!link.tags.is_empty()).then_some(link.tags),
+        ..Default::default()
+    };
+
+    let Some(raw) = link.url else {
+        return request;
+    };
+    match reqwest::Url::parse(&raw) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {
+            request.protocol = Some(if url.scheme() == "https" {
+                ServiceProtocol::Https
+            } else {
+                ServiceProtocol::Http
+            });
+            request.host = url.host_str().map(str::to_string);
+            request.port = url.port_or_known_default();
+            request.path = Some(url.path().to_string()).filter(|path| path != "/");
+            if url.query().is_some() || url.fragment().is_some() {
+                request.url = Some(raw);
+            }
+        }
+        _ => request.url = Some(raw),
+    }
+    request
+}
+
+/// The UI renders icons as text, so emoji are kept as they are and icon
+/// names (`hl-jellyfin`, `si-grafana`, `sonarr`) or files
+/// (`assets/tools/jellyfin.png`, `icons/sonarr.svg`) become the emoji of the
+/// catalog app they are named after. Anything else, such as `fas fa-film`,
+/// is dropped and the frontend falls back to its own icon for the name.
+fn map_icon(icon: &str) -> Option<String> {
+    let icon = icon.trim();
+    if icon.is_empty() {
+        return None;
+    }
+    if !icon.chars().any(|character| character.is_ascii()) {
+        return Some(icon.to_string());
+    }
+    let file = icon
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(icon)
+        .rsplit('/')
+        .next()
+        .unwrap_or(icon);
+    let stem = match file.rsplit_once('.') {
+        Some((stem, "png" | "svg" | "webp" | "jpg" | "jpeg" | "gif" | "ico")) => stem,
+        _ => file,
+    };
+    let name = ["hl-", "si-", "sh-", "mdi-"]
+        .iter()
+        .find_map(|prefix| stem.strip_prefix(prefix))
+        .unwrap_or(stem);
+    catalog::named(name).map(|app| app.icon.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> Vec<u8> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures")
+            .join(name);
+        std::fs::read(&path).expect("fixture should exist")
+    }
+
+    fn find<'a>(requests: &'a [CreateServiceRequest], name: &str) -> &'a CreateServiceRequest {
+        requests
+            .iter()
+            .find(|request| request.display_name.as_deref() == Some(name))
+            .expect("request should exist")
+    }
+
+    #[test]
+    fn homer_maps_sections_and_urls() {
+        let requests = parse(ImportSource::Homer, &fixture("homer.yml"), None).expect("homer");
+        assert_eq!(requests.len(), 3);
+
+        let jellyfin = find(&requests, "Jellyfin");
+        assert_eq!(jellyfin.group.as_deref(), Some("Media"));
+        assert_eq!(jellyfin.host.as_deref(), Some("server.lan"));
+        assert_eq!(jellyfin.port, Some(8096));
+        assert_eq!(jellyfin.icon.as_deref(), Some("🎬"));
+        assert_eq!(
+            jellyfin.tags.as_deref(),
+            Some(
+                &[
+                    "media".to_string(),
+                    "video".to_string(),
+                    "streaming".to_string()
+                ][..]
+            )
+        );
+
+        let sonarr = find(&requests, "Sonarr");
+        assert_eq!(sonarr.protocol, Some(ServiceProtocol::Https));
+        assert_eq!(sonarr.icon.as_deref(), Some("📺"));
+        assert_eq!(
+            sonarr.url.as_deref(),
+            Some("https://server.lan:8989/calendar?view=week")
+        );
+    }
+
+    #[test]
+    fn homepage_flattens_nested_groups() {
+        let requests = parse(
+            ImportSource::Homepage,
+            &fixture("homepage-services.yaml"),
+            None,
+        )
+        .expect("homepage");
+        assert_eq!(requests.len(), 4);
+
+        assert_eq!(find(&requests, "Jellyfin").service_name, "jellyfin");
+        assert_eq!(find(&requests, "Jellyfin").icon.as_deref(), Some("🎬"));
+        assert_eq!(find(&requests, "Sonarr").icon.as_deref(), Some("📺"));
+        let pihole = find(&requests, "Pi-hole");
+        assert_eq!(pihole.group.as_deref(), Some("Infrastructure"));
+        assert_eq!(pihole.port, Some(80));
+        assert_eq!(pihole.path.as_deref(), Some("/admin"));
+        assert_eq!(find(&requests, "Proxmox").port, Some(8006));
+    }
+
+    #[test]
+    fn dashy_maps_sections_and_tags() {
+        let requests = parse(ImportSource::Dashy, &fixture("dashy-conf.yml"), None).expect("dashy");
+        assert_eq!(requests.len(), 3);
+        assert_eq!(
+            find(&requests, "Jellyfin").tags.as_deref(),
+            Some(&["video".to_string(), "streaming".to_string()][..])
+        );
+        assert_eq!(find(&requests, "Jellyfin").icon.as_deref(), Some("🎬"));
+        assert_eq!(find(&requests, "Immich").icon.as_deref(), Some("📷"));
+        assert_eq!(
+            find(&requests, "qBittorrent").group.as_deref(),
+            Some("Downloads")
+        );
+    }
+
+    #[test]
+    fn heimdall_reads_json_and_sqlite() {
+        let requests =
+            parse(ImportSource::Heimdall, &fixture("heimdall.json"), None).expect("json");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(find(&requests, "Jellyfin").group.as_deref(), Some("Media"));
+        assert_eq!(find(&requests, "Router").port, Some(443));
+
+        let requests =
+            parse(ImportSource::Heimdall, &fixture("heimdall.sqlite"), None).expect("sqlite");
+        assert_eq!(requests.len(), 2);
+        assert_eq!(find(&requests, "Jellyfin").group.as_deref(), Some("Media"));
+        assert_eq!(find(&requests, "Grafana").group, None);
+        assert_eq!(find(&requests, "Jellyfin").icon.as_deref(), Some("🎬"));
+    }
+
+    #[test]
+    fn icons_map_to_catalog_apps() {
+        assert_eq!(map_icon("si-grafana").as_deref(), Some("📈"));
+        assert_eq!(
+            map_icon("https://cdn.example.com/png/immich.png?v=2").as_deref(),
+            Some("📷")
+        );
+        assert_eq!(map_icon("hl-home-assistant").as_deref(), Some("🏠"));
+        assert_eq!(map_icon("homeassistant.svg").as_deref(), Some("🏠"));
+        assert_eq!(map_icon("fas fa-film"), None);
+        assert_eq!(map_icon("icons/a1b2c3d4.png"), None);
+    }
+}
@@ -5,10 +5,31 @@
 mod api;
 #[cfg(test)]
 mod api_tests;
+mod audit;
+mod auth;
+mod bookmarks;
+mod bundle;
+mod catalog;
+mod config;
+mod declared;
 mod discovery;
+mod forward_auth;
+mod golinks;
+mod groups;
+mod host;
+mod importers;
 mod models;
+mod networks;
+mod notify;
+mod ratelimit;
+mod read_only;
+mod rules;
+mod search;
+mod security;
 mod state;
 mod store;
+mod tls;
+mod tokens;
 
 use anyhow::{anyhow, bail, Context};
 use axum::{
@@ -28,11 +49,19 @@ use std::{
     net::SocketAddr,
     path::{Path as StdPath, PathBuf},
 };
-use tower_http::cors::CorsLayer;
 use tracing::info;
 
 use crate::api::create_router;
+use crate::audit::{Actor, ActorKind};
+use crate::auth::{Role, Scope};
+use crate::bundle::{BundleFormat, ConfigBundle, ImportMode};
+use crate::config::{FileConfig, HostOverrides, Settings};
+use crate::groups::Locale;
+use crate::host::HostStrategy;
+use crate::importers::ImportSource;
+use crate::models::ServiceQuery;
 use crate::state::AppState;
+use crate::tokens::TokenStore;
 
 include!(concat!(env!("OUT_DIR"), "/embedded_assets.rs"));
 
@@ -81,20 +110,132 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Command>,
 
-    #[arg(long, env = "HOST", default_value = "0.0.0.0")]
-    host: String,
-    #[arg(long, env = "PORT", default_value_t = 8080)]
-    port: u16,
-    #[arg(long, env = "DEFAULT_HOST", default_value = "localhost")]
-    default_host: String,
-    #[arg(long, env = "DATA_FILE", default_value = "data/services.json")]
-    data_file: String,
+    /// TOML config file. Flags and environment variables override its values.
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+    /// Listen address [default: 0.0.0.0]
+    #[arg(long, env = "HOST")]
+    host: Option<String>,
+    /// Listen port [default: 8080]
+    #[arg(long, env = "PORT")]
+    port: Option<u16>,
+    /// Hostname used in service URLs [default: detected]
+    #[arg(long, env = "DEFAULT_HOST")]
+    default_host: Option<String>,
+    /// How to detect the hostname when none is given [default: lan_ip]
+    #[arg(long, value_enum, env = "DEFAULT_HOST_STRATEGY")]
+    default_host_strategy: Option<HostStrategy>,
+    /// Service data file [default: data/services.json]
+    #[arg(long, env = "DATA_FILE")]
+    data_file: Option<String>,
+    /// Directory of `*.yaml` service declarations that override discovery and edits.
+    #[arg(long, env = "SERVICES_DIR")]
+    services_dir: Option<PathBuf>,
 }
 
 #[derive(Debug, Clone, Subcommand)]
 enum Command {
     #[command(subcommand)]
     Systemd(SystemdCommand),
+    /// Export services, settings and ignore list as a bundle or bookmarks file.
+    Export(ExportArgs),
+    /// Import a bundle or another dashboard's config into the data file.
+    Import(ImportArgs),
+    /// Manage login accounts in the users file.
+    #[command(subcommand)]
+    Users(UsersCommand),
+    /// Manage API tokens for scripts and automation.
+    #[command(subcommand)]
+    Tokens(TokensCommand),
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum UsersCommand {
+    /// Add a user, prompting for the password.
+    Add {
+        #[command(flatten)]
+        args: UserPasswordArgs,
+        #[arg(long, value_enum, default_value_t = Role::Admin)]
+        role: Role,
+    },
+    /// Change a user's password; their existing sessions end.
+    Passwd(UserPasswordArgs),
+    /// Change a user's role.
+    Role {
+        username: String,
+        #[arg(value_enum)]
+        role: Role,
+    },
+    /// Remove a user; their existing sessions end.
+    Remove { username: String },
+    /// List users and their roles.
+    List,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+enum TokensCommand {
+    /// Create a token and print its secret; it cannot be shown again.
+    Create {
+        name: String,
+        /// Repeat for several scopes.
+        #[arg(long = "scope", value_enum, required = true)]
+        scopes: Vec<Scope>,
+        /// Days until the token expires; never when omitted.
+        #[arg(long)]
+        expires_in_days: Option<u32>,
+    },
+    /// List tokens with their scopes, expiry and last use.
+    List,
+    /// Revoke a token by id; it stops working immediately.
+    Revoke { id: String },
+}
+
+#[derive(Debug, Clone, Args)]
+struct UserPasswordArgs {
+    username: String,
+    /// Read the password from the first line of stdin instead of prompting.
+    #[arg(long)]
+    password_stdin: bool,
+}
+
+#[derive(Debug, Clone, Args)]
+struct ExportArgs {
+    /// Output file (stdout when omitted).
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+    /// Bundle format (defaults to the output file extension, then JSON).
+    #[arg(long, value_enum)]
+    format: Option<BundleFormat>,
+    /// Write a Netscape bookmarks HTML file instead (implied by `.html`).
+    #[arg(long, conflicts_with = "format")]
+    bookmarks: bool,
+    /// Include hidden services in the bookmarks file.
+    #[arg(long, requires = "bookmarks")]
+    include_hidden: bool,
+    /// Language of the bookmark folder names.
+    #[arg(long, value_enum, default_value = "en", requires = "bookmarks")]
+    locale: Locale,
+}
+
+#[derive(Debug, Clone, Args)]
+struct ImportArgs {
+    /// Bundle or dashboard config file to import.
+    file: PathBuf,
+    /// Bundle format (defaults to the file extension, then JSON).
+    #[arg(long, value_enum, conflicts_with = "from")]
+    format: Option<BundleFormat>,
+    /// Read FILE as another dashboard's config instead of a bundle.
+    #[arg(long, value_enum)]
+    from: Option<ImportSource>,
+    /// With `--from bookmarks`, only read links below this folder.
+    #[arg(long)]
+    folder: Option<String>,
+    /// Merge into existing services or replace them entirely.
+    #[arg(long, value_enum, default_value_t = ImportMode::Merge)]
+    mode: ImportMode,
+    /// Print the changes without writing the data file.
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[derive(Debug, Clone, Subcommand)]
@@ -128,9 +269,9 @@ struct SystemdInstallArgs {
     /// Listen port to write into env file.
     #[arg(long, default_value_t = 8080)]
     port: u16,
-    /// Used to compose clickable URLs in UI.
-    #[arg(long, default_value = "localhost")]
-    default_host: String,
+    /// Used to compose clickable URLs in UI; detected at startup when unset.
+    #[arg(long)]
+    default_host: Option<String>,
     /// Do not enable/start the service.
     #[arg(long)]
     no_enable: bool,
@@ -169,40 +310,313 @@ async fn main() -> anyhow::Result<()> {
 
     let cli = Cli::parse();
 
-    if let Some(command) = cli.command {
-        match command {
-            Command::Systemd(systemd) => {
-                return handle_systemd(systemd).await;
-            }
+    if let Some(Command::Systemd(systemd)) = cli.command {
+        return handle_systemd(systemd).await;
+    }
+
+    let file = match &cli.config {
+        Some(path) => config::load_file(path)?,
+        None => FileConfig::default(),
+    };
+    let overrides = HostOverrides {
+        default_host: cli.default_host.clone(),
+        strategy: cli.default_host_strategy,
+    };
+    let settings = Settings::resolve(&file, &overrides).context("invalid configuration")?;
+    if let Some(strategy) = settings.default_host_strategy {
+        info!(
+            default_host = %settings.default_host,
+            ?strategy,
+            "detected default host"
+        );
+    }
+    let data_file = cli
+        .data_file
+        .clone()
+        .or_else(|| file.data_file.clone())
+        .unwrap_or_else(|| config::DEFAULT_DATA_FILE.to_string());
+
+    match cli.command {
+        Some(Command::Export(args)) => return handle_export(args, settings, data_file).await,
+        Some(Command::Import(args)) => return handle_import(args, settings, data_file).await,
+        Some(Command::Users(command)) => {
+            let users = auth::UserStore::new(settings.auth.users_path(&data_file));
+            return handle_users(command, &users);
+        }
+        Some(Command::Tokens(command)) => {
+            let tokens = TokenStore::new(settings.auth.tokens_path(&data_file));
+            return handle_tokens(command, &tokens);
         }
+        Some(Command::Systemd(_)) | None => {}
     }
 
-    let state = AppState::new(cli.default_host, cli.data_file)
+    let tls_config = if file.tls.enabled {
+        let (tls_config, resolver) =
+            tls::server_config(&file.tls, &data_file, &settings.default_host)
+                .context("failed to set up TLS")?;
+        let (cert_path, key_path) = file.tls.paths(&data_file);
+        tokio::spawn(tls::watch(resolver, cert_path, key_path));
+        Some(tls_config)
+    } else {
+        None
+    };
+
+    let mut state = AppState::with_settings(settings, data_file)
         .await
         .context("failed to initialize app state")?;
+    if let Some(dir) = cli
+        .services_dir
+        .clone()
+        .or_else(|| file.services_dir.clone())
+    {
+        state = state
+            .with_services_dir(&dir)
+            .await
+            .with_context(|| format!("failed to load declared services from {}", dir.display()))?;
+        tokio::spawn(declared::watch(state.clone(), dir));
+    }
+
+    if let Some(path) = cli.config.clone() {
+        tokio::spawn(config::watch(state.clone(), path, file.clone(), overrides));
+    }
 
-    let _ = state.run_discovery().await;
+    let _ = state
+        .run_discovery(&Actor::system(ActorKind::Discovery))
+        .await;
+    tokio::spawn(discovery::schedule(state.clone()));
 
     let app = Router::new()
-        .merge(create_router(state))
+        .merge(create_router(state.clone()))
         .route("/", get(index_handler))
         .route("/{*path}", get(asset_or_index_handler))
-        .layer(CorsLayer::permissive());
-
-    let bind_addr = format!("{}:{}", cli.host, cli.port);
+        .layer(axum::middleware::from_fn_with_state(
+            state.clone(),
+            security::security_headers,
+        ))
+        .layer(security::cors_layer(state));
+
+    let host = cli
+        .host
+        .or(file.listen.host)
+        .unwrap_or_else(|| config::DEFAULT_LISTEN_HOST.to_string());
+    let port = cli
+        .port
+        .or(file.listen.port)
+        .unwrap_or(config::DEFAULT_LISTEN_PORT);
+    let bind_addr = format!("{host}:{port}");
     let listener = tokio::net::TcpListener::bind(&bind_addr)
         .await
         .with_context(|| format!("failed binding on {bind_addr}"))?;
     let local_addr: SocketAddr = listener.local_addr()?;
 
+    if let Some(tls_config) = tls_config {
+        if let Some(redirect_port) = file.tls.redirect_port {
+            let redirect_addr = format!("{host}:{redirect_port}");
+            let redirect_listener = tokio::net::TcpListener::bind(&redirect_addr)
+                .await
+                .with_context(|| format!("failed binding on {redirect_addr}"))?;
+            info!(address = %redirect_listener.local_addr()?, "redirecting HTTP to HTTPS");
+            let redirect = tls::redirect_router(local_addr.port());
+            tokio::spawn(async move {
+                if let Err(error) = axum::serve(redirect_listener, redirect).await {
+                    tracing::warn!("HTTPS redirect listener failed: {error}");
+                }
+            });
+        }
+
+        info!(address = %local_addr, "home server navigator started with TLS");
+        return tls::serve(listener, app, tls_config).await;
+    }
+
     info!(address = %local_addr, "home server navigator started");
-    axum::serve(listener, app)
+    // Peer addresses decide whether SSO proxy headers are trusted.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("server exited with error")?;
+
+    Ok(())
+}
+
+async fn handle_export(
+    args: ExportArgs,
+    settings: Settings,
+    data_file: String,
+) -> anyhow::Result<()> {
+    let state = AppState::with_settings(settings, data_file)
         .await
-        .context("server exited with error")?;
+        .context("failed to initialize app state")?;
+    let is_html = args
+        .output
+        .as_deref()
+        .and_then(|path| path.extension())
+        .is_some_and(|extension| extension == "html" || extension == "htm");
+
+    let content = if args.bookmarks || (args.format.is_none() && is_html) {
+        let services = state
+            .list_services(
+                ServiceQuery {
+                    include_hidden: Some(args.include_hidden),
+                    ..Default::default()
+                },
+                None,
+            )
+            .await;
+        bookmarks::render(&services, args.locale, None)
+    } else {
+        let format = args
+            .format
+            .or_else(|| args.output.as_deref().and_then(BundleFormat::from_path))
+            .unwrap_or_default();
+        state.export_bundle().await.render(format)?
+    };
+
+    match args.output {
+        Some(path) => write_file(&path, &content)?,
+        None => print!("{content}"),
+    }
+    Ok(())
+}
+
+/// Works on the data file directly; stop a running server first or use
+/// `POST /api/import` instead, otherwise its next save overwrites the result.
+async fn handle_import(
+    args: ImportArgs,
+    settings: Settings,
+    data_file: String,
+) -> anyhow::Result<()> {
+    let content =
+        fs::read(&args.file).with_context(|| format!("failed reading {}", args.file.display()))?;
+    let state = AppState::with_settings(settings, data_file)
+        .await
+        .context("failed to initialize app state")?;
+
+    let bundle = match args.from {
+        Some(source) => {
+            let requests = importers::parse(source, &content, args.folder.as_deref())?;
+            ConfigBundle::from_requests(requests, &state.default_host().await)
+        }
+        None => {
+            let format = args
+                .format
+                .or_else(|| BundleFormat::from_path(&args.file))
+                .unwrap_or_default();
+            let content = String::from_utf8(content).context("bundle is not valid UTF-8")?;
+            ConfigBundle::parse(&content, format)?
+        }
+    };
+    let report = state
+        .import_bundle(
+            bundle,
+            args.mode,
+            args.dry_run,
+            &Actor::system(ActorKind::Import),
+        )
+        .await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
+}
+
+fn handle_users(command: UsersCommand, users: &auth::UserStore) -> anyhow::Result<()> {
+    match command {
+        UsersCommand::Add { args, role } => {
+            if users.find(&args.username)?.is_some() {
+                bail!("user {} already exists; use `users passwd`", args.username);
+            }
+            let password = read_password(args.password_stdin)?;
+            users.set_password(&args.username, &password, role)?;
+            println!("Added {} to {}", args.username, users.path().display());
+        }
+        UsersCommand::Passwd(args) => {
+            if users.find(&args.username)?.is_none() {
+                bail!("no such user: {}", args.username);
+            }
+            let password = read_password(args.password_stdin)?;
+            users.set_password(&args.username, &password, Role::default())?;
+            println!("Changed password for {}", args.username);
+        }
+        UsersCommand::Role { username, role } => {
+            if !users.set_role(&username, role)? {
+                bail!("no such user: {username}");
+            }
+            println!("{username} is now {role:?}");
+        }
+        UsersCommand::Remove { username } => {
+            if !users.remove(&username)? {
+                bail!("no such user: {username}");
+            }
+            println!("Removed {username}");
+        }
+        UsersCommand::List => {
+            for user in users.load()? {
+                println!("{}\t{:?}", user.username, user.role);
+            }
+        }
+    }
+    Ok(())
+}
 
+fn handle_tokens(command: TokensCommand, tokens: &TokenStore) -> anyhow::Result<()> {
+    match command {
+        TokensCommand::Create {
+            name,
+            scopes,
+            expires_in_days,
+        } => {
+            let expires_at = expires_in_days
+                .map(|days| chrono::Utc::now() + chrono::Duration::days(days.into()));
+            let (secret, info) = tokens.create(&name, &scopes, expires_at, None)?;
+            eprintln!(
+                "Created token {} in {}; store it now, it is not shown again:",
+                info.id,
+                tokens.path().display()
+            );
+            println!("{secret}");
+        }
+        TokensCommand::List => {
+            for token in tokens.list()? {
+                let scopes: Vec<&str> = token.scopes.iter().map(|scope| scope.as_str()).collect();
+                let format_time = |time: Option<chrono::DateTime<chrono::Utc>>| {
+                    time.map(|time| time.to_rfc3339())
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                println!(
+                    "{}\t{}\t{}\texpires {}\tlast used {}",
+                    token.id,
+                    token.name,
+                    scopes.join(","),
+                    format_time(token.expires_at),
+                    format_time(token.last_used_at)
+                );
+            }
+        }
+        TokensCommand::Revoke { id } => {
+            if !tokens.revoke(&id)? {
+                bail!("no such token: {id}");
+            }
+            println!("Revoked {id}");
+        }
+    }
     Ok(())
 }
 
+fn read_password(from_stdin: bool) -> anyhow::Result<String> {
+    if from_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .context("failed reading password from stdin")?;
+        return Ok(line.trim_end_matches(['\r', '\n']).to_string());
+    }
+    let password = rpassword::prompt_password("Password: ")?;
+    if rpassword::prompt_password("Repeat password: ")? != password {
+        bail!("passwords do not match");
+    }
+    Ok(password)
+}
+
 async fn handle_systemd(command: SystemdCommand) -> anyhow::Result<()> {
     if !cfg!(target_os = "linux") {
         bail!("systemd install is only supported on Linux");
@@ -228,7 +642,12 @@ async fn systemd_install(args: SystemdInstallArgs) -> anyhow::Result<()> {
     ensure_dir(&data_dir)?;
     write_file(
         &env_path,
-        &render_env(&args.host, args.port, &args.default_host, &data_file),
+        &render_env(
+            &args.host,
+            args.port,
+            args.default_host.as_deref(),
+            &data_file,
+        ),
     )?;
     write_file(
         &unit_path,
@@ -338,13 +757,22 @@ fn remove_file_if_exists(path: &PathBuf) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn render_env(host: &str, port: u16, default_host: &str, data_file: &str) -> String {
+fn render_env(host: &str, port: u16, default_host: Option<&str>, data_file: &str) -> String {
+    let default_host = match default_host {
+        Some(default_host) => format!("DEFAULT_HOST={default_host}"),
+        None => "# Detected at startup unless set; see DEFAULT_HOST_STRATEGY\n\
+# DEFAULT_HOST=server.lan\n\
+# DEFAULT_HOST_STRATEGY=lan_ip"
+            .to_string(),
+    };
     format!(
         "# {APP_NAME} env\n\
 HOST={host}\n\
 PORT={port}\n\
-DEFAULT_HOST={default_host}\n\
-DATA_FILE={data_file}\n"
+{default_host}\n\
+DATA_FILE={data_file}\n\
+# Optional TOML config; the values above take precedence over it\n\
+# CONFIG_FILE=/etc/{APP_NAME}/config.toml\n"
     )
 }
 
@@ -358,7 +786,8 @@ Wants=network-online.target\n\
 [Service]\n\
 Type=simple\n\
 EnvironmentFile={env_path}\n\
-ExecStart={install_path} --host ${{HOST}} --port ${{PORT}} --default-host ${{DEFAULT_HOST}} --data-file ${{DATA_FILE}}\n\
+ExecStart={install_path}\n\
+ExecReload=/bin/kill -HUP $MAINPID\n\
 Restart=on-failure\n\
 RestartSec=2\n\
 \n\
@@ -1,5 +1,17 @@
+use crate::{
+    audit::AuditAction,
+    auth::{Role, Scope},
+    bundle::{BundleFormat, ImportMode},
+    groups,
+    host::{HostInfo, HostStrategy},
+    networks::Network,
+    rules::{Rule, RuleMatch, RuleSource},
+    tokens::TokenInfo,
+};
+use anyhow::{bail, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
@@ -11,7 +23,8 @@ pub enum ServiceProtocol {
     Other,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+/// Ordered as listed: running services sort first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum ServiceStatus {
     Running,
@@ -27,6 +40,8 @@ pub enum ServiceSource {
     Manual,
     #[default]
     Merged,
+    /// Only exists because a `services.d` file declares it.
+    Declared,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -36,6 +51,7 @@ pub struct ServiceEntry {
     pub display_name: String,
     #[serde(default)]
     pub description: Option<String>,
+    #[serde(default)]
     pub host: String,
     #[serde(default)]
     pub port: Option<u16>,
@@ -45,38 +61,135 @@ pub struct ServiceEntry {
     pub path: Option<String>,
     #[serde(default)]
     pub url: Option<String>,
+    /// The URL to open from the network of the request; only set in API
+    /// responses.
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub open_url: Option<String>,
     #[serde(default)]
     pub status: ServiceStatus,
     #[serde(default)]
     pub group: Option<String>,
     #[serde(default)]
     pub tags: Vec<String>,
+    /// Short names to search for, such as `jf` for Jellyfin.
+    #[serde(default)]
+    pub aliases: Vec<String>,
     #[serde(default)]
     pub icon: Option<String>,
     #[serde(default)]
     pub hidden: bool,
     #[serde(default)]
     pub favorite: bool,
+    /// Secondary pages such as API docs, admin or metrics.
+    #[serde(default)]
+    pub links: Vec<ServiceLink>,
+    /// Full URLs by access network name, used instead of the main URL when
+    /// the dashboard is opened from that network.
+    #[serde(default)]
+    pub network_urls: BTreeMap<String, String>,
     #[serde(default)]
     pub source: ServiceSource,
     #[serde(default)]
     pub locked_fields: Vec<String>,
+    /// Fields set by `services.d` declarations; locked for discovery and edits.
+    #[serde(default)]
+    pub declared_fields: Vec<String>,
+    /// User who last created or edited the entry through the API.
+    #[serde(default)]
+    pub updated_by: Option<String>,
     #[serde(default)]
     pub last_seen_at: Option<DateTime<Utc>>,
+    /// Manual sort position, lowest first.
+    #[serde(default)]
+    pub position: Option<u32>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When the service was last opened from the dashboard.
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+    /// How often the service was opened from the dashboard.
+    #[serde(default)]
+    pub launch_count: u32,
+    #[serde(default = "Utc::now")]
     pub updated_at: DateTime<Utc>,
 }
 
+/// A named page of a service besides its main URL.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ServiceLink {
+    pub name: String,
+    /// Used as is; otherwise the URL is built from the fields below, each
+    /// defaulting to the service's own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub protocol: Option<ServiceProtocol>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    /// Like [`ServiceEntry::open_url`]; cleared when links are saved.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub open_url: Option<String>,
+}
+
 impl ServiceEntry {
-    #[allow(dead_code)]
-    pub fn resolved_url(&self) -> Option<String> {
+    /// The main URL as opened from `network`: the service's URL for that
+    /// network if it has one, else the usual URL with the network's host in
+    /// place of `default_host`.
+    pub fn resolved_url(&self, network: Option<&Network>) -> Option<String> {
+        if let Some(url) = network.and_then(|network| self.network_urls.get(&network.name)) {
+            return Some(url.clone());
+        }
         if let Some(url) = &self.url {
             return Some(url.clone());
         }
-        build_service_url(&self.protocol, &self.host, self.port, self.path.as_deref())
+        build_service_url(
+            &self.protocol,
+            network.map_or(&self.host, |network| network.host_for(&self.host)),
+            self.port,
+            self.path.as_deref(),
+        )
+    }
+
+    pub fn link_url(&self, link: &ServiceLink, network: Option<&Network>) -> Option<String> {
+        if let Some(url) = &link.url {
+            return Some(url.clone());
+        }
+        let host = link.host.as_deref().unwrap_or(&self.host);
+        build_service_url(
+            link.protocol.as_ref().unwrap_or(&self.protocol),
+            network.map_or(host, |network| network.host_for(host)),
+            link.port.or(self.port),
+            link.path.as_deref(),
+        )
+    }
+
+    /// Sets `open_url` on the entry and its links for `network`.
+    pub fn with_open_urls(mut self, network: Option<&Network>) -> Self {
+        self.open_url = self.resolved_url(network);
+        let urls: Vec<_> = self
+            .links
+            .iter()
+            .map(|link| self.link_url(link, network))
+            .collect();
+        for (link, url) in self.links.iter_mut().zip(urls) {
+            link.open_url = url;
+        }
+        self
     }
 
     pub fn is_locked(&self, field: &str) -> bool {
-        self.locked_fields.iter().any(|value| value == field)
+        self.locked_fields.iter().any(|value| value == field) || self.is_declared(field)
+    }
+
+    pub fn is_declared(&self, field: &str) -> bool {
+        self.declared_fields.iter().any(|value| value == field)
     }
 
     pub fn lock_field(&mut self, field: &str) {
@@ -86,7 +199,7 @@ impl ServiceEntry {
     }
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct CreateServiceRequest {
     pub service_name: String,
     #[serde(default)]
@@ -108,13 +221,22 @@ pub struct CreateServiceRequest {
     #[serde(default)]
     pub tags: Option<Vec<String>>,
     #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    #[serde(default)]
     pub icon: Option<String>,
     #[serde(default)]
     pub hidden: Option<bool>,
     #[serde(default)]
     pub favorite: Option<bool>,
     #[serde(default)]
+    pub links: Option<Vec<ServiceLink>>,
+    #[serde(default)]
+    pub network_urls: Option<BTreeMap<String, String>>,
+    #[serde(default)]
     pub locked_fields: Option<Vec<String>>,
+    /// Catalog app id; fields left out are filled in from the catalog.
+    #[serde(default)]
+    pub catalog: Option<String>,
 }
 
 impl CreateServiceRequest {
@@ -138,15 +260,25 @@ impl CreateServiceRequest {
                 .unwrap_or_else(|| infer_protocol_from_port(self.port)),
             path: clean_optional(self.path),
             url: clean_optional(self.url),
+            open_url: None,
             status: ServiceStatus::Unknown,
-            group: clean_optional(self.group),
+            group: clean_optional(self.group).map(|group| groups::canonical(&group).to_string()),
             tags: self.tags.unwrap_or_default(),
+            aliases: self.aliases.unwrap_or_default(),
             icon: clean_optional(self.icon),
             hidden: self.hidden.unwrap_or(false),
             favorite: self.favorite.unwrap_or(false),
+            links: self.links.unwrap_or_default(),
+            network_urls: self.network_urls.unwrap_or_default(),
             source: ServiceSource::Manual,
             locked_fields: self.locked_fields.unwrap_or_else(default_locked_fields),
+            declared_fields: Vec::new(),
+            updated_by: None,
             last_seen_at: None,
+            position: None,
+            created_at: Some(now),
+            last_used_at: None,
+            launch_count: 0,
             updated_at: now,
         };
         normalize_locked_fields(&mut entry.locked_fields);
@@ -177,12 +309,18 @@ pub struct UpdateServiceRequest {
     #[serde(default)]
     pub tags: Option<Vec<String>>,
     #[serde(default)]
+    pub aliases: Option<Vec<String>>,
+    #[serde(default)]
     pub icon: Option<Option<String>>,
     #[serde(default)]
     pub hidden: Option<bool>,
     #[serde(default)]
     pub favorite: Option<bool>,
     #[serde(default)]
+    pub links: Option<Vec<ServiceLink>>,
+    #[serde(default)]
+    pub network_urls: Option<BTreeMap<String, String>>,
+    #[serde(default)]
     pub locked_fields: Option<Vec<String>>,
     #[serde(default)]
     pub auto_lock: Option<bool>,
@@ -205,6 +343,96 @@ pub struct ServiceQuery {
     pub status: Option<ServiceStatus>,
     #[serde(default)]
     pub include_hidden: Option<bool>,
+    #[serde(default)]
+    pub sort: Option<ServiceSort>,
+}
+
+/// Listing order. Ties keep the manual order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceSort {
+    #[default]
+    Manual,
+    Name,
+    /// Running, then stopped, then unknown.
+    Status,
+    /// Most recently opened first.
+    LastUsed,
+    RecentlyAdded,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: Option<String>,
+    /// At most this many results; defaults to 20.
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub include_hidden: Option<bool>,
+}
+
+/// A service found by `/api/search`, best first.
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub service: ServiceEntry,
+    pub score: f64,
+    pub matches: Vec<SearchMatch>,
+}
+
+/// Where a search matched: a field, its value and the matched ranges as
+/// `[start, end)` character offsets into the value.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchMatch {
+    pub field: &'static str,
+    pub value: String,
+    pub ranges: Vec<[usize; 2]>,
+}
+
+/// The new order of some services, e.g. after dragging a card within a group.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReorderRequest {
+    /// Every id must belong to this group; omit for ungrouped services.
+    #[serde(default)]
+    pub group: Option<String>,
+    pub ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ExportQuery {
+    #[serde(default)]
+    pub format: Option<BundleFormat>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct ImportQuery {
+    #[serde(default)]
+    pub format: Option<BundleFormat>,
+    #[serde(default)]
+    pub mode: Option<ImportMode>,
+    #[serde(default)]
+    pub dry_run: Option<bool>,
+    /// Bookmark imports only: read links below this folder.
+    #[serde(default)]
+    pub folder: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct AuditQuery {
+    /// Actor name, or a kind such as `discovery` or `token`.
+    #[serde(default)]
+    pub actor: Option<String>,
+    #[serde(default)]
+    pub action: Option<AuditAction>,
+    #[serde(default)]
+    pub service_id: Option<String>,
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Keep only the most recent events.
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -226,6 +454,154 @@ pub struct DiscoveryRunResponse {
     pub summary: DiscoveryStatusInfo,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct SessionInfo {
+    pub auth_enabled: bool,
+    pub anonymous_read: bool,
+    pub authenticated: bool,
+    pub username: Option<String>,
+    pub role: Option<Role>,
+}
+
+/// What the caller may do here, so clients can hide controls that the
+/// server would refuse anyway.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct Capabilities {
+    pub read_only: bool,
+    pub can_edit: bool,
+    pub can_run_discovery: bool,
+    pub can_import: bool,
+    pub can_admin: bool,
+    /// Whether `POST /api/services/{id}/open` is accepted, so clients know
+    /// to record opened services.
+    pub can_record_use: bool,
+    pub show_hidden: bool,
+}
+
+/// The machine's detected names and addresses, and the `default_host` in
+/// use.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostResponse {
+    pub default_host: String,
+    /// Unset when `default_host` was configured explicitly.
+    pub default_host_strategy: Option<HostStrategy>,
+    #[serde(flatten)]
+    pub detected: HostInfo,
+}
+
+/// A group as listed by `/api/groups`.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupInfo {
+    pub key: String,
+    /// The name to show in the requested locale.
+    pub label: String,
+    pub builtin: bool,
+    /// Visible services in the group, hidden ones excluded.
+    pub services: usize,
+    pub icon: Option<String>,
+    pub description: Option<String>,
+    pub collapsed: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct CreateGroupRequest {
+    pub key: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub collapsed: Option<bool>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct UpdateGroupRequest {
+    /// Renames the group, or merges it into an existing one.
+    #[serde(default)]
+    pub key: Option<String>,
+    #[serde(default)]
+    pub icon: Option<Option<String>>,
+    #[serde(default)]
+    pub description: Option<Option<String>>,
+    #[serde(default)]
+    pub collapsed: Option<bool>,
+}
+
+/// Result of renaming, merging or deleting a group.
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupChange {
+    /// The group after the change; absent when it was deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<GroupInfo>,
+    /// Services moved to the new group, or ungrouped.
+    pub moved: Vec<String>,
+    /// Members left as they are because their group is declared.
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct CatalogQuery {
+    /// Matches id, name, aliases and description.
+    #[serde(default)]
+    pub q: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+}
+
+/// A unit to run through the classification rules, given directly or by
+/// the id of a known service.
+#[derive(Debug, Clone, Deserialize, Default)]
+pub struct RuleTestQuery {
+    #[serde(default)]
+    pub service_id: Option<String>,
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub process: Option<String>,
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleTestResult {
+    pub unit: String,
+    pub matched: Option<RuleMatch>,
+    pub rule: Option<Rule>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ListedRule {
+    pub source: RuleSource,
+    pub index: usize,
+    #[serde(flatten)]
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateTokenRequest {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    /// Days until the token stops working; omitted means it never expires.
+    #[serde(default)]
+    pub expires_in_days: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CreatedToken {
+    /// The secret to send as `Authorization: Bearer`. Not retrievable later.
+    pub token: String,
+    #[serde(flatten)]
+    pub info: TokenInfo,
+}
+
 pub fn service_id(service_name: &str) -> String {
     let mut output = String::with_capacity(service_name.len());
     let mut previous_dash = false;
@@ -241,6 +617,24 @@ pub fn service_id(service_name: &str) -> String {
     output.trim_matches('-').to_string()
 }
 
+/// Returns `id` unchanged when it is free, otherwise suffixes it with the
+/// current timestamp (and a counter if that is taken too).
+pub fn unique_service_id(services: &[ServiceEntry], id: &str) -> String {
+    let taken = |candidate: &str| services.iter().any(|value| value.id == candidate);
+    if !taken(id) {
+        return id.to_string();
+    }
+
+    let base = format!("{id}-{}", Utc::now().timestamp());
+    let mut candidate = base.clone();
+    let mut counter = 1;
+    while taken(&candidate) {
+        counter += 1;
+        candidate = format!("{base}-{counter}");
+    }
+    candidate
+}
+
 pub fn infer_protocol_from_port(port: Option<u16>) -> ServiceProtocol {
     match port {
         Some(443 | 8443) => ServiceProtocol::Https,
@@ -307,20 +701,80 @@ pub fn default_locked_fields() -> Vec<String> {
         "url".to_string(),
         "group".to_string(),
         "tags".to_string(),
+        "aliases".to_string(),
         "icon".to_string(),
         "description".to_string(),
         "hidden".to_string(),
         "favorite".to_string(),
+        "links".to_string(),
+        "network_urls".to_string(),
     ]
 }
 
+/// Numbers services without a position after the last one, in their current
+/// order, so new services are appended.
+pub fn assign_positions(services: &mut [ServiceEntry]) {
+    let next = services
+        .iter()
+        .filter_map(|entry| entry.position)
+        .max()
+        .map_or(0, |position| position + 1);
+    let unpositioned = services.iter_mut().filter(|entry| entry.position.is_none());
+    for (entry, position) in unpositioned.zip(next..) {
+        entry.position = Some(position);
+    }
+}
+
+/// Trims every link and drops blank optional fields. Fails on a link
+/// without a name.
+pub fn normalize_links(links: &mut [ServiceLink]) -> Result<()> {
+    for (index, link) in links.iter_mut().enumerate() {
+        link.name = link.name.trim().to_string();
+        if link.name.is_empty() {
+            bail!("link {} has no name", index + 1);
+        }
+        link.url = clean_optional(link.url.take());
+        link.host = clean_optional(link.host.take());
+        link.path = clean_optional(link.path.take());
+        link.icon = clean_optional(link.icon.take());
+        link.open_url = None;
+    }
+    Ok(())
+}
+
+/// Trims and lowercases aliases, dropping blanks and duplicates.
+pub fn normalize_aliases(aliases: &mut Vec<String>) {
+    for alias in aliases.iter_mut() {
+        *alias = alias.trim().to_lowercase();
+    }
+    let mut seen = std::collections::HashSet::new();
+    aliases.retain(|alias| !alias.is_empty() && seen.insert(alias.clone()));
+}
+
+/// Trims network names and URLs and drops entries with either blank.
+pub fn normalize_network_urls(urls: &mut BTreeMap<String, String>) {
+    *urls = std::mem::take(urls)
+        .into_iter()
+        .map(|(network, url)| (network.trim().to_string(), url.trim().to_string()))
+        .filter(|(network, url)| !network.is_empty() && !url.is_empty())
+        .collect();
+}
+
 pub fn normalize_locked_fields(values: &mut Vec<String>) {
     values.retain(|value| !value.trim().is_empty());
     values.sort();
     values.dedup();
 }
 
-fn clean_optional(value: Option<String>) -> Option<String> {
+/// Trims ignore-list patterns, drops empty ones and sorts out duplicates.
+pub fn normalize_ignored(values: &mut Vec<String>) {
+    for value in values.iter_mut() {
+        *value = value.trim().to_string();
+    }
+    normalize_locked_fields(values);
+}
+
+pub fn clean_optional(value: Option<String>) -> Option<String> {
     value.and_then(|inner| {
         let trimmed = inner.trim();
         if trimmed.is_empty() {
@@ -0,0 +1,215 @@
+use crate::{
+    auth, config::glob_match, forward_auth::split_host_port, ratelimit::ClientIp, state::AppState,
+};
+use anyhow::{bail, Result};
+use axum::{
+    extract::FromRequestParts,
+    http::{header::HOST, request::Parts, HeaderMap, Uri},
+};
+use serde::Deserialize;
+use std::{collections::HashSet, convert::Infallible, net::IpAddr};
+
+/// A network the dashboard is reached from, such as the LAN, a VPN or a
+/// public reverse proxy. Requests are matched by host first, then by client
+/// address; the first matching network wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Key of the network in services' `network_urls`.
+    pub name: String,
+    /// Globs on the host the dashboard was opened as, e.g. `*.ts.net`.
+    pub hosts: Vec<String>,
+    /// Client addresses or CIDR ranges, e.g. `100.64.0.0/10`.
+    pub subnets: Vec<String>,
+    /// Replaces `default_host` in the URLs of services on this machine.
+    pub host: Option<String>,
+}
+
+impl NetworkConfig {
+    fn validate(&self) -> Result<()> {
+        if self.name.trim().is_empty() {
+            bail!("every network needs a name");
+        }
+        if self.hosts.is_empty() && self.subnets.is_empty() {
+            bail!("network {} needs hosts or subnets", self.name);
+        }
+        if self.hosts.iter().any(|host| host.trim().is_empty()) {
+            bail!("network {} has an empty host pattern", self.name);
+        }
+        if self
+            .host
+            .as_deref()
+            .is_some_and(|host| host.trim().is_empty())
+        {
+            bail!("network {} has an empty host", self.name);
+        }
+        auth::parse_networks(&self.subnets, &format!("network {}", self.name))?;
+        Ok(())
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        self.hosts
+            .iter()
+            .any(|glob| glob_match(&glob.trim().to_lowercase(), host))
+    }
+
+    fn contains(&self, client: IpAddr) -> bool {
+        auth::parse_networks(&self.subnets, "network")
+            .is_ok_and(|networks| networks.iter().any(|network| network.contains(&client)))
+    }
+}
+
+pub fn validate(networks: &[NetworkConfig]) -> Result<()> {
+    let mut names = HashSet::new();
+    for network in networks {
+        network.validate()?;
+        if !names.insert(network.name.trim()) {
+            bail!("network {} is defined twice", network.name);
+        }
+    }
+    Ok(())
+}
+
+/// The network a request came from, as used to build service URLs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub name: String,
+    host: Option<String>,
+    default_host: String,
+}
+
+impl Network {
+    /// `host`, or the network's own host when `host` is the default one.
+    pub fn host_for<'a>(&'a self, host: &'a str) -> &'a str {
+        match &self.host {
+            Some(own) if host.eq_ignore_ascii_case(&self.default_host) => own,
+            _ => host,
+        }
+    }
+}
+
+/// The first network matching the request host, else the first containing
+/// the client address.
+pub fn detect(
+    networks: &[NetworkConfig],
+    default_host: &str,
+    host: Option<&str>,
+    client: Option<IpAddr>,
+) -> Option<Network> {
+    let host = host.map(|host| split_host_port(host.trim()).0.to_lowercase());
+    let client = client.map(auth::canonical_ip);
+    networks
+        .iter()
+        .find(|network| {
+            host.as_deref()
+                .is_some_and(|host| network.matches_host(host))
+        })
+        .or_else(|| {
+            networks
+                .iter()
+                .find(|network| client.is_some_and(|client| network.contains(client)))
+        })
+        .map(|network| Network {
+            name: network.name.trim().to_string(),
+            host: network.host.as_deref().map(|host| host.trim().to_string()),
+            default_host: default_host.to_string(),
+        })
+}
+
+/// The host a request was sent to: `X-Forwarded-Host`, else `Host`, else
+/// the URI authority, which HTTP/2 carries instead of a Host header.
+pub fn request_host<'a>(headers: &'a HeaderMap, uri: &'a Uri) -> Option<&'a str> {
+    ["x-forwarded-host", HOST.as_str()]
+        .iter()
+        .find_map(|name| headers.get(*name).and_then(|value| value.to_str().ok()))
+        .and_then(|value| value.split(',').next())
+        .map(str::trim)
+        .or_else(|| uri.authority().map(|authority| authority.as_str()))
+}
+
+/// The [`Network`] of the request, if any matches. The host comes from
+/// `X-Forwarded-Host`, else `Host`; the client address is the one the rate
+/// limiter resolved. Neither is a security boundary: a spoofed header only
+/// changes which URLs the caller is shown.
+#[derive(Debug, Clone, Default)]
+pub struct RequestNetwork(pub Option<Network>);
+
+impl FromRequestParts<AppState> for RequestNetwork {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let settings = state.settings.read().await;
+        if settings.networks.is_empty() {
+            return Ok(Self(None));
+        }
+        let host = request_host(&parts.headers, &parts.uri);
+        let client = parts
+            .extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(address)| *address);
+        Ok(Self(detect(
+            &settings.networks,
+            &settings.default_host,
+            host,
+            client,
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_network_by_host_then_subnet() {
+        let networks = vec![
+            NetworkConfig {
+                name: "tailscale".to_string(),
+                hosts: vec!["*.ts.net".to_string()],
+                subnets: vec!["100.64.0.0/10".to_string()],
+                host: Some("100.100.1.2".to_string()),
+            },
+            NetworkConfig {
+                name: "public".to_string(),
+                hosts: vec!["nav.example.com".to_string()],
+                ..Default::default()
+            },
+        ];
+        validate(&networks).expect("valid networks");
+        let detect = |host, client: Option<&str>| {
+            detect(
+                &networks,
+                "server.lan",
+                host,
+                client.map(|client| client.parse().expect("ip")),
+            )
+            .map(|network| network.name)
+        };
+
+        assert_eq!(
+            detect(Some("NAV.example.com:443"), Some("100.64.3.4")).as_deref(),
+            Some("public")
+        );
+        assert_eq!(
+            detect(Some("server.lan:8080"), Some("100.64.3.4")).as_deref(),
+            Some("tailscale")
+        );
+        assert_eq!(
+            detect(Some("box.tail1234.ts.net"), None).as_deref(),
+            Some("tailscale")
+        );
+        assert_eq!(detect(Some("server.lan"), Some("192.168.1.5")), None);
+
+        let tailscale =
+            super::detect(&networks, "server.lan", Some("box.ts.net"), None).expect("tailscale");
+        assert_eq!(tailscale.host_for("server.lan"), "100.100.1.2");
+        assert_eq!(tailscale.host_for("192.168.1.1"), "192.168.1.1");
+
+        let mut duplicate = networks.clone();
+        duplicate[1].name = "tailscale".to_string();
+        assert!(validate(&duplicate).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use crate::models::{ServiceEntry, ServiceStatus};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::warn;
+
+const NOTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A webhook that receives a JSON POST when discovery finds changes.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotifierConfig {
+    pub url: String,
+    /// Only notify about status changes, not newly discovered services.
+    #[serde(default)]
+    pub status_only: bool,
+}
+
+impl NotifierConfig {
+    pub fn validate(&self) -> Result<()> {
+        match reqwest::Url::parse(&self.url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(()),
+            Ok(_) => bail!("notifier url {} must be http or https", self.url),
+            Err(error) => bail!("invalid notifier url {}: {error}", self.url),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct StatusChange {
+    pub id: String,
+    pub display_name: String,
+    pub from: ServiceStatus,
+    pub to: ServiceStatus,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiscoveryEvent {
+    pub event: &'static str,
+    pub added: Vec<String>,
+    pub status_changes: Vec<StatusChange>,
+}
+
+impl DiscoveryEvent {
+    pub fn between(before: &[ServiceEntry], after: &[ServiceEntry]) -> Self {
+        let mut added = Vec::new();
+        let mut status_changes = Vec::new();
+        for entry in after {
+            match before.iter().find(|value| value.id == entry.id) {
+                None => added.push(entry.id.clone()),
+                Some(previous) if previous.status != entry.status => {
+                    status_changes.push(StatusChange {
+                        id: entry.id.clone(),
+                        display_name: entry.display_name.clone(),
+                        from: previous.status.clone(),
+                        to: entry.status.clone(),
+                    });
+                }
+                Some(_) => {}
+            }
+        }
+
+        Self {
+            event: "discovery",
+            added,
+            status_changes,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.status_changes.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    http_client: reqwest::Client,
+}
+
+impl Notifier {
+    pub fn new() -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(NOTIFY_TIMEOUT)
+            .build()
+            .expect("Failed to build HTTP client");
+        Self { http_client }
+    }
+
+    /// Fire-and-forget delivery; failures are only logged.
+    pub fn send(&self, targets: &[NotifierConfig], event: DiscoveryEvent) {
+        for target in targets {
+            if event.is_empty() || (target.status_only && event.status_changes.is_empty()) {
+                continue;
+            }
+            let Ok(body) = serde_json::to_vec(&event) else {
+                continue;
+            };
+            let request = self
+                .http_client
+                .post(&target.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(body);
+            let url = target.url.clone();
+            tokio::spawn(async move {
+                match request.send().await {
+                    Ok(response) if !response.status().is_success() => {
+                        warn!(%url, status = %response.status(), "notifier rejected event");
+                    }
+                    Ok(_) => {}
+                    Err(error) => warn!(%url, "failed to notify: {error}"),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateServiceRequest;
+
+    fn service(name: &str, status: ServiceStatus) -> ServiceEntry {
+        let mut entry = CreateServiceRequest {
+            service_name: name.to_string(),
+            ..Default::default()
+        }
+        .into_entry("server.lan");
+        entry.status = status;
+        entry
+    }
+
+    #[test]
+    fn event_lists_new_services_and_status_changes() {
+        let before = vec![
+            service("jellyfin", ServiceStatus::Running),
+            service("grafana", ServiceStatus::Running),
+        ];
+        let after = vec![
+            service("jellyfin", ServiceStatus::Stopped),
+            service("grafana", ServiceStatus::Running),
+            service("immich", ServiceStatus::Running),
+        ];
+
+        let event = DiscoveryEvent::between(&before, &after);
+        assert_eq!(event.added, vec!["immich".to_string()]);
+        assert_eq!(event.status_changes.len(), 1);
+        assert_eq!(event.status_changes[0].id, "jellyfin");
+        assert_eq!(event.status_changes[0].to, ServiceStatus::Stopped);
+    }
+}
@@ -0,0 +1,381 @@
+use crate::{auth, state::AppState};
+use anyhow::{bail, Result};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    net::{IpAddr, SocketAddr},
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+/// Rejections within this window count towards a ban.
+const STRIKE_WINDOW: Duration = Duration::from_secs(600);
+/// Idle clients are forgotten once this many are tracked.
+const PRUNE_THRESHOLD: usize = 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    /// Mutating API requests per client and minute; also the burst size.
+    pub writes_per_minute: u32,
+    /// `POST /api/discovery/run` per client and minute.
+    pub discoveries_per_minute: u32,
+    /// Rejected requests within ten minutes that earn a ban; 0 never bans.
+    pub ban_after: u32,
+    pub ban_minutes: u32,
+    /// Addresses or CIDR ranges that are never limited.
+    pub exempt: Vec<String>,
+    /// Reverse proxies whose `X-Forwarded-For` names the real client, in
+    /// addition to `auth.proxy.trusted_proxies`.
+    pub trusted_proxies: Vec<String>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            writes_per_minute: 60,
+            discoveries_per_minute: 4,
+            ban_after: 10,
+            ban_minutes: 15,
+            exempt: Vec::new(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.writes_per_minute == 0 || self.discoveries_per_minute == 0 {
+            bail!("rate_limit per-minute limits must be at least 1");
+        }
+        if self.ban_after > 0 && self.ban_minutes == 0 {
+            bail!("rate_limit.ban_minutes must be at least 1 when bans are enabled");
+        }
+        auth::parse_networks(&self.exempt, "rate_limit.exempt")?;
+        auth::parse_networks(&self.trusted_proxies, "rate_limit.trusted_proxies")?;
+        Ok(())
+    }
+
+    fn exempts(&self, client: IpAddr) -> bool {
+        auth::parse_networks(&self.exempt, "rate_limit.exempt")
+            .is_ok_and(|networks| networks.iter().any(|network| network.contains(&client)))
+    }
+}
+
+/// The client behind `peer`: the last `X-Forwarded-For` hop when the peer
+/// is one of `trusted_proxies`, otherwise the peer itself.
+fn client_ip(trusted_proxies: &[String], peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+    let peer = auth::canonical_ip(peer);
+    let trusted = auth::parse_networks(trusted_proxies, "trusted proxy")
+        .is_ok_and(|networks| networks.iter().any(|network| network.contains(&peer)));
+    if !trusted {
+        return peer;
+    }
+    headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|value| value.trim().parse().ok())
+        .map(auth::canonical_ip)
+        .unwrap_or(peer)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Class {
+    Write,
+    Discovery,
+}
+
+impl Class {
+    fn of(request: &Request) -> Option<Self> {
+        let path = request.uri().path();
+        match *request.method() {
+            Method::POST if path == "/api/discovery/run" => Some(Self::Discovery),
+            // Forward-auth checks carry the proxied request's method.
+            _ if path == "/api/auth/verify" => None,
+            Method::GET | Method::HEAD | Method::OPTIONS => None,
+            _ => Some(Self::Write),
+        }
+    }
+}
+
+/// Why a request was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limited {
+    pub retry_after: Duration,
+    pub banned: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ban {
+    pub address: IpAddr,
+    pub until: DateTime<Utc>,
+    pub strikes: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    /// Takes one token from a bucket holding `per_minute`, refilled evenly.
+    fn take(&mut self, per_minute: u32, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(per_minute);
+        let rate = capacity / 60.0;
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate))
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Client {
+    writes: Option<Bucket>,
+    discoveries: Option<Bucket>,
+    strikes: VecDeque<Instant>,
+    banned: Option<(Instant, DateTime<Utc>)>,
+    seen: Instant,
+}
+
+/// Per-client token buckets and bans, kept in memory only.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    clients: Mutex<HashMap<IpAddr, Client>>,
+}
+
+impl RateLimiter {
+    pub fn check(
+        &self,
+        config: &RateLimitConfig,
+        client: IpAddr,
+        class: Option<Class>,
+        now: Instant,
+    ) -> Result<(), Limited> {
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        if clients.len() > PRUNE_THRESHOLD {
+            clients.retain(|_, entry| {
+                entry.banned.is_some() || now.saturating_duration_since(entry.seen) < STRIKE_WINDOW
+            });
+        }
+        let entry = clients.entry(client).or_insert_with(|| Client {
+            writes: None,
+            discoveries: None,
+            strikes: VecDeque::new(),
+            banned: None,
+            seen: now,
+        });
+        entry.seen = now;
+
+        match entry.banned {
+            Some((until, _)) if until > now => {
+                return Err(Limited {
+                    retry_after: until - now,
+                    banned: true,
+                })
+            }
+            Some(_) => {
+                entry.banned = None;
+                entry.strikes.clear();
+            }
+            None => {}
+        }
+
+        let Some(class) = class else {
+            return Ok(());
+        };
+        let (bucket, per_minute) = match class {
+            Class::Write => (&mut entry.writes, config.writes_per_minute),
+            Class::Discovery => (&mut entry.discoveries, config.discoveries_per_minute),
+        };
+        let bucket = bucket.get_or_insert(Bucket {
+            tokens: f64::from(per_minute),
+            updated: now,
+        });
+        let Err(wait) = bucket.take(per_minute, now) else {
+            return Ok(());
+        };
+
+        while entry
+            .strikes
+            .front()
+            .is_some_and(|strike| now.saturating_duration_since(*strike) >= STRIKE_WINDOW)
+        {
+            entry.strikes.pop_front();
+        }
+        entry.strikes.push_back(now);
+        if config.ban_after > 0 && entry.strikes.len() >= config.ban_after as usize {
+            let length = Duration::from_secs(u64::from(config.ban_minutes) * 60);
+            let until = Utc::now() + chrono::Duration::from_std(length).unwrap_or_default();
+            entry.banned = Some((now + length, until));
+            warn!(%client, until = %until, "banning client after repeated rate limit hits");
+            return Err(Limited {
+                retry_after: length,
+                banned: true,
+            });
+        }
+        Err(Limited {
+            retry_after: wait,
+            banned: false,
+        })
+    }
+
+    pub fn bans(&self, now: Instant) -> Vec<Ban> {
+        let clients = self.clients.lock().expect("rate limiter lock poisoned");
+        let mut bans: Vec<Ban> = clients
+            .iter()
+            .filter_map(|(address, entry)| {
+                let (until, until_utc) = entry.banned?;
+                (until > now).then_some(Ban {
+                    address: *address,
+                    until: until_utc,
+                    strikes: entry.strikes.len(),
+                })
+            })
+            .collect();
+        bans.sort_by_key(|ban| ban.until);
+        bans
+    }
+
+    /// Lifts the ban on `address` and forgets its strikes and buckets.
+    pub fn unban(&self, address: IpAddr) -> bool {
+        let mut clients = self.clients.lock().expect("rate limiter lock poisoned");
+        clients
+            .remove(&auth::canonical_ip(address))
+            .is_some_and(|entry| entry.banned.is_some())
+    }
+
+    pub fn unban_all(&self) {
+        self.clients
+            .lock()
+            .expect("rate limiter lock poisoned")
+            .clear();
+    }
+}
+
+/// Address of the client behind the request, after `X-Forwarded-For` from
+/// `rate_limit.trusted_proxies` or `auth.proxy.trusted_proxies`. Attached by
+/// [`limit`] for everything that goes by client address; absent without a
+/// peer address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Applies `[rate_limit]` to API requests: bans cover every route, budgets
+/// only writes and discovery runs. Requests without a peer address (tests)
+/// pass through.
+pub async fn limit(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let (config, trusted_proxies) = {
+        let settings = state.settings.read().await;
+        // A proxy trusted with the user's identity also names the client.
+        let mut trusted_proxies = settings.rate_limit.trusted_proxies.clone();
+        if let Some(proxy) = &settings.auth.proxy {
+            trusted_proxies.extend(proxy.trusted_proxies.iter().cloned());
+        }
+        (settings.rate_limit.clone(), trusted_proxies)
+    };
+    let Some(peer) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(address)| address.ip())
+    else {
+        return next.run(request).await;
+    };
+    let client = client_ip(&trusted_proxies, peer, request.headers());
+    request.extensions_mut().insert(ClientIp(client));
+    if !config.enabled || config.exempts(client) {
+        return next.run(request).await;
+    }
+
+    match state
+        .rate_limiter
+        .check(&config, client, Class::of(&request), Instant::now())
+    {
+        Ok(()) => next.run(request).await,
+        Err(limited) => {
+            let message = if limited.banned {
+                "too many requests; temporarily banned"
+            } else {
+                "too many requests"
+            };
+            let mut response = auth::deny(StatusCode::TOO_MANY_REQUESTS, message);
+            let seconds = limited.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(seconds));
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_refill_and_repeat_offenders_are_banned() {
+        let config = RateLimitConfig {
+            writes_per_minute: 2,
+            ban_after: 3,
+            ..Default::default()
+        };
+        let limiter = RateLimiter::default();
+        let client: IpAddr = "192.168.1.50".parse().expect("ip");
+        let start = Instant::now();
+        let check = |class, offset| {
+            limiter.check(&config, client, class, start + Duration::from_secs(offset))
+        };
+
+        assert!(check(Some(Class::Write), 0).is_ok());
+        assert!(check(Some(Class::Write), 0).is_ok());
+        let limited = check(Some(Class::Write), 0).expect_err("bucket empty");
+        assert!(!limited.banned);
+        assert_eq!(limited.retry_after, Duration::from_secs(30));
+        assert!(check(None, 0).is_ok());
+        assert!(check(Some(Class::Discovery), 0).is_ok());
+
+        assert!(check(Some(Class::Write), 30).is_ok());
+        assert!(check(Some(Class::Write), 30).is_err());
+        let banned = check(Some(Class::Write), 30).expect_err("third strike");
+        assert!(banned.banned);
+        assert!(check(None, 31).is_err());
+        assert_eq!(limiter.bans(start + Duration::from_secs(31)).len(), 1);
+
+        assert!(limiter.unban(client));
+        assert!(check(Some(Class::Write), 31).is_ok());
+        assert!(limiter.bans(start).is_empty());
+    }
+
+    #[test]
+    fn forwarded_for_only_from_trusted_proxies() {
+        let trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.2.3.4, 192.168.1.9"),
+        );
+        let proxy: IpAddr = "10.0.0.2".parse().expect("ip");
+        let other: IpAddr = "192.168.1.20".parse().expect("ip");
+        assert_eq!(
+            client_ip(&trusted_proxies, proxy, &headers),
+            "192.168.1.9".parse::<IpAddr>().expect("ip")
+        );
+        assert_eq!(client_ip(&trusted_proxies, other, &headers), other);
+    }
+}
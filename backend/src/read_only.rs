@@ -0,0 +1,113 @@
+use crate::{auth, ratelimit::ClientIp, state::AppState};
+use anyhow::Result;
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{request::Parts, Method, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use serde::Deserialize;
+use std::{convert::Infallible, net::IpAddr};
+
+/// Writes that stay allowed for read-only clients: signing in and out, and
+/// forward-auth checks, which carry the proxied request's method.
+const ALLOWED_WRITES: &[&str] = &["/api/auth/login", "/api/auth/logout", "/api/auth/verify"];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReadOnlyConfig {
+    /// Reject writes from everyone.
+    pub enabled: bool,
+    /// Addresses or CIDR ranges, e.g. wall tablets, that may only read.
+    /// Behind a reverse proxy these match the `X-Forwarded-For` client once
+    /// the proxy is in `rate_limit.trusted_proxies` or
+    /// `auth.proxy.trusted_proxies`.
+    pub clients: Vec<String>,
+    /// Read-only clients never see hidden services.
+    pub hide_hidden: bool,
+}
+
+impl ReadOnlyConfig {
+    pub fn validate(&self) -> Result<()> {
+        auth::parse_networks(&self.clients, "read_only.clients")?;
+        Ok(())
+    }
+
+    fn applies(&self, client: Option<IpAddr>) -> bool {
+        self.enabled
+            || client.is_some_and(|client| {
+                auth::parse_networks(&self.clients, "read_only.clients")
+                    .is_ok_and(|networks| networks.iter().any(|network| network.contains(&client)))
+            })
+    }
+}
+
+/// What the read-only settings leave a request allowed to do. Handlers
+/// that list services take it as an extractor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Restrictions {
+    pub read_only: bool,
+    pub hide_hidden: bool,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Restrictions {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<Restrictions>()
+            .copied()
+            .unwrap_or_default())
+    }
+}
+
+/// Rejects writes from read-only clients and attaches [`Restrictions`].
+pub async fn guard(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    let config = state.settings.read().await.read_only.clone();
+    let client = request
+        .extensions()
+        .get::<ClientIp>()
+        .map(|ClientIp(address)| *address);
+    if !config.applies(client) {
+        return next.run(request).await;
+    }
+
+    let is_read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if !is_read && !ALLOWED_WRITES.contains(&request.uri().path()) {
+        return auth::deny(StatusCode::FORBIDDEN, "read-only mode");
+    }
+    request.extensions_mut().insert(Restrictions {
+        read_only: true,
+        hide_hidden: config.hide_hidden,
+    });
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_globally_or_to_listed_clients() {
+        let tablet: IpAddr = "192.168.1.40".parse().expect("ip");
+        let laptop: IpAddr = "192.168.1.20".parse().expect("ip");
+        let config = ReadOnlyConfig {
+            clients: vec!["192.168.1.40/32".to_string()],
+            ..Default::default()
+        };
+        config.validate().expect("valid clients");
+        assert!(config.applies(Some(tablet)));
+        assert!(!config.applies(Some(laptop)));
+        assert!(!config.applies(None));
+
+        let global = ReadOnlyConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        assert!(global.applies(None));
+    }
+}
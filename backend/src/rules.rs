@@ -0,0 +1,341 @@
+use crate::{
+    catalog,
+    config::{glob_match, Settings},
+    groups,
+    models::{ServiceEntry, ServiceProtocol},
+};
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+use tracing::warn;
+
+const BUILTIN_RULES: &str = include_str!("default_rules.toml");
+
+/// Matches discovered services and sets how they are shown. Every condition
+/// that is set must hold; a rule without conditions matches everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rule {
+    pub name: Option<String>,
+    /// Substrings of the unit name without `.service`.
+    #[serde(rename = "match", skip_serializing_if = "Vec::is_empty")]
+    pub contains: Vec<String>,
+    /// Globs on the full unit name, e.g. `jellyfin*.service`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub unit: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit_regex: Option<String>,
+    #[serde(skip)]
+    pub(crate) compiled_unit_regex: CompiledRegex,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub port: Vec<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_port: Option<bool>,
+    /// Globs on the name of the process holding the port.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub process: Vec<String>,
+    /// Globs on the container image, when it is known.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub image: Vec<String>,
+
+    pub group: Option<String>,
+    pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// Unset means visible.
+    pub hidden: Option<bool>,
+    pub protocol: Option<ServiceProtocol>,
+    pub path: Option<String>,
+}
+
+/// `unit_regex`, compiled on first use. Clones share the cell, so the
+/// settings snapshot each discovery run matches against compiles it into
+/// the stored rule too, and later runs reuse it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompiledRegex(Arc<OnceLock<Option<Regex>>>);
+
+impl CompiledRegex {
+    fn get(&self, pattern: &str) -> Option<&Regex> {
+        self.0
+            .get_or_init(|| match Regex::new(pattern) {
+                Ok(regex) => Some(regex),
+                Err(error) => {
+                    warn!("ignoring invalid unit_regex {pattern:?}: {error}");
+                    None
+                }
+            })
+            .as_ref()
+    }
+}
+
+/// Always equal: the cache follows `unit_regex`, which is compared.
+impl PartialEq for CompiledRegex {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CompiledRegex {}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct RulesFile {
+    rules: Vec<Rule>,
+}
+
+/// What a rule is matched against.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Subject<'a> {
+    pub unit: &'a str,
+    pub port: Option<u16>,
+    pub process: Option<&'a str>,
+    pub image: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleSource {
+    /// `[[classification]]` in the config file.
+    Config,
+    /// `discovery.rules_file`.
+    File,
+    /// The app catalog, for units named after a known app.
+    Catalog,
+    Builtin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuleMatch {
+    pub source: RuleSource,
+    /// 1-based position within its source.
+    pub index: usize,
+    pub name: Option<String>,
+}
+
+impl Rule {
+    pub fn validate(&self, label: &str) -> Result<()> {
+        if !self.contains.is_empty() && self.contains.iter().all(|value| value.trim().is_empty()) {
+            bail!("{label} has no match patterns");
+        }
+        for glob in self.unit.iter().chain(&self.process).chain(&self.image) {
+            if glob.trim().is_empty() {
+                bail!("{label} has an empty pattern");
+            }
+        }
+        if let Some(pattern) = &self.unit_regex {
+            Regex::new(pattern).with_context(|| format!("{label} has an invalid unit_regex"))?;
+        }
+        Ok(())
+    }
+
+    pub fn matches(&self, subject: &Subject) -> bool {
+        let unit = subject.unit.trim().to_lowercase();
+        let stem = unit.trim_end_matches(".service");
+        let any_glob = |globs: &[String], value: Option<&str>| {
+            globs.is_empty()
+                || value.is_some_and(|value| {
+                    let value = value.to_lowercase();
+                    globs
+                        .iter()
+                        .any(|glob| glob_match(&glob.to_lowercase(), &value))
+                })
+        };
+
+        (self.contains.is_empty()
+            || self
+                .contains
+                .iter()
+                .filter(|value| !value.trim().is_empty())
+                .any(|value| stem.contains(&value.to_lowercase())))
+            && any_glob(&self.unit, Some(&unit))
+            && self.unit_regex.as_deref().map_or(true, |pattern| {
+                self.compiled_unit_regex
+                    .get(pattern)
+                    .is_some_and(|regex| regex.is_match(&unit))
+            })
+            && (self.port.is_empty() || subject.port.is_some_and(|port| self.port.contains(&port)))
+            && self
+                .has_port
+                .map_or(true, |has_port| has_port == subject.port.is_some())
+            && any_glob(&self.process, subject.process)
+            && any_glob(&self.image, subject.image)
+    }
+
+    /// Fills in what the entry does not already have. Visibility, and the
+    /// protocol when the rule names one, are always set.
+    pub fn apply(&self, entry: &mut ServiceEntry) {
+        if entry.group.is_none() {
+            entry.group = self
+                .group
+                .as_deref()
+                .map(|group| groups::canonical(group).to_string());
+        }
+        if entry.icon.is_none() {
+            entry.icon = self.icon.clone();
+        }
+        for tag in &self.tags {
+            if !entry.tags.contains(tag) {
+                entry.tags.push(tag.clone());
+            }
+        }
+        entry.hidden = self.hidden.unwrap_or(false);
+        if let Some(protocol) = &self.protocol {
+            entry.protocol = protocol.clone();
+        }
+        if entry.path.is_none() {
+            entry.path = self.path.clone();
+        }
+    }
+}
+
+/// The rules that ship with the server, from `default_rules.toml`.
+pub fn builtin() -> &'static [Rule] {
+    static RULES: OnceLock<Vec<Rule>> = OnceLock::new();
+    RULES.get_or_init(|| {
+        toml::from_str::<RulesFile>(BUILTIN_RULES)
+            .expect("built-in rules should parse")
+            .rules
+    })
+}
+
+/// Reads and validates a rules file of `[[rules]]` tables.
+pub fn load_file(path: &Path) -> Result<Vec<Rule>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed reading {}", path.display()))?;
+    let file: RulesFile =
+        toml::from_str(&content).with_context(|| format!("failed parsing {}", path.display()))?;
+    for (index, rule) in file.rules.iter().enumerate() {
+        rule.validate(&format!("{} rule {}", path.display(), index + 1))?;
+    }
+    Ok(file.rules)
+}
+
+/// The first rule matching `subject`: config rules, then the rules file,
+/// then the app catalog, then the built-in rules.
+pub fn find<'a>(settings: &'a Settings, subject: &Subject) -> Option<(RuleMatch, Cow<'a, Rule>)> {
+    first_match(RuleSource::Config, &settings.classification, subject)
+        .or_else(|| first_match(RuleSource::File, &settings.rules, subject))
+        .or_else(|| {
+            catalog::find(subject).map(|(index, app)| {
+                let found = RuleMatch {
+                    source: RuleSource::Catalog,
+                    index,
+                    name: Some(app.id.clone()),
+                };
+                (found, Cow::Owned(app.rule()))
+            })
+        })
+        .or_else(|| first_match(RuleSource::Builtin, builtin(), subject))
+}
+
+fn first_match<'a>(
+    source: RuleSource,
+    rules: &'a [Rule],
+    subject: &Subject,
+) -> Option<(RuleMatch, Cow<'a, Rule>)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.matches(subject))
+        .map(|(index, rule)| {
+            let found = RuleMatch {
+                source,
+                index: index + 1,
+                name: rule.name.clone(),
+            };
+            (found, Cow::Borrowed(rule))
+        })
+}
+
+/// Applies the first matching rule to `entry`.
+pub fn classify(
+    entry: &mut ServiceEntry,
+    subject: &Subject,
+    settings: &Settings,
+) -> Option<RuleMatch> {
+    let (found, rule) = find(settings, subject)?;
+    rule.apply(entry);
+    Some(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subject(unit: &str, port: Option<u16>) -> Subject<'_> {
+        Subject {
+            unit,
+            port,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn builtin_rules_parse_and_validate() {
+        assert!(!builtin().is_empty());
+        for rule in builtin() {
+            rule.validate("built-in rule").expect("valid");
+        }
+        let settings = Settings::default();
+        let (found, rule) = find(&settings, &subject("my-jellyfin.service", Some(8096)))
+            .expect("catalog app should match");
+        assert_eq!(found.source, RuleSource::Catalog);
+        assert_eq!(rule.icon.as_deref(), Some("🎬"));
+        let (found, _) = find(&settings, &subject("myjellyfin.service", Some(8096)))
+            .expect("media rule should match");
+        assert_eq!(found.source, RuleSource::Builtin);
+        assert_eq!(found.name.as_deref(), Some("media"));
+        let (fallback, _) =
+            find(&settings, &subject("inventory.service", Some(8000))).expect("fallback");
+        assert_eq!(fallback.name.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn conditions_must_all_hold() {
+        let rule = Rule {
+            unit: vec!["*-exporter.service".to_string()],
+            unit_regex: Some("^node".to_string()),
+            port: vec![9100],
+            process: vec!["node_export*".to_string()],
+            ..Default::default()
+        };
+        rule.validate("rule").expect("valid");
+        let mut matching = subject("node-exporter.service", Some(9100));
+        matching.process = Some("node_exporter");
+        // Matching a snapshot, as discovery runs do, compiles the stored rule
+        let snapshot = rule.clone();
+        assert!(snapshot.matches(&matching));
+        assert!(rule.compiled_unit_regex.0.get().is_some());
+        assert!(rule.matches(&matching));
+        assert!(!rule.matches(&Subject {
+            port: Some(9101),
+            ..matching
+        }));
+        assert!(!rule.matches(&Subject {
+            process: None,
+            ..matching
+        }));
+
+        let image = Rule {
+            image: vec!["*/linuxserver/*".to_string()],
+            ..Default::default()
+        };
+        assert!(!image.matches(&matching));
+        assert!(image.matches(&Subject {
+            image: Some("lscr.io/linuxserver/jellyfin:latest"),
+            ..matching
+        }));
+
+        let invalid = Rule {
+            unit_regex: Some("(".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate("rule").is_err());
+        assert!(!invalid.matches(&matching));
+    }
+}
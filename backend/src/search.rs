@@ -0,0 +1,329 @@
+use crate::{
+    groups,
+    models::{SearchHit, SearchMatch, SearchQuery, ServiceEntry},
+};
+use std::borrow::Cow;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+/// Ranks `services` against the query. Every word of the query has to match
+/// some field, exactly, as a subsequence or within a typo or two; favorites
+/// and often launched services rank higher. An empty query lists services
+/// by that weighting alone, as a launcher would.
+pub fn search<'a>(
+    services: impl IntoIterator<Item = &'a ServiceEntry>,
+    query: &SearchQuery,
+) -> Vec<SearchHit> {
+    let words: Vec<Vec<char>> = query
+        .q
+        .as_deref()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|word| word.chars().map(fold).collect())
+        .collect();
+    let mut hits: Vec<SearchHit> = services
+        .into_iter()
+        .filter_map(|entry| {
+            let (relevance, matches) = match_entry(entry, &words)?;
+            let score = relevance * weight(entry);
+            Some(SearchHit {
+                service: entry.clone(),
+                score: (score * 1000.0).round() / 1000.0,
+                matches,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| {
+                let position = |hit: &SearchHit| hit.service.position.unwrap_or(u32::MAX);
+                position(a).cmp(&position(b))
+            })
+            .then_with(|| a.service.display_name.cmp(&b.service.display_name))
+    });
+    hits.truncate(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    hits
+}
+
+/// Favorites count a quarter more; launches add a little, less with each.
+fn weight(entry: &ServiceEntry) -> f64 {
+    let favorite = if entry.favorite { 0.25 } else { 0.0 };
+    1.0 + favorite + 0.1 * f64::from(entry.launch_count).ln_1p()
+}
+
+/// The searchable fields of `entry` and how much a match in each counts.
+fn fields(entry: &ServiceEntry) -> Vec<(&'static str, f64, Cow<'_, str>)> {
+    let mut fields = vec![("display_name", 1.0, Cow::from(entry.display_name.as_str()))];
+    fields.extend(
+        entry
+            .aliases
+            .iter()
+            .map(|alias| ("aliases", 1.2, Cow::from(alias.as_str()))),
+    );
+    fields.push(("service_name", 0.8, Cow::from(entry.service_name.as_str())));
+    fields.extend(
+        entry
+            .tags
+            .iter()
+            .map(|tag| ("tags", 0.7, Cow::from(tag.as_str()))),
+    );
+    if let Some(group) = &entry.group {
+        fields.extend(groups::names(group).map(|name| ("group", 0.6, Cow::from(name))));
+    }
+    if let Some(port) = entry.port {
+        fields.push(("port", 0.6, Cow::from(port.to_string())));
+    }
+    if let Some(description) = &entry.description {
+        fields.push(("description", 0.4, Cow::from(description.as_str())));
+    }
+    fields
+}
+
+/// The average score of the best match for each word, or `None` when a
+/// word matches nothing.
+fn match_entry(entry: &ServiceEntry, words: &[Vec<char>]) -> Option<(f64, Vec<SearchMatch>)> {
+    let fields = fields(entry);
+    let mut total = 0.0;
+    let mut matches: Vec<SearchMatch> = Vec::new();
+    for word in words {
+        let (score, field, value, ranges) = fields
+            .iter()
+            .filter_map(|(field, weight, value)| {
+                let (score, ranges) = fuzzy_match(word, value)?;
+                Some((score * weight, *field, value, ranges))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))?;
+        total += score;
+        match matches
+            .iter_mut()
+            .find(|found| found.field == field && found.value == *value)
+        {
+            Some(found) => {
+                found.ranges.extend(ranges);
+                found.ranges = merge_ranges(std::mem::take(&mut found.ranges));
+            }
+            None => matches.push(SearchMatch {
+                field,
+                value: value.to_string(),
+                ranges,
+            }),
+        }
+    }
+    let relevance = if words.is_empty() {
+        1.0
+    } else {
+        total / words.len() as f64
+    };
+    Some((relevance, matches))
+}
+
+fn fold(character: char) -> char {
+    character.to_lowercase().next().unwrap_or(character)
+}
+
+/// How well the lowercase `needle` matches `text`, with the matched
+/// character ranges. A substring scores 1 to 2.5, more for a larger share
+/// of the value and at its start or a word start; letters in order score up
+/// to 0.6 and a typo 0.35 or less.
+pub(crate) fn fuzzy_match(needle: &[char], text: &str) -> Option<(f64, Vec<[usize; 2]>)> {
+    let hay: Vec<char> = text.chars().map(fold).collect();
+    if needle.is_empty() || hay.is_empty() {
+        return None;
+    }
+    if let Some(start) = hay
+        .windows(needle.len())
+        .position(|window| window == needle)
+    {
+        let mut score = 1.0 + needle.len() as f64 / hay.len() as f64;
+        if start == 0 {
+            score += 0.5;
+        } else if !hay[start - 1].is_alphanumeric() {
+            score += 0.25;
+        }
+        return Some((score, vec![[start, start + needle.len()]]));
+    }
+    subsequence(&hay, needle).or_else(|| typo(&hay, needle))
+}
+
+/// The tightest run of `hay` holding `needle`'s letters in order, if it is
+/// at most three times as long as `needle`.
+fn subsequence(hay: &[char], needle: &[char]) -> Option<(f64, Vec<[usize; 2]>)> {
+    if needle.len() < 2 {
+        return None;
+    }
+    let mut best: Option<Vec<usize>> = None;
+    for start in (0..hay.len()).filter(|index| hay[*index] == needle[0]) {
+        let mut positions = vec![start];
+        let mut next = start + 1;
+        for character in &needle[1..] {
+            let Some(offset) = hay[next..].iter().position(|value| value == character) else {
+                break;
+            };
+            positions.push(next + offset);
+            next += offset + 1;
+        }
+        let span = |positions: &[usize]| positions[positions.len() - 1] - positions[0];
+        if positions.len() == needle.len()
+            && best
+                .as_deref()
+                .map_or(true, |best| span(&positions) < span(best))
+        {
+            best = Some(positions);
+        }
+    }
+    let positions = best?;
+    let span = positions[positions.len() - 1] - positions[0] + 1;
+    if span > needle.len() * 3 {
+        return None;
+    }
+    let ranges = positions.iter().map(|index| [*index, index + 1]).collect();
+    Some((
+        0.6 * needle.len() as f64 / span as f64,
+        merge_ranges(ranges),
+    ))
+}
+
+/// The word of `hay`, or its start, within one typo of `needle` (two for
+/// needles longer than five letters).
+fn typo(hay: &[char], needle: &[char]) -> Option<(f64, Vec<[usize; 2]>)> {
+    if needle.len() < 3 {
+        return None;
+    }
+    let allowed = if needle.len() <= 5 { 1 } else { 2 };
+    let mut best: Option<(usize, [usize; 2])> = None;
+    let mut start = 0;
+    while start < hay.len() {
+        if !hay[start].is_alphanumeric() {
+            start += 1;
+            continue;
+        }
+        let end = hay[start..]
+            .iter()
+            .position(|character| !character.is_alphanumeric())
+            .map_or(hay.len(), |length| start + length);
+        let word = &hay[start..end];
+        let prefix = &word[..word.len().min(needle.len())];
+        let candidates = [
+            (edit_distance(needle, prefix), start + prefix.len()),
+            (edit_distance(needle, word), end),
+        ];
+        for (distance, matched_end) in candidates {
+            if distance <= allowed && best.map_or(true, |(best, _)| distance < best) {
+                best = Some((distance, [start, matched_end]));
+            }
+        }
+        start = end;
+    }
+    best.map(|(distance, range)| (0.5 - 0.15 * distance as f64, vec![range]))
+}
+
+/// Edits, counting a swap of neighbours as one, to turn `a` into `b`.
+fn edit_distance(a: &[char], b: &[char]) -> usize {
+    let mut rows = vec![(0..=b.len()).collect::<Vec<usize>>()];
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            row[j] = (rows[i - 1][j] + 1)
+                .min(row[j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(rows[i - 2][j - 2] + 1);
+            }
+        }
+        rows.push(row);
+    }
+    rows[a.len()][b.len()]
+}
+
+fn merge_ranges(mut ranges: Vec<[usize; 2]>) -> Vec<[usize; 2]> {
+    ranges.sort();
+    let mut merged: Vec<[usize; 2]> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range[0] <= last[1] => last[1] = last[1].max(range[1]),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateServiceRequest;
+
+    fn service(name: &str, display_name: &str) -> ServiceEntry {
+        CreateServiceRequest {
+            service_name: name.to_string(),
+            display_name: Some(display_name.to_string()),
+            port: Some(8096),
+            ..Default::default()
+        }
+        .into_entry("server.lan")
+    }
+
+    fn chars(value: &str) -> Vec<char> {
+        value.chars().collect()
+    }
+
+    #[test]
+    fn matches_substrings_subsequences_and_typos() {
+        let (exact, ranges) = fuzzy_match(&chars("fin"), "Jellyfin").expect("substring");
+        assert_eq!(ranges, vec![[5, 8]]);
+        let (prefix, _) = fuzzy_match(&chars("jel"), "Jellyfin").expect("prefix");
+        assert!(prefix > exact);
+
+        let (_, ranges) = fuzzy_match(&chars("jlf"), "Jellyfin").expect("subsequence");
+        assert_eq!(ranges, vec![[0, 1], [2, 3], [5, 6]]);
+        assert_eq!(fuzzy_match(&chars("jn"), "Jellyfin"), None);
+
+        let (_, ranges) = fuzzy_match(&chars("grafnaa"), "My Grafana").expect("typo");
+        assert_eq!(ranges, vec![[3, 10]]);
+        assert_eq!(fuzzy_match(&chars("plex"), "Jellyfin"), None);
+    }
+
+    #[test]
+    fn ranks_by_relevance_favorites_and_launches() {
+        let mut jellyfin = service("jellyfin", "Jellyfin");
+        jellyfin.aliases = vec!["jf".to_string()];
+        let mut jackett = service("jackett", "Jackett");
+        jackett.position = Some(1);
+        let mut jellyseerr = service("jellyseerr", "Jellyseerr");
+        jellyseerr.favorite = true;
+        jellyseerr.launch_count = 12;
+        let services = [jellyfin, jackett, jellyseerr];
+        let ids = |q: &str| {
+            let query = SearchQuery {
+                q: Some(q.to_string()),
+                ..Default::default()
+            };
+            search(&services, &query)
+                .into_iter()
+                .map(|hit| hit.service.id)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids("jelly"), vec!["jellyseerr", "jellyfin"]);
+        assert_eq!(ids("jf"), vec!["jellyfin"]);
+        assert_eq!(ids("jellyfn"), vec!["jellyfin", "jellyseerr"]);
+        assert_eq!(ids("8096 jack"), vec!["jackett"]);
+        assert_eq!(ids(""), vec!["jellyseerr", "jackett", "jellyfin"]);
+
+        let query = SearchQuery {
+            q: Some("jf".to_string()),
+            ..Default::default()
+        };
+        let hit = &search(&services, &query)[0];
+        assert_eq!(
+            hit.matches,
+            vec![SearchMatch {
+                field: "aliases",
+                value: "jf".to_string(),
+                ranges: vec![[0, 2]],
+            }]
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use crate::{auth, state::AppState};
+use anyhow::{bail, Result};
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{
+            AUTHORIZATION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, HOST, ORIGIN, REFERRER_POLICY,
+            X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+        HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::Response,
+};
+use reqwest::Url;
+use serde::Deserialize;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Cookie-authenticated writes must carry this header. Pages on other
+/// origins cannot add it without passing a CORS preflight.
+pub const CSRF_HEADER: &str = "x-requested-with";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SecurityConfig {
+    /// Other origins whose pages may call the API, e.g. `https://home.lan`.
+    /// The UI itself is same-origin and needs no entry.
+    pub allowed_origins: Vec<String>,
+    /// Pages allowed to embed the UI in a frame; empty forbids framing.
+    pub frame_ancestors: Vec<String>,
+}
+
+impl SecurityConfig {
+    pub fn validate(&self) -> Result<()> {
+        for origin in &self.allowed_origins {
+            let valid = Url::parse(origin).is_ok_and(|url| {
+                matches!(url.scheme(), "http" | "https")
+                    && url
+                        .origin()
+                        .ascii_serialization()
+                        .eq_ignore_ascii_case(origin.trim_end_matches('/'))
+            });
+            if !valid {
+                bail!("security.allowed_origins entry {origin} must look like https://host[:port]");
+            }
+        }
+        for source in &self.frame_ancestors {
+            if source.is_empty()
+                || source.contains(|value: char| value == ';' || value.is_whitespace())
+            {
+                bail!("invalid security.frame_ancestors entry: {source:?}");
+            }
+        }
+        Ok(())
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed.trim_end_matches('/').eq_ignore_ascii_case(origin))
+    }
+
+    fn content_security_policy(&self) -> String {
+        let frame_ancestors = if self.frame_ancestors.is_empty() {
+            "'none'".to_string()
+        } else {
+            self.frame_ancestors.join(" ")
+        };
+        format!(
+            "default-src 'self'; script-src 'self'; \
+             style-src 'self' 'unsafe-inline' https://fonts.googleapis.com; \
+             font-src 'self' https://fonts.gstatic.com; img-src 'self' data: http: https:; \
+             connect-src 'self'; object-src 'none'; base-uri 'self'; form-action 'self'; \
+             frame-ancestors {frame_ancestors}"
+        )
+    }
+}
+
+/// Answers CORS requests only for `allowed_origins`, which are re-read on
+/// every request so config reloads apply.
+pub fn cors_layer(state: AppState) -> CorsLayer {
+    CorsLayer::new()
+        .allow_origin(AllowOrigin::async_predicate(
+            move |origin: HeaderValue, _| async move {
+                let Ok(origin) = origin.to_str() else {
+                    return false;
+                };
+                state.settings.read().await.security.allows_origin(origin)
+            },
+        ))
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+        ])
+        .allow_headers([
+            CONTENT_TYPE,
+            AUTHORIZATION,
+            HeaderName::from_static(CSRF_HEADER),
+        ])
+        .allow_credentials(true)
+}
+
+/// Whether `origin` names the host the request was sent to.
+fn same_origin(origin: &str, request: &Request) -> bool {
+    let Ok(url) = Url::parse(origin) else {
+        return false;
+    };
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    let authority = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    // HTTP/2 carries the host in the URI instead of a Host header
+    let headers = request.headers();
+    [HOST.as_str(), "x-forwarded-host"]
+        .iter()
+        .filter_map(|name| headers.get(*name).and_then(|value| value.to_str().ok()))
+        .chain(
+            request
+                .uri()
+                .authority()
+                .map(|authority| authority.as_str()),
+        )
+        .any(|value| value.trim().eq_ignore_ascii_case(&authority))
+}
+
+/// Rejects writes sent by pages on other origins, and cookie-authenticated
+/// writes without [`CSRF_HEADER`]. Scripts using bearer tokens or sending no
+/// `Origin` are unaffected, and so are forward-auth checks, which carry the
+/// proxied request's method.
+pub async fn csrf_guard(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    ) || request.uri().path() == "/api/auth/verify"
+    {
+        return next.run(request).await;
+    }
+
+    let headers = request.headers();
+    if let Some(origin) = headers.get(ORIGIN) {
+        let origin = origin.to_str().unwrap_or_default();
+        if !same_origin(origin, &request)
+            && !state.settings.read().await.security.allows_origin(origin)
+        {
+            return auth::deny(StatusCode::FORBIDDEN, "cross-origin request blocked");
+        }
+    }
+    if auth::session_token(headers).is_some()
+        && auth::bearer_token(headers).is_none()
+        && !headers.contains_key(CSRF_HEADER)
+    {
+        return auth::deny(StatusCode::FORBIDDEN, "missing X-Requested-With header");
+    }
+    next.run(request).await
+}
+
+/// Adds CSP, framing, referrer and MIME-sniffing headers to every response.
+pub async fn security_headers(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let security = state.settings.read().await.security.clone();
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&security.content_security_policy()) {
+        headers.insert(CONTENT_SECURITY_POLICY, value);
+    }
+    if security.frame_ancestors.is_empty() {
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    }
+    headers.insert(REFERRER_POLICY, HeaderValue::from_static("same-origin"));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn origins_are_validated_and_matched() {
+        let config = SecurityConfig {
+            allowed_origins: vec!["https://home.lan/".to_string()],
+            ..Default::default()
+        };
+        config.validate().expect("valid origins");
+        assert!(config.allows_origin("https://home.lan"));
+        assert!(!config.allows_origin("https://evil.example"));
+
+        let invalid = SecurityConfig {
+            allowed_origins: vec!["https://home.lan/path".to_string()],
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+
+        let request = Request::builder()
+            .header(HOST, "nav.home.lan:8080")
+            .body(axum::body::Body::empty())
+            .expect("request");
+        assert!(same_origin("http://nav.home.lan:8080", &request));
+        assert!(!same_origin("http://nav.home.lan", &request));
+        assert!(!same_origin("null", &request));
+    }
+}
@@ -1,49 +1,162 @@
 use crate::{
+    audit::{self, Actor, ActorKind, AuditAction, AuditLog},
+    auth::{self, SessionStore, UserStore, Visibility},
+    bundle::{plan_import, ConfigBundle, ImportMode, ImportReport},
+    config::Settings,
+    declared::{self, ServiceDeclaration},
     discovery::DiscoveryEngine,
+    groups::{self, GroupSettings, Locale},
     models::{
-        default_locked_fields, normalize_locked_fields, CreateServiceRequest, DiscoveryStatusInfo,
-        ServiceEntry, ServiceQuery, UpdateServiceRequest,
+        assign_positions, clean_optional, default_locked_fields, normalize_aliases,
+        normalize_ignored, normalize_links, normalize_locked_fields, normalize_network_urls,
+        unique_service_id, CreateGroupRequest, CreateServiceRequest, DiscoveryStatusInfo,
+        GroupChange, GroupInfo, ReorderRequest, SearchHit, SearchQuery, ServiceEntry, ServiceQuery,
+        ServiceSort, ServiceSource, UpdateGroupRequest, UpdateServiceRequest,
     },
-    store::ServiceStore,
+    notify::{DiscoveryEvent, Notifier},
+    ratelimit::RateLimiter,
+    search,
+    store::{ServiceStore, StoreMeta},
+    tokens::TokenStore,
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::Utc;
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::{cmp::Ordering, collections::BTreeMap, path::PathBuf, sync::Arc};
+use tokio::sync::{Notify, RwLock};
+
+const AUDIT_FILE: &str = "audit.jsonl";
 
 #[derive(Clone)]
 pub struct AppState {
-    pub default_host: String,
+    pub settings: Arc<RwLock<Settings>>,
+    /// Signalled whenever settings are replaced, e.g. to reschedule discovery.
+    pub settings_changed: Arc<Notify>,
     pub store: Arc<ServiceStore>,
     pub discovery: Arc<DiscoveryEngine>,
     pub services: Arc<RwLock<Vec<ServiceEntry>>>,
+    pub meta: Arc<RwLock<StoreMeta>>,
     pub discovery_status: Arc<RwLock<DiscoveryStatusInfo>>,
+    pub services_dir: Option<PathBuf>,
+    pub declarations: Arc<RwLock<Vec<ServiceDeclaration>>>,
+    pub notifier: Arc<Notifier>,
+    pub users: Arc<UserStore>,
+    pub sessions: Arc<SessionStore>,
+    pub tokens: Arc<TokenStore>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub audit: Arc<AuditLog>,
 }
 
 impl AppState {
-    pub async fn new(default_host: String, data_file: String) -> Result<Self> {
+    pub async fn with_settings(settings: Settings, data_file: String) -> Result<Self> {
+        let users = Arc::new(UserStore::new(settings.auth.users_path(&data_file)));
+        let tokens = Arc::new(TokenStore::new(settings.auth.tokens_path(&data_file)));
+        let audit = Arc::new(AuditLog::new(auth::beside(&data_file, AUDIT_FILE)));
         let store = Arc::new(ServiceStore::new(data_file));
-        let mut services = store.load_services().await?;
-        services.sort_by(|left, right| left.display_name.cmp(&right.display_name));
+        let (mut services, meta) = store.load().await?;
+        // Earlier versions had no positions and kept services in name order.
+        assign_positions(&mut services);
 
         Ok(Self {
-            default_host: default_host.clone(),
-            discovery: Arc::new(DiscoveryEngine::new(default_host)),
+            settings: Arc::new(RwLock::new(settings)),
+            settings_changed: Arc::new(Notify::new()),
+            discovery: Arc::new(DiscoveryEngine::new()),
             store,
             services: Arc::new(RwLock::new(services)),
+            meta: Arc::new(RwLock::new(meta)),
             discovery_status: Arc::new(RwLock::new(DiscoveryStatusInfo::default())),
+            services_dir: None,
+            declarations: Arc::new(RwLock::new(Vec::new())),
+            notifier: Arc::new(Notifier::new()),
+            users,
+            sessions: Arc::new(SessionStore::default()),
+            tokens,
+            rate_limiter: Arc::new(RateLimiter::default()),
+            audit,
         })
     }
 
-    pub async fn list_services(&self, query: ServiceQuery) -> Vec<ServiceEntry> {
+    pub async fn default_host(&self) -> String {
+        self.settings.read().await.default_host.clone()
+    }
+
+    pub async fn apply_settings(&self, settings: Settings) {
+        let changed = {
+            let mut current = self.settings.write().await;
+            let changed = current.changed_sections(&settings);
+            *current = settings;
+            changed
+        };
+        self.settings_changed.notify_one();
+        if !changed.is_empty() {
+            self.audit
+                .record(vec![Actor::system(ActorKind::Config)
+                    .event(AuditAction::SettingsChanged)
+                    .fields(changed)])
+                .await;
+        }
+    }
+
+    /// Enables GitOps mode: services declared in `dir` are layered over the
+    /// stored state. Fails if the directory cannot be read at startup.
+    pub async fn with_services_dir(mut self, dir: impl Into<PathBuf>) -> Result<Self> {
+        self.services_dir = Some(dir.into());
+        self.reload_declarations().await?;
+        Ok(self)
+    }
+
+    /// Re-reads the services directory. On error the previous declarations
+    /// stay in effect.
+    pub async fn reload_declarations(&self) -> Result<usize> {
+        let Some(dir) = &self.services_dir else {
+            return Ok(0);
+        };
+        let loaded = declared::load_dir(dir)?;
+        let count = loaded.len();
+        let default_host = self.default_host().await;
+
+        let mut services = self.services.write().await;
+        let mut declarations = self.declarations.write().await;
+        *declarations = loaded;
+        let before = services.clone();
+        declared::apply(&mut services, &declarations, &default_host);
+        self.persist(&services).await?;
+        let events = audit::service_changes(&Actor::system(ActorKind::Config), &before, &services);
+        drop((services, declarations));
+        self.audit.record(events).await;
+        Ok(count)
+    }
+
+    /// Services matching `query` that the caller may see; `None` sees all.
+    pub async fn list_services(
+        &self,
+        query: ServiceQuery,
+        visibility: Option<&Visibility>,
+    ) -> Vec<ServiceEntry> {
         let include_hidden = query.include_hidden.unwrap_or(false);
         let services = self.services.read().await;
-        services
+        let mut listed: Vec<ServiceEntry> = services
             .iter()
             .filter(|entry| if include_hidden { true } else { !entry.hidden })
-            .filter(|entry| matches_query(entry, &query))
+            .filter(|entry| matches_query(entry, &query, visibility))
             .cloned()
-            .collect()
+            .collect();
+        sort_services(&mut listed, query.sort.unwrap_or_default());
+        listed
+    }
+
+    /// Services ranked against `query`, among those the caller may see.
+    pub async fn search(
+        &self,
+        query: &SearchQuery,
+        visibility: Option<&Visibility>,
+    ) -> Vec<SearchHit> {
+        let include_hidden = query.include_hidden.unwrap_or(false);
+        let services = self.services.read().await;
+        let visible = services.iter().filter(|entry| {
+            (include_hidden || !entry.hidden)
+                && visibility.map_or(true, |visibility| visibility.allows(entry))
+        });
+        search::search(visible, query)
     }
 
     pub async fn get_service(&self, id: &str) -> Option<ServiceEntry> {
@@ -51,17 +164,30 @@ impl AppState {
         services.iter().find(|entry| entry.id == id).cloned()
     }
 
-    pub async fn create_service(&self, request: CreateServiceRequest) -> Result<ServiceEntry> {
-        let mut entry = request.into_entry(&self.default_host);
+    pub async fn create_service(
+        &self,
+        request: CreateServiceRequest,
+        actor: &Actor,
+    ) -> Result<ServiceEntry> {
+        let mut entry = request.into_entry(&self.default_host().await);
+        normalize_links(&mut entry.links)?;
+        normalize_network_urls(&mut entry.network_urls);
+        normalize_aliases(&mut entry.aliases);
+        entry.updated_by = actor.name.clone();
         let mut services = self.services.write().await;
 
-        if services.iter().any(|value| value.id == entry.id) {
-            entry.id = format!("{}-{}", entry.id, Utc::now().timestamp());
-        }
+        entry.id = unique_service_id(&services, &entry.id);
 
-        services.push(entry.clone());
-        services.sort_by(|left, right| left.display_name.cmp(&right.display_name));
-        self.store.save_services(&services).await?;
+        services.push(entry);
+        assign_positions(&mut services);
+        let entry = services.last().cloned().expect("just pushed");
+        self.persist(&services).await?;
+        drop(services);
+        self.audit
+            .record(vec![actor
+                .event(AuditAction::ServiceCreated)
+                .service(&entry.id)])
+            .await;
         Ok(entry)
     }
 
@@ -69,7 +195,17 @@ impl AppState {
         &self,
         id: &str,
         patch: UpdateServiceRequest,
+        actor: &Actor,
     ) -> Result<Option<ServiceEntry>> {
+        let mut services = self.services.write().await;
+        let Some(existing) = services.iter_mut().find(|entry| entry.id == id) else {
+            return Ok(None);
+        };
+        if let Some(field) = declared_conflict(existing, &patch) {
+            bail!("{field} is declared in the services directory and cannot be edited");
+        }
+        let before = existing.clone();
+
         let UpdateServiceRequest {
             display_name,
             description,
@@ -81,19 +217,17 @@ impl AppState {
             status,
             group,
             tags,
+            aliases,
             icon,
             hidden,
             favorite,
+            links,
+            network_urls,
             locked_fields,
             auto_lock,
         } = patch;
         let auto_lock_enabled = auto_lock.unwrap_or(true);
 
-        let mut services = self.services.write().await;
-        let Some(existing) = services.iter_mut().find(|entry| entry.id == id) else {
-            return Ok(None);
-        };
-
         let mut touched_locked = false;
         if let Some(display_name) = display_name {
             if !display_name.trim().is_empty() {
@@ -151,7 +285,8 @@ impl AppState {
         if let Some(status) = status {
             existing.status = status;
         }
-        if let Some(group) = group {
+        if let Some(mut group) = group {
+            groups::migrate(&mut group);
             existing.group = group;
             touched_locked = true;
             if auto_lock_enabled {
@@ -165,6 +300,14 @@ impl AppState {
                 existing.lock_field("tags");
             }
         }
+        if let Some(mut aliases) = aliases {
+            normalize_aliases(&mut aliases);
+            existing.aliases = aliases;
+            touched_locked = true;
+            if auto_lock_enabled {
+                existing.lock_field("aliases");
+            }
+        }
         if let Some(icon) = icon {
             existing.icon = icon;
             touched_locked = true;
@@ -186,6 +329,22 @@ impl AppState {
                 existing.lock_field("favorite");
             }
         }
+        if let Some(mut links) = links {
+            normalize_links(&mut links)?;
+            existing.links = links;
+            touched_locked = true;
+            if auto_lock_enabled {
+                existing.lock_field("links");
+            }
+        }
+        if let Some(mut network_urls) = network_urls {
+            normalize_network_urls(&mut network_urls);
+            existing.network_urls = network_urls;
+            touched_locked = true;
+            if auto_lock_enabled {
+                existing.lock_field("network_urls");
+            }
+        }
         if let Some(mut locked_fields) = locked_fields {
             normalize_locked_fields(&mut locked_fields);
             existing.locked_fields = locked_fields;
@@ -194,25 +353,109 @@ impl AppState {
         }
 
         existing.updated_at = Utc::now();
+        existing.updated_by = actor.name.clone();
         let updated = existing.clone();
-        services.sort_by(|left, right| left.display_name.cmp(&right.display_name));
-        self.store.save_services(&services).await?;
+        self.persist(&services).await?;
+        drop(services);
+        self.audit
+            .record(audit::service_update(actor, &before, &updated))
+            .await;
         Ok(Some(updated))
     }
 
-    pub async fn run_discovery(&self) -> Result<DiscoveryStatusInfo> {
-        let (discovered, summary) = self.discovery.discover().await?;
+    /// Gives the listed services the positions they held between them, in the
+    /// listed order. Returns `None` if an id is unknown.
+    pub async fn reorder_services(
+        &self,
+        request: ReorderRequest,
+        actor: &Actor,
+    ) -> Result<Option<Vec<ServiceEntry>>> {
+        let group = request.group.as_deref().map(groups::canonical);
+        let mut services = self.services.write().await;
+        let mut indexes = Vec::new();
+        for id in &request.ids {
+            let Some(index) = services.iter().position(|entry| entry.id == *id) else {
+                return Ok(None);
+            };
+            if services[index].group.as_deref() != group {
+                bail!("{id} is not in group {}", group.unwrap_or("(none)"));
+            }
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        let before = services.clone();
+        let mut slots: Vec<Option<u32>> = indexes
+            .iter()
+            .map(|index| services[*index].position)
+            .collect();
+        slots.sort();
+        for (index, position) in indexes.iter().zip(slots) {
+            services[*index].position = position;
+        }
+        self.persist(&services).await?;
+        let reordered = indexes
+            .iter()
+            .map(|index| services[*index].clone())
+            .collect();
+        let events = audit::service_changes(actor, &before, &services);
+        drop(services);
+        self.audit.record(events).await;
+        Ok(Some(reordered))
+    }
+
+    /// Records that the service was opened. Returns `false` if it is unknown.
+    pub async fn record_use(&self, id: &str) -> Result<bool> {
+        let mut services = self.services.write().await;
+        let Some(entry) = services.iter_mut().find(|entry| entry.id == id) else {
+            return Ok(false);
+        };
+        entry.last_used_at = Some(Utc::now());
+        entry.launch_count = entry.launch_count.saturating_add(1);
+        self.persist(&services).await?;
+        Ok(true)
+    }
 
-        let merged = {
+    /// Discovers services and merges them in. Changes are recorded as made by
+    /// discovery; the run itself as made by `trigger`.
+    pub async fn run_discovery(&self, trigger: &Actor) -> Result<DiscoveryStatusInfo> {
+        let settings = self.settings.read().await.clone();
+        let (mut discovered, summary) = self.discovery.discover(&settings).await?;
+        let ignored = self.meta.read().await.ignored.clone();
+        discovered.retain(|entry| !is_ignored(entry, &ignored));
+
+        let mut merged = {
             let services = self.services.read().await;
             crate::discovery::merge_services(&services, &discovered, summary)
         };
+        merged
+            .0
+            .retain(|entry| entry.source == ServiceSource::Manual || !is_ignored(entry, &ignored));
+        declared::apply(
+            &mut merged.0,
+            &self.declarations.read().await,
+            &settings.default_host,
+        );
 
-        {
+        let mut events = {
             let mut services = self.services.write().await;
+            let event = DiscoveryEvent::between(&services, &merged.0);
+            let changes =
+                audit::service_changes(&Actor::system(ActorKind::Discovery), &services, &merged.0);
             *services = merged.0.clone();
-            self.store.save_services(&services).await?;
-        }
+            self.persist(&services).await?;
+            self.notifier.send(&settings.notifiers, event);
+            changes
+        };
+        let count = |action| events.iter().filter(|event| event.action == action).count();
+        let run = trigger.event(AuditAction::DiscoveryRun).detail(format!(
+            "{} added, {} updated, {} removed",
+            count(AuditAction::ServiceCreated),
+            count(AuditAction::ServiceUpdated),
+            count(AuditAction::ServiceRemoved)
+        ));
+        events.insert(0, run);
+        self.audit.record(events).await;
 
         {
             let mut status = self.discovery_status.write().await;
@@ -225,11 +468,491 @@ impl AppState {
     pub async fn discovery_status(&self) -> DiscoveryStatusInfo {
         self.discovery_status.read().await.clone()
     }
+
+    pub async fn ignored(&self) -> Vec<String> {
+        self.meta.read().await.ignored.clone()
+    }
+
+    pub async fn set_ignored(
+        &self,
+        mut ignored: Vec<String>,
+        actor: &Actor,
+    ) -> Result<Vec<String>> {
+        normalize_ignored(&mut ignored);
+        let services = self.services.read().await;
+        let mut meta = self.meta.write().await;
+        let changed = meta.ignored != ignored;
+        meta.ignored = ignored.clone();
+        self.store.save(&services, &meta).await?;
+        drop((services, meta));
+        if changed {
+            self.audit
+                .record(vec![actor
+                    .event(AuditAction::IgnoredChanged)
+                    .detail(ignored.join(", "))])
+                .await;
+        }
+        Ok(ignored)
+    }
+
+    pub async fn export_bundle(&self) -> ConfigBundle {
+        let services = self.services.read().await;
+        let meta = self.meta.read().await;
+        ConfigBundle::new(&services, &meta, &self.default_host().await)
+    }
+
+    pub async fn import_bundle(
+        &self,
+        bundle: ConfigBundle,
+        mode: ImportMode,
+        dry_run: bool,
+        actor: &Actor,
+    ) -> Result<ImportReport> {
+        let default_host = self.default_host().await;
+        let mut services = self.services.write().await;
+        let mut meta = self.meta.write().await;
+        let mut plan = plan_import(&services, &meta, bundle, mode, &default_host);
+        declared::apply(
+            &mut plan.services,
+            &self.declarations.read().await,
+            &default_host,
+        );
+
+        let mut report = plan.report;
+        report.dry_run = dry_run;
+        for entry in plan.services.iter_mut().filter(|entry| {
+            report.added.contains(&entry.id)
+                || report.updated.iter().any(|change| change.id == entry.id)
+        }) {
+            entry.updated_by = actor.name.clone();
+        }
+        if !dry_run {
+            self.store.save(&plan.services, &plan.meta).await?;
+            let mut events = vec![actor.event(AuditAction::Imported).detail(format!(
+                "{} added, {} updated, {} removed",
+                report.added.len(),
+                report.updated.len(),
+                report.removed.len()
+            ))];
+            events.extend(audit::service_changes(actor, &services, &plan.services));
+            *services = plan.services;
+            *meta = plan.meta;
+            drop((services, meta));
+            self.audit.record(events).await;
+        }
+        Ok(report)
+    }
+
+    /// Groups in display order, with how many of their services the caller
+    /// may see. Restricted callers only see custom groups they have services in.
+    pub async fn list_groups(
+        &self,
+        visibility: Option<&Visibility>,
+        locale: Locale,
+    ) -> Vec<GroupInfo> {
+        let services = self
+            .list_services(ServiceQuery::default(), visibility)
+            .await;
+        let meta = self.meta.read().await;
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for group in services.iter().filter_map(|entry| entry.group.as_deref()) {
+            *counts.entry(group).or_default() += 1;
+        }
+
+        let mut keys: Vec<&str> = meta.groups.iter().map(|group| group.key.as_str()).collect();
+        for key in groups::builtin() {
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        for key in counts.keys() {
+            if !keys.contains(key) {
+                keys.push(key);
+            }
+        }
+        keys.into_iter()
+            .map(|key| group_info(key, &meta, counts.get(key).copied().unwrap_or(0), locale))
+            .filter(|group| visibility.is_none() || group.builtin || group.services > 0)
+            .collect()
+    }
+
+    /// One group as the caller sees it, even when [`Self::list_groups`]
+    /// would leave it out, e.g. right after it was created.
+    pub async fn group(
+        &self,
+        key: &str,
+        visibility: Option<&Visibility>,
+        locale: Locale,
+    ) -> GroupInfo {
+        let members = self
+            .list_services(ServiceQuery::default(), visibility)
+            .await
+            .iter()
+            .filter(|entry| entry.group.as_deref() == Some(key))
+            .count();
+        group_info(key, &*self.meta.read().await, members, locale)
+    }
+
+    /// Saves display settings for a group, which may not have services yet.
+    pub async fn create_group(&self, request: CreateGroupRequest, actor: &Actor) -> Result<String> {
+        let key = group_key(&request.key)?;
+        let services = self.services.read().await;
+        let mut meta = self.meta.write().await;
+        if meta.groups.iter().any(|group| group.key == key) {
+            bail!("group {key} already exists");
+        }
+        meta.groups.push(GroupSettings {
+            key: key.clone(),
+            icon: clean_optional(request.icon),
+            description: clean_optional(request.description),
+            collapsed: request.collapsed.unwrap_or(false),
+        });
+        self.store.save(&services, &meta).await?;
+        drop((services, meta));
+        self.audit
+            .record(vec![actor
+                .event(AuditAction::GroupChanged)
+                .detail(format!("created {key}"))])
+            .await;
+        Ok(key)
+    }
+
+    /// Changes display settings and, with a new key, moves every member
+    /// service to it. Returns the resulting key, or `None` for an unknown group.
+    pub async fn update_group(
+        &self,
+        key: &str,
+        patch: UpdateGroupRequest,
+        actor: &Actor,
+    ) -> Result<Option<(String, GroupChange)>> {
+        let target = match &patch.key {
+            Some(value) => group_key(value)?,
+            None => key.to_string(),
+        };
+        let mut services = self.services.write().await;
+        let mut meta = self.meta.write().await;
+        if !group_exists(&services, &meta, key) {
+            return Ok(None);
+        }
+        let before = services.clone();
+
+        let (moved, skipped) = if target == key {
+            (Vec::new(), Vec::new())
+        } else {
+            // Merging keeps the settings of the group merged into.
+            let target_exists = meta.groups.iter().any(|group| group.key == target);
+            if let Some(index) = meta.groups.iter().position(|group| group.key == key) {
+                if target_exists {
+                    meta.groups.remove(index);
+                } else {
+                    meta.groups[index].key = target.clone();
+                }
+            }
+            move_members(&mut services, key, Some(&target), actor)
+        };
+
+        let UpdateGroupRequest {
+            icon,
+            description,
+            collapsed,
+            ..
+        } = patch;
+        if icon.is_some() || description.is_some() || collapsed.is_some() {
+            let settings = match meta.groups.iter().position(|group| group.key == target) {
+                Some(index) => &mut meta.groups[index],
+                None => {
+                    meta.groups.push(GroupSettings {
+                        key: target.clone(),
+                        ..Default::default()
+                    });
+                    meta.groups.last_mut().expect("just pushed")
+                }
+            };
+            if let Some(icon) = icon {
+                settings.icon = clean_optional(icon);
+            }
+            if let Some(description) = description {
+                settings.description = clean_optional(description);
+            }
+            if let Some(collapsed) = collapsed {
+                settings.collapsed = collapsed;
+            }
+        }
+
+        self.store.save(&services, &meta).await?;
+        let detail = if target == key {
+            format!("updated {key}")
+        } else {
+            format!("renamed {key} to {target}")
+        };
+        let mut events = vec![actor.event(AuditAction::GroupChanged).detail(detail)];
+        events.extend(audit::service_changes(actor, &before, &services));
+        drop((services, meta));
+        self.audit.record(events).await;
+        Ok(Some((
+            target,
+            GroupChange {
+                group: None,
+                moved,
+                skipped,
+            },
+        )))
+    }
+
+    /// Removes a group's settings and ungroups its services.
+    pub async fn delete_group(&self, key: &str, actor: &Actor) -> Result<Option<GroupChange>> {
+        let mut services = self.services.write().await;
+        let mut meta = self.meta.write().await;
+        if !group_exists(&services, &meta, key) {
+            return Ok(None);
+        }
+        let before = services.clone();
+        meta.groups.retain(|group| group.key != key);
+        let (moved, skipped) = move_members(&mut services, key, None, actor);
+        self.store.save(&services, &meta).await?;
+        let mut events = vec![actor
+            .event(AuditAction::GroupChanged)
+            .detail(format!("deleted {key}"))];
+        events.extend(audit::service_changes(actor, &before, &services));
+        drop((services, meta));
+        self.audit.record(events).await;
+        Ok(Some(GroupChange {
+            group: None,
+            moved,
+            skipped,
+        }))
+    }
+
+    /// Puts the given groups first, in that order; others keep their order.
+    pub async fn reorder_groups(&self, keys: Vec<String>, actor: &Actor) -> Result<()> {
+        let services = self.services.read().await;
+        let mut meta = self.meta.write().await;
+        let mut ordered: Vec<GroupSettings> = Vec::new();
+        for key in keys {
+            let key = group_key(&key)?;
+            if ordered.iter().any(|group| group.key == key) {
+                continue;
+            }
+            let settings = match meta.groups.iter().position(|group| group.key == key) {
+                Some(index) => meta.groups.remove(index),
+                None => GroupSettings {
+                    key,
+                    ..Default::default()
+                },
+            };
+            ordered.push(settings);
+        }
+        ordered.append(&mut meta.groups);
+        meta.groups = ordered;
+        self.store.save(&services, &meta).await?;
+        drop((services, meta));
+        self.audit
+            .record(vec![actor
+                .event(AuditAction::GroupChanged)
+                .detail("reordered groups")])
+            .await;
+        Ok(())
+    }
+
+    async fn persist(&self, services: &[ServiceEntry]) -> Result<()> {
+        let meta = self.meta.read().await;
+        self.store.save(services, &meta).await
+    }
 }
 
-fn matches_query(entry: &ServiceEntry, query: &ServiceQuery) -> bool {
+/// First declared field the patch would change. Sending the current value
+/// back is allowed, so clients that submit whole forms keep working.
+fn declared_conflict(entry: &ServiceEntry, patch: &UpdateServiceRequest) -> Option<&'static str> {
+    let changes = [
+        (
+            "display_name",
+            patch
+                .display_name
+                .as_ref()
+                .is_some_and(|value| *value != entry.display_name),
+        ),
+        (
+            "description",
+            patch
+                .description
+                .as_ref()
+                .is_some_and(|value| *value != entry.description),
+        ),
+        (
+            "host",
+            patch
+                .host
+                .as_ref()
+                .is_some_and(|value| *value != entry.host),
+        ),
+        ("port", patch.port.is_some_and(|value| value != entry.port)),
+        (
+            "protocol",
+            patch
+                .protocol
+                .as_ref()
+                .is_some_and(|value| *value != entry.protocol),
+        ),
+        (
+            "path",
+            patch
+                .path
+                .as_ref()
+                .is_some_and(|value| *value != entry.path),
+        ),
+        (
+            "url",
+            patch.url.as_ref().is_some_and(|value| *value != entry.url),
+        ),
+        (
+            "group",
+            patch
+                .group
+                .as_ref()
+                .is_some_and(|value| *value != entry.group),
+        ),
+        (
+            "tags",
+            patch
+                .tags
+                .as_ref()
+                .is_some_and(|value| *value != entry.tags),
+        ),
+        (
+            "icon",
+            patch
+                .icon
+                .as_ref()
+                .is_some_and(|value| *value != entry.icon),
+        ),
+        (
+            "aliases",
+            patch
+                .aliases
+                .as_ref()
+                .is_some_and(|value| *value != entry.aliases),
+        ),
+        (
+            "hidden",
+            patch.hidden.is_some_and(|value| value != entry.hidden),
+        ),
+        (
+            "favorite",
+            patch.favorite.is_some_and(|value| value != entry.favorite),
+        ),
+        (
+            "links",
+            patch
+                .links
+                .as_ref()
+                .is_some_and(|value| *value != entry.links),
+        ),
+        (
+            "network_urls",
+            patch
+                .network_urls
+                .as_ref()
+                .is_some_and(|value| *value != entry.network_urls),
+        ),
+    ];
+    changes
+        .into_iter()
+        .find(|(field, changed)| *changed && entry.is_declared(field))
+        .map(|(field, _)| field)
+}
+
+/// A trimmed group key, with legacy names mapped to built-in keys.
+fn group_key(value: &str) -> Result<String> {
+    let key = groups::canonical(value.trim());
+    if key.is_empty() {
+        bail!("group key must not be empty");
+    }
+    Ok(key.to_string())
+}
+
+fn group_info(key: &str, meta: &StoreMeta, services: usize, locale: Locale) -> GroupInfo {
+    let settings = meta.groups.iter().find(|group| group.key == key);
+    GroupInfo {
+        key: key.to_string(),
+        label: groups::label(key, locale).to_string(),
+        builtin: groups::is_builtin(key),
+        services,
+        icon: settings.and_then(|group| group.icon.clone()),
+        description: settings.and_then(|group| group.description.clone()),
+        collapsed: settings.is_some_and(|group| group.collapsed),
+    }
+}
+
+fn group_exists(services: &[ServiceEntry], meta: &StoreMeta, key: &str) -> bool {
+    groups::is_builtin(key)
+        || meta.groups.iter().any(|group| group.key == key)
+        || services
+            .iter()
+            .any(|entry| entry.group.as_deref() == Some(key))
+}
+
+/// Moves the members of `from` to `to` and locks their group so discovery
+/// keeps it. Members whose group is declared are skipped. Returns the moved
+/// and skipped ids.
+fn move_members(
+    services: &mut [ServiceEntry],
+    from: &str,
+    to: Option<&str>,
+    actor: &Actor,
+) -> (Vec<String>, Vec<String>) {
+    let mut moved = Vec::new();
+    let mut skipped = Vec::new();
+    for entry in services
+        .iter_mut()
+        .filter(|entry| entry.group.as_deref() == Some(from))
+    {
+        if entry.is_declared("group") {
+            skipped.push(entry.id.clone());
+            continue;
+        }
+        entry.group = to.map(str::to_string);
+        entry.lock_field("group");
+        entry.updated_at = Utc::now();
+        entry.updated_by = actor.name.clone();
+        moved.push(entry.id.clone());
+    }
+    (moved, skipped)
+}
+
+fn sort_services(services: &mut [ServiceEntry], sort: ServiceSort) {
+    services.sort_by(|left, right| {
+        let order = match sort {
+            ServiceSort::Manual => Ordering::Equal,
+            ServiceSort::Name => left
+                .display_name
+                .to_lowercase()
+                .cmp(&right.display_name.to_lowercase()),
+            ServiceSort::Status => left.status.cmp(&right.status),
+            // Newest first; never used or of unknown age last.
+            ServiceSort::LastUsed => right.last_used_at.cmp(&left.last_used_at),
+            ServiceSort::RecentlyAdded => right.created_at.cmp(&left.created_at),
+        };
+        order.then_with(|| left.position.cmp(&right.position))
+    });
+}
+
+fn is_ignored(entry: &ServiceEntry, ignored: &[String]) -> bool {
+    ignored
+        .iter()
+        .any(|value| *value == entry.id || *value == entry.service_name)
+}
+
+fn matches_query(
+    entry: &ServiceEntry,
+    query: &ServiceQuery,
+    visibility: Option<&Visibility>,
+) -> bool {
+    if visibility.is_some_and(|visibility| !visibility.allows(entry)) {
+        return false;
+    }
+
     if let Some(group) = &query.group {
-        if entry.group.as_deref().unwrap_or_default() != group {
+        if entry.group.as_deref().unwrap_or_default() != groups::canonical(group) {
             return false;
         }
     }
@@ -248,15 +971,20 @@ fn matches_query(entry: &ServiceEntry, query: &ServiceQuery) -> bool {
                 .tags
                 .iter()
                 .any(|tag| tag.to_lowercase().contains(&needle))
-            && !entry
-                .group
-                .as_ref()
-                .map(|group| group.to_lowercase().contains(&needle))
-                .unwrap_or(false)
+            && !entry.aliases.iter().any(|alias| alias.contains(&needle))
+            && !entry.group.as_deref().is_some_and(|group| {
+                groups::names(group).any(|name| name.to_lowercase().contains(&needle))
+            })
             && !entry
                 .port
                 .map(|port| port.to_string().contains(&needle))
                 .unwrap_or(false)
+            && !entry.links.iter().any(|link| {
+                link.name.to_lowercase().contains(&needle)
+                    || entry
+                        .link_url(link, None)
+                        .is_some_and(|url| url.to_lowercase().contains(&needle))
+            })
         {
             return false;
         }
@@ -268,11 +996,10 @@ fn matches_query(entry: &ServiceEntry, query: &ServiceQuery) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::{ServiceProtocol, ServiceSource, ServiceStatus};
+    use crate::models::{ServiceLink, ServiceProtocol, ServiceStatus};
 
-    #[test]
-    fn query_filters_by_text() {
-        let entry = ServiceEntry {
+    fn grafana() -> ServiceEntry {
+        ServiceEntry {
             id: "grafana-service".to_string(),
             service_name: "grafana.service".to_string(),
             display_name: "Grafana".to_string(),
@@ -282,31 +1009,92 @@ mod tests {
             protocol: ServiceProtocol::Http,
             path: None,
             url: None,
+            open_url: None,
             status: ServiceStatus::Running,
             group: Some("monitoring".to_string()),
             tags: vec!["dashboard".to_string()],
+            aliases: Vec::new(),
             icon: None,
             hidden: false,
             favorite: false,
+            links: Vec::new(),
+            network_urls: BTreeMap::new(),
             source: ServiceSource::Manual,
             locked_fields: Vec::new(),
+            declared_fields: Vec::new(),
+            updated_by: None,
             last_seen_at: None,
+            position: None,
+            created_at: None,
+            last_used_at: None,
+            launch_count: 0,
             updated_at: Utc::now(),
-        };
+        }
+    }
+
+    #[test]
+    fn query_filters_by_text() {
+        let mut entry = grafana();
+        entry.links.push(ServiceLink {
+            name: "API docs".to_string(),
+            path: Some("/swagger".to_string()),
+            ..Default::default()
+        });
 
         assert!(matches_query(
             &entry,
             &ServiceQuery {
                 q: Some("graf".to_string()),
                 ..Default::default()
-            }
+            },
+            None
         ));
         assert!(!matches_query(
             &entry,
             &ServiceQuery {
                 q: Some("jellyfin".to_string()),
                 ..Default::default()
-            }
+            },
+            None
+        ));
+        for q in ["api docs", "swagger"] {
+            assert!(matches_query(
+                &entry,
+                &ServiceQuery {
+                    q: Some(q.to_string()),
+                    ..Default::default()
+                },
+                None
+            ));
+        }
+    }
+
+    #[test]
+    fn query_respects_visibility() {
+        let entry = grafana();
+        let query = ServiceQuery::default();
+        let only = |groups: &[&str], tags: &[&str]| Visibility {
+            groups: groups.iter().map(|value| value.to_string()).collect(),
+            tags: tags.iter().map(|value| value.to_string()).collect(),
+            ..Default::default()
+        };
+
+        assert!(matches_query(&entry, &query, Some(&Visibility::default())));
+        assert!(matches_query(
+            &entry,
+            &query,
+            Some(&only(&["Monitoring"], &[]))
+        ));
+        assert!(matches_query(
+            &entry,
+            &query,
+            Some(&only(&["media"], &["dashboard"]))
         ));
+        assert!(!matches_query(&entry, &query, Some(&only(&["media"], &[]))));
+        let excluded = Visibility {
+            exclude_tags: vec!["dashboard".to_string()],
+            ..Default::default()
+        };
+        assert!(!matches_query(&entry, &query, Some(&excluded)));
     }
 }
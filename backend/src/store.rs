@@ -1,8 +1,60 @@
-use crate::models::ServiceEntry;
+use crate::{
+    groups::{self, GroupSettings},
+    models::ServiceEntry,
+};
 use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use tokio::{fs, io::AsyncWriteExt};
 
+const STORE_VERSION: u32 = 3;
+
+/// Everything persisted next to the service list.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreMeta {
+    /// Service ids or unit names that discovery must skip.
+    #[serde(default)]
+    pub ignored: Vec<String>,
+    /// Group order and display settings.
+    #[serde(default)]
+    pub groups: Vec<GroupSettings>,
+}
+
+#[derive(Serialize)]
+struct StoreDocumentRef<'a> {
+    version: u32,
+    services: &'a [ServiceEntry],
+    #[serde(flatten)]
+    meta: &'a StoreMeta,
+}
+
+/// Version 1 files are a bare array of services.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoreDocument {
+    Current {
+        #[serde(default)]
+        services: Vec<ServiceEntry>,
+        #[serde(flatten)]
+        meta: StoreMeta,
+    },
+    Legacy(Vec<ServiceEntry>),
+}
+
+impl StoreDocument {
+    /// Version 2 and older files hold Chinese group names instead of keys.
+    fn into_parts(self) -> (Vec<ServiceEntry>, StoreMeta) {
+        let (mut services, meta) = match self {
+            Self::Current { services, meta } => (services, meta),
+            Self::Legacy(services) => (services, StoreMeta::default()),
+        };
+        for service in &mut services {
+            groups::migrate(&mut service.group);
+        }
+        (services, meta)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ServiceStore {
     path: PathBuf,
@@ -16,10 +68,10 @@ impl ServiceStore {
         Self { path, backup_path }
     }
 
-    pub async fn load_services(&self) -> Result<Vec<ServiceEntry>> {
+    pub async fn load(&self) -> Result<(Vec<ServiceEntry>, StoreMeta)> {
         if !self.path.exists() {
             self.ensure_parent_dir().await?;
-            return Ok(Vec::new());
+            return Ok((Vec::new(), StoreMeta::default()));
         }
 
         match fs::read_to_string(&self.path).await {
@@ -30,10 +82,15 @@ impl ServiceStore {
         }
     }
 
-    pub async fn save_services(&self, services: &[ServiceEntry]) -> Result<()> {
+    pub async fn save(&self, services: &[ServiceEntry], meta: &StoreMeta) -> Result<()> {
         self.ensure_parent_dir().await?;
+        let document = StoreDocumentRef {
+            version: STORE_VERSION,
+            services,
+            meta,
+        };
         let json =
-            serde_json::to_string_pretty(services).context("failed to serialize services")?;
+            serde_json::to_string_pretty(&document).context("failed to serialize services")?;
 
         if self.path.exists() {
             let _ = fs::copy(&self.path, &self.backup_path).await;
@@ -63,22 +120,24 @@ impl ServiceStore {
         Ok(())
     }
 
-    fn parse_content(&self, content: &str) -> Result<Vec<ServiceEntry>> {
-        match serde_json::from_str::<Vec<ServiceEntry>>(content) {
-            Ok(services) => Ok(services),
+    fn parse_content(&self, content: &str) -> Result<(Vec<ServiceEntry>, StoreMeta)> {
+        match serde_json::from_str::<StoreDocument>(content) {
+            Ok(document) => Ok(document.into_parts()),
             Err(primary_error) => {
                 if self.backup_path.exists() {
                     let backup_content =
                         std::fs::read_to_string(&self.backup_path).with_context(|| {
                             format!("failed reading backup {}", self.backup_path.display())
                         })?;
-                    serde_json::from_str::<Vec<ServiceEntry>>(&backup_content).with_context(|| {
-                        format!(
-                            "failed parsing {} and backup {}",
-                            self.path.display(),
-                            self.backup_path.display()
-                        )
-                    })
+                    serde_json::from_str::<StoreDocument>(&backup_content)
+                        .map(StoreDocument::into_parts)
+                        .with_context(|| {
+                            format!(
+                                "failed parsing {} and backup {}",
+                                self.path.display(),
+                                self.backup_path.display()
+                            )
+                        })
                 } else {
                     Err(primary_error).with_context(|| {
                         format!(
@@ -100,3 +159,25 @@ impl ServiceStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_legacy_array_and_current_document() {
+        let store = ServiceStore::new(std::env::temp_dir().join("navigator-store-test.json"));
+
+        let (services, meta) = store
+            .parse_content(r#"[{"id": "jellyfin", "service_name": "jellyfin", "display_name": "Jellyfin", "host": "server.lan", "protocol": "http", "status": "running", "group": "影音", "updated_at": "2024-02-07T00:00:00Z"}]"#)
+            .expect("legacy array");
+        assert_eq!(services[0].group.as_deref(), Some("media"));
+        assert_eq!(meta, StoreMeta::default());
+
+        let (services, meta) = store
+            .parse_content(r#"{"version": 2, "services": [], "ignored": ["getty.service"]}"#)
+            .expect("current document");
+        assert!(services.is_empty());
+        assert_eq!(meta.ignored, vec!["getty.service".to_string()]);
+    }
+}
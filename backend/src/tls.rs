@@ -0,0 +1,378 @@
+use crate::host;
+use anyhow::{anyhow, bail, Context, Result};
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{header::HOST, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::conn::auto,
+};
+use rustls::{
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+use serde::Deserialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, SystemTime},
+};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tower::ServiceExt;
+use tracing::{debug, info, warn};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const RELOAD_INTERVAL: Duration = Duration::from_secs(10);
+const SELF_SIGNED_DIR: &str = "tls";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain. Without `cert_file` and `key_file` a
+    /// self-signed certificate is generated and kept next to the data file.
+    pub cert_file: Option<PathBuf>,
+    pub key_file: Option<PathBuf>,
+    /// Plain HTTP port that redirects every request to HTTPS.
+    pub redirect_port: Option<u16>,
+}
+
+impl TlsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.cert_file.is_some() != self.key_file.is_some() {
+            bail!("tls.cert_file and tls.key_file must be set together");
+        }
+        if self.redirect_port == Some(0) {
+            bail!("tls.redirect_port must not be 0");
+        }
+        Ok(())
+    }
+
+    /// Certificate and key paths, falling back to the self-signed pair.
+    pub fn paths(&self, data_file: &str) -> (PathBuf, PathBuf) {
+        match (&self.cert_file, &self.key_file) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+            _ => {
+                let dir = crate::auth::beside(data_file, SELF_SIGNED_DIR);
+                (dir.join("cert.pem"), dir.join("key.pem"))
+            }
+        }
+    }
+
+    pub fn self_signed(&self) -> bool {
+        self.cert_file.is_none()
+    }
+}
+
+/// Hands out the current certificate; [`watch`] swaps it when the files change.
+#[derive(Debug)]
+pub struct CertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl CertResolver {
+    fn set(&self, key: CertifiedKey) {
+        *self.current.write().expect("certificate lock poisoned") = Arc::new(key);
+    }
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .expect("certificate lock poisoned")
+                .clone(),
+        )
+    }
+}
+
+fn load_pem(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let cert_pem = std::fs::read(cert_path)
+        .with_context(|| format!("failed reading {}", cert_path.display()))?;
+    let certs = rustls_pemfile::certs(&mut cert_pem.as_slice())
+        .collect::<Result<Vec<CertificateDer<'static>>, _>>()
+        .with_context(|| format!("failed parsing {}", cert_path.display()))?;
+    if certs.is_empty() {
+        bail!("no certificates found in {}", cert_path.display());
+    }
+
+    let key_pem = std::fs::read(key_path)
+        .with_context(|| format!("failed reading {}", key_path.display()))?;
+    let key: PrivateKeyDer<'static> = rustls_pemfile::private_key(&mut key_pem.as_slice())
+        .with_context(|| format!("failed parsing {}", key_path.display()))?
+        .ok_or_else(|| anyhow!("no private key found in {}", key_path.display()))?;
+    let signing_key = ring::sign::any_supported_type(&key)
+        .map_err(|error| anyhow!("unsupported key in {}: {error}", key_path.display()))?;
+
+    Ok(CertifiedKey::new(certs, signing_key))
+}
+
+/// Names the self-signed certificate covers: `default_host`, loopback,
+/// and the machine's detected names and interface addresses.
+fn self_signed_names(default_host: &str) -> Vec<String> {
+    let detected = host::info();
+    let mut names = vec![
+        default_host.to_string(),
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ];
+    names.extend(
+        [&detected.hostname, &detected.fqdn, &detected.mdns]
+            .into_iter()
+            .flatten()
+            .cloned(),
+    );
+    names.extend(
+        detected
+            .addresses
+            .iter()
+            .map(|entry| entry.address.to_string()),
+    );
+    let mut seen = std::collections::HashSet::new();
+    names.retain(|name| seen.insert(name.to_ascii_lowercase()));
+    names
+}
+
+fn write_self_signed(cert_path: &Path, key_path: &Path, default_host: &str) -> Result<()> {
+    let names = self_signed_names(default_host);
+    let generated = rcgen::generate_simple_self_signed(names.clone())
+        .context("failed generating self-signed certificate")?;
+    if let Some(parent) = cert_path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating {}", parent.display()))?;
+    }
+    for (path, content) in [
+        (key_path, generated.key_pair.serialize_pem()),
+        (cert_path, generated.cert.pem()),
+    ] {
+        let temp = path.with_extension("pem.tmp");
+        std::fs::write(&temp, content)
+            .with_context(|| format!("failed writing {}", temp.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&temp, std::fs::Permissions::from_mode(0o600))?;
+        }
+        std::fs::rename(&temp, path)
+            .with_context(|| format!("failed writing {}", path.display()))?;
+    }
+    info!(
+        cert = %cert_path.display(),
+        names = %names.join(", "),
+        "generated self-signed certificate"
+    );
+    Ok(())
+}
+
+/// Loads the configured certificate, or the self-signed one (generating it
+/// on first start), and builds the rustls server config around it.
+pub fn server_config(
+    config: &TlsConfig,
+    data_file: &str,
+    default_host: &str,
+) -> Result<(Arc<ServerConfig>, Arc<CertResolver>)> {
+    let (cert_path, key_path) = config.paths(data_file);
+    if config.self_signed() && !(cert_path.exists() && key_path.exists()) {
+        write_self_signed(&cert_path, &key_path, default_host)?;
+    }
+    let resolver = Arc::new(CertResolver {
+        current: RwLock::new(Arc::new(load_pem(&cert_path, &key_path)?)),
+    });
+
+    let mut server = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .context("failed configuring TLS")?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+    server.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok((Arc::new(server), resolver))
+}
+
+fn fingerprint(path: &Path) -> Option<(u64, Option<SystemTime>)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+/// Reloads the certificate when either PEM file changes, so renewals from
+/// certbot or similar apply without a restart. A broken pair is logged and
+/// the previous certificate stays in use.
+pub async fn watch(resolver: Arc<CertResolver>, cert_path: PathBuf, key_path: PathBuf) {
+    let mut last = (fingerprint(&cert_path), fingerprint(&key_path));
+    let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+    interval.tick().await;
+
+    loop {
+        interval.tick().await;
+        let current = (fingerprint(&cert_path), fingerprint(&key_path));
+        if current == last {
+            continue;
+        }
+        last = current;
+        match load_pem(&cert_path, &key_path) {
+            Ok(key) => {
+                resolver.set(key);
+                info!(cert = %cert_path.display(), "reloaded TLS certificate");
+            }
+            Err(error) => {
+                warn!(cert = %cert_path.display(), "keeping previous certificate: {error:#}")
+            }
+        }
+    }
+}
+
+/// Marks requests that arrived over TLS.
+#[derive(Debug, Clone, Copy)]
+pub struct Encrypted;
+
+/// Serves `app` over TLS. Each connection gets the peer address as
+/// [`ConnectInfo`], like the plain listener.
+pub async fn serve(listener: TcpListener, app: Router, config: Arc<ServerConfig>) -> Result<()> {
+    let acceptor = TlsAcceptor::from(config);
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                warn!("failed accepting connection: {error}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            let stream =
+                match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                    Ok(Ok(stream)) => stream,
+                    Ok(Err(error)) => {
+                        debug!(%peer, "TLS handshake failed: {error}");
+                        return;
+                    }
+                    Err(_) => {
+                        debug!(%peer, "TLS handshake timed out");
+                        return;
+                    }
+                };
+            let service = hyper::service::service_fn(move |request: Request<_>| {
+                let mut request = request.map(Body::new);
+                request.extensions_mut().insert(ConnectInfo(peer));
+                request.extensions_mut().insert(Encrypted);
+                app.clone().oneshot(request)
+            });
+            if let Err(error) = auto::Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!(%peer, "connection closed with error: {error}");
+            }
+        });
+    }
+}
+
+/// Plain HTTP app that sends every request to the same path on the HTTPS port.
+pub fn redirect_router(https_port: u16) -> Router {
+    Router::new()
+        .fallback(redirect_to_https)
+        .with_state(https_port)
+}
+
+async fn redirect_to_https(State(https_port): State<u16>, request: Request) -> Response {
+    let Some(host) = request
+        .headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+    else {
+        return (StatusCode::BAD_REQUEST, "missing Host header").into_response();
+    };
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|value| value.as_str())
+        .unwrap_or("/");
+    Redirect::permanent(&https_url(host, https_port, path)).into_response()
+}
+
+fn https_url(host: &str, https_port: u16, path: &str) -> String {
+    let (host, _) = crate::forward_auth::split_host_port(host.trim());
+    let host = if host.contains(':') {
+        format!("[{host}]")
+    } else {
+        host.to_string()
+    };
+    if https_port == 443 {
+        format!("https://{host}{path}")
+    } else {
+        format!("https://{host}:{https_port}{path}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory, removed again even when an assertion fails.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(prefix: &str) -> Self {
+            Self(std::env::temp_dir().join(format!(
+                "{prefix}-{}-{}",
+                std::process::id(),
+                chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+            )))
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn generates_and_loads_self_signed_certificate() {
+        let dir = TempDir::new("navigator-tls");
+        let data_file = dir.0.join("services.json");
+        let data_file = data_file.to_str().expect("temp path should be UTF-8");
+        let config = TlsConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let (_, resolver) = server_config(&config, data_file, "nav.home.lan").expect("config");
+        let (cert_path, key_path) = config.paths(data_file);
+        assert!(cert_path.exists() && key_path.exists());
+        assert_eq!(
+            resolver
+                .current
+                .read()
+                .expect("certificate lock poisoned")
+                .cert
+                .len(),
+            1
+        );
+
+        let partial = TlsConfig {
+            cert_file: Some("cert.pem".into()),
+            ..Default::default()
+        };
+        assert!(partial.validate().is_err());
+    }
+
+    #[test]
+    fn redirects_keep_host_and_path() {
+        assert_eq!(
+            https_url("nav.home.lan:8081", 8443, "/api/services?q=x"),
+            "https://nav.home.lan:8443/api/services?q=x"
+        );
+        assert_eq!(https_url("[::1]:80", 443, "/"), "https://[::1]/");
+    }
+}
@@ -0,0 +1,296 @@
+use crate::auth::{Identity, Role, Scope, SecretFile};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub const TOKEN_PREFIX: &str = "hsn_";
+const TOKENS_VERSION: u32 = 1;
+/// `last_used_at` is only written back this often, to keep the file quiet
+/// under busy scripts.
+const LAST_USED_RESOLUTION: Duration = Duration::seconds(60);
+
+/// Everything about a token except its secret; safe to show in listings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub created_by: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl TokenInfo {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    /// Changes made with the token are recorded as `token:<name>`.
+    pub fn identity(&self) -> Identity {
+        let role = if self.scopes.contains(&Scope::Admin) {
+            Role::Admin
+        } else if self.scopes.iter().any(|scope| *scope != Scope::Read) {
+            Role::Editor
+        } else {
+            Role::Viewer
+        };
+        Identity {
+            username: format!("token:{}", self.name),
+            role,
+            scopes: Some(self.scopes.clone()),
+            visibility: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredToken {
+    #[serde(flatten)]
+    info: TokenInfo,
+    /// Hex SHA-256 of the secret. Tokens are random, so a fast hash is enough.
+    hash: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct TokensDocument {
+    version: u32,
+    tokens: Vec<StoredToken>,
+}
+
+/// API tokens, stored hashed. Like users, the file is re-read when it
+/// changes, so tokens revoked from the CLI stop working immediately.
+pub struct TokenStore {
+    file: SecretFile<TokensDocument>,
+    /// Held from load to save, so concurrent changes don't undo each other.
+    writes: Mutex<()>,
+}
+
+impl TokenStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            file: SecretFile::new(path),
+            writes: Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ()> {
+        self.writes.lock().expect("token store lock poisoned")
+    }
+
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    pub fn list(&self) -> Result<Vec<TokenInfo>> {
+        Ok(self
+            .file
+            .load()?
+            .tokens
+            .into_iter()
+            .map(|token| token.info)
+            .collect())
+    }
+
+    /// Returns the secret, which is only ever shown this once.
+    pub fn create(
+        &self,
+        name: &str,
+        scopes: &[Scope],
+        expires_at: Option<DateTime<Utc>>,
+        created_by: Option<&str>,
+    ) -> Result<(String, TokenInfo)> {
+        let name = name.trim();
+        if name.is_empty() || name.len() > 64 {
+            bail!("token name must be 1-64 characters");
+        }
+        if scopes.is_empty() {
+            bail!("a token needs at least one scope");
+        }
+        if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            bail!("token expiry must be in the future");
+        }
+
+        let _writes = self.lock();
+        let mut document = self.file.load()?;
+        let mut id = random_hex(4);
+        while document.tokens.iter().any(|token| token.info.id == id) {
+            id = random_hex(4);
+        }
+        let secret = format!("{TOKEN_PREFIX}{}", random_hex(32));
+        let mut unique = Vec::new();
+        for scope in scopes {
+            if !unique.contains(scope) {
+                unique.push(*scope);
+            }
+        }
+        let info = TokenInfo {
+            id,
+            name: name.to_string(),
+            scopes: unique,
+            created_at: Utc::now(),
+            created_by: created_by.map(str::to_string),
+            expires_at,
+            last_used_at: None,
+        };
+        document.version = TOKENS_VERSION;
+        document.tokens.push(StoredToken {
+            info: info.clone(),
+            hash: hash_token(&secret),
+        });
+        self.file.save(&document)?;
+        Ok((secret, info))
+    }
+
+    pub fn revoke(&self, id: &str) -> Result<bool> {
+        let _writes = self.lock();
+        let mut document = self.file.load()?;
+        let before = document.tokens.len();
+        document.tokens.retain(|token| token.info.id != id);
+        if document.tokens.len() == before {
+            return Ok(false);
+        }
+        self.file.save(&document)?;
+        Ok(true)
+    }
+
+    /// Looks up an unexpired token by its secret and records the use.
+    pub fn authenticate(&self, secret: &str) -> Result<Option<TokenInfo>> {
+        if !secret.starts_with(TOKEN_PREFIX) {
+            return Ok(None);
+        }
+        let hash = hash_token(secret);
+        let now = Utc::now();
+        let find = |document: &TokensDocument| {
+            document
+                .tokens
+                .iter()
+                .position(|token| token.hash == hash && !token.info.is_expired(now))
+        };
+        let document = self.file.load()?;
+        let Some(index) = find(&document) else {
+            return Ok(None);
+        };
+        let fresh = document.tokens[index]
+            .info
+            .last_used_at
+            .is_some_and(|last_used_at| now - last_used_at < LAST_USED_RESOLUTION);
+        if fresh {
+            return Ok(Some(document.tokens[index].info.clone()));
+        }
+
+        // Re-read under the lock, the token may have been revoked meanwhile
+        let _writes = self.lock();
+        let mut document = self.file.load()?;
+        let Some(index) = find(&document) else {
+            return Ok(None);
+        };
+        let token = &mut document.tokens[index];
+        token.info.last_used_at = Some(now);
+        let info = token.info.clone();
+        self.file.save(&document)?;
+        Ok(Some(info))
+    }
+}
+
+fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn hash_token(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokens_are_hashed_scoped_and_revocable() {
+        let path = std::env::temp_dir().join(format!(
+            "navigator-tokens-{}.json",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = TokenStore::new(&path);
+        let (secret, info) = store
+            .create("cron", &[Scope::DiscoveryRun], None, Some("alice"))
+            .expect("create");
+        assert!(!std::fs::read_to_string(&path)
+            .expect("tokens file")
+            .contains(&secret));
+
+        let found = store.authenticate(&secret).expect("auth").expect("valid");
+        assert_eq!(found.id, info.id);
+        assert!(found.last_used_at.is_some());
+        let identity = found.identity();
+        assert!(identity.allows(Scope::DiscoveryRun));
+        assert!(identity.allows(Scope::Read));
+        assert!(!identity.allows(Scope::ServicesWrite));
+        assert!(store.authenticate("hsn_wrong").expect("auth").is_none());
+
+        assert!(store
+            .create(
+                "old",
+                &[Scope::Read],
+                Some(Utc::now() - Duration::hours(1)),
+                None
+            )
+            .is_err());
+        assert!(store.create("empty", &[], None, None).is_err());
+
+        assert!(store.revoke(&info.id).expect("revoke"));
+        assert!(store.authenticate(&secret).expect("auth").is_none());
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn revokes_survive_concurrent_authentication() {
+        let path = std::env::temp_dir().join(format!(
+            "navigator-tokens-race-{}.json",
+            Utc::now().timestamp_nanos_opt().unwrap_or_default()
+        ));
+        let store = TokenStore::new(&path);
+        let tokens: Vec<(String, TokenInfo)> = (0..8)
+            .map(|index| {
+                store
+                    .create(&format!("script-{index}"), &[Scope::Read], None, None)
+                    .expect("create")
+            })
+            .collect();
+
+        // Every first use writes `last_used_at` while the others are revoked
+        std::thread::scope(|scope| {
+            for (index, (secret, info)) in tokens.iter().enumerate() {
+                let store = &store;
+                scope.spawn(move || {
+                    if index % 2 == 0 {
+                        store.authenticate(secret).expect("auth");
+                    } else {
+                        assert!(store.revoke(&info.id).expect("revoke"));
+                    }
+                });
+            }
+        });
+
+        for (index, (secret, _)) in tokens.iter().enumerate() {
+            let found = store.authenticate(secret).expect("auth");
+            assert_eq!(found.is_some(), index % 2 == 0, "token {index}");
+        }
+        assert_eq!(store.list().expect("list").len(), 4);
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -1,19 +1,42 @@
 import type {
+  Capabilities,
   DiscoveryRunResponse,
   DiscoveryStatusInfo,
+  GroupChange,
+  GroupInfo,
+  SearchHit,
   ServiceEntry,
+  ServiceSort,
+  SessionInfo,
+  UpdateGroupRequest,
   UpdateServiceRequest,
 } from '../types';
 
+// Required on cookie-authenticated writes as CSRF protection
+const writeHeaders = {
+  'X-Requested-With': 'navigator',
+};
+
 const jsonHeaders = {
+  ...writeHeaders,
   'Content-Type': 'application/json',
 };
 
+/** Thrown when the server requires a login for the request. */
+export class UnauthorizedError extends Error {}
+
+function checkAuth(response: Response) {
+  if (response.status === 401) {
+    throw new UnauthorizedError('Login required');
+  }
+}
+
 export async function fetchServices(params?: {
   q?: string;
   group?: string;
   status?: string;
   include_hidden?: boolean;
+  sort?: ServiceSort;
 }): Promise<ServiceEntry[]> {
   const query = new URLSearchParams();
   if (params?.q) {
@@ -28,14 +51,29 @@ export async function fetchServices(params?: {
   if (params?.include_hidden) {
     query.set('include_hidden', 'true');
   }
+  if (params?.sort) {
+    query.set('sort', params.sort);
+  }
   const suffix = query.toString() ? `?${query.toString()}` : '';
   const response = await fetch(`/api/services${suffix}`);
+  checkAuth(response);
   if (!response.ok) {
     throw new Error('Failed to fetch services');
   }
   return response.json();
 }
 
+/** Fuzzy search ranked by relevance, favorites and launch frequency. */
+export async function searchServices(q: string, limit = 10): Promise<SearchHit[]> {
+  const query = new URLSearchParams({ q, limit: String(limit) });
+  const response = await fetch(`/api/search?${query.toString()}`);
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to search services');
+  }
+  return response.json();
+}
+
 export async function updateService(id: string, payload: UpdateServiceRequest): Promise<ServiceEntry> {
   const response = await fetch(`/api/services/${id}`, {
     method: 'PATCH',
@@ -43,16 +81,41 @@ export async function updateService(id: string, payload: UpdateServiceRequest):
     body: JSON.stringify(payload),
   });
 
+  checkAuth(response);
   if (!response.ok) {
     throw new Error('Failed to update service');
   }
   return response.json();
 }
 
+/** Moves the given services of one group into this order, e.g. after a drag. */
+export async function reorderServices(group: string | null, ids: string[]): Promise<ServiceEntry[]> {
+  const response = await fetch('/api/services/order', {
+    method: 'PUT',
+    headers: jsonHeaders,
+    body: JSON.stringify({ group, ids }),
+  });
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to reorder services');
+  }
+  return response.json();
+}
+
+/** Records that a service was opened; failures are ignored. */
+export function recordOpen(id: string): void {
+  void fetch(`/api/services/${encodeURIComponent(id)}/open`, {
+    method: 'POST',
+    headers: writeHeaders,
+  }).catch(() => undefined);
+}
+
 export async function runDiscovery(): Promise<DiscoveryRunResponse> {
   const response = await fetch('/api/discovery/run', {
     method: 'POST',
+    headers: writeHeaders,
   });
+  checkAuth(response);
   if (!response.ok) {
     throw new Error('Failed to run discovery');
   }
@@ -61,8 +124,87 @@ export async function runDiscovery(): Promise<DiscoveryRunResponse> {
 
 export async function fetchDiscoveryStatus(): Promise<DiscoveryStatusInfo> {
   const response = await fetch('/api/discovery/status');
+  checkAuth(response);
   if (!response.ok) {
     throw new Error('Failed to fetch discovery status');
   }
   return response.json();
 }
+
+export async function fetchGroups(locale: string): Promise<GroupInfo[]> {
+  const response = await fetch(`/api/groups?locale=${encodeURIComponent(locale)}`);
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to fetch groups');
+  }
+  return response.json();
+}
+
+export async function updateGroup(
+  key: string,
+  payload: UpdateGroupRequest,
+  locale: string,
+): Promise<GroupChange> {
+  const response = await fetch(
+    `/api/groups/${encodeURIComponent(key)}?locale=${encodeURIComponent(locale)}`,
+    {
+      method: 'PATCH',
+      headers: jsonHeaders,
+      body: JSON.stringify(payload),
+    },
+  );
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to update group');
+  }
+  return response.json();
+}
+
+export async function reorderGroups(keys: string[], locale: string): Promise<GroupInfo[]> {
+  const response = await fetch(`/api/groups?locale=${encodeURIComponent(locale)}`, {
+    method: 'PUT',
+    headers: jsonHeaders,
+    body: JSON.stringify(keys),
+  });
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to reorder groups');
+  }
+  return response.json();
+}
+
+export async function fetchSession(): Promise<SessionInfo> {
+  const response = await fetch('/api/auth/session');
+  if (!response.ok) {
+    throw new Error('Failed to fetch session');
+  }
+  return response.json();
+}
+
+export async function fetchCapabilities(): Promise<Capabilities> {
+  const response = await fetch('/api/capabilities');
+  checkAuth(response);
+  if (!response.ok) {
+    throw new Error('Failed to fetch capabilities');
+  }
+  return response.json();
+}
+
+export async function login(username: string, password: string): Promise<SessionInfo> {
+  const response = await fetch('/api/auth/login', {
+    method: 'POST',
+    headers: jsonHeaders,
+    body: JSON.stringify({ username, password }),
+  });
+  if (response.status === 401) {
+    throw new UnauthorizedError('Invalid username or password');
+  }
+  if (!response.ok) {
+    throw new Error('Failed to sign in');
+  }
+  return response.json();
+}
+
+export async function logout(): Promise<void> {
+  await fetch('/api/auth/logout', { method: 'POST', headers: writeHeaders });
+}
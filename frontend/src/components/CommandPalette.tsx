@@ -0,0 +1,149 @@
+import { type KeyboardEvent, type ReactNode, useEffect, useRef, useState } from 'react';
+import { recordOpen, searchServices } from '../api/client';
+import { useTranslation } from '../i18n';
+import { useDebounce } from '../hooks/useDebounce';
+import type { SearchHit, SearchMatch } from '../types';
+
+const RESULT_LIMIT = 8;
+
+// Wraps the matched character ranges of a value in <mark>
+function highlight(match: SearchMatch | undefined, fallback: string): ReactNode {
+  if (!match) return fallback;
+  const chars = Array.from(match.value);
+  const parts: ReactNode[] = [];
+  let cursor = 0;
+  match.ranges.forEach(([start, end]) => {
+    if (start > cursor) parts.push(chars.slice(cursor, start).join(''));
+    parts.push(<mark key={start}>{chars.slice(start, end).join('')}</mark>);
+    cursor = end;
+  });
+  if (cursor < chars.length) parts.push(chars.slice(cursor).join(''));
+  return parts;
+}
+
+interface CommandPaletteProps {
+  // Off where the server refuses to record opens, e.g. read-only tablets
+  trackOpens?: boolean;
+}
+
+/** Keyboard launcher opened with Ctrl-K / Cmd-K. */
+export function CommandPalette({ trackOpens }: CommandPaletteProps) {
+  const { t } = useTranslation();
+  const [open, setOpen] = useState(false);
+  const [query, setQuery] = useState('');
+  const [hits, setHits] = useState<SearchHit[]>([]);
+  const [selected, setSelected] = useState(0);
+  const inputRef = useRef<HTMLInputElement>(null);
+  const debouncedQuery = useDebounce(query, 120);
+
+  useEffect(() => {
+    const handleShortcut = (e: globalThis.KeyboardEvent) => {
+      if ((e.ctrlKey || e.metaKey) && e.key.toLowerCase() === 'k') {
+        e.preventDefault();
+        setOpen((value) => !value);
+      }
+    };
+    document.addEventListener('keydown', handleShortcut);
+    return () => document.removeEventListener('keydown', handleShortcut);
+  }, []);
+
+  useEffect(() => {
+    if (open) {
+      inputRef.current?.focus();
+    } else {
+      setQuery('');
+      setHits([]);
+    }
+  }, [open]);
+
+  useEffect(() => {
+    if (!open) return;
+    let cancelled = false;
+    searchServices(debouncedQuery.trim(), RESULT_LIMIT)
+      .then((results) => {
+        if (cancelled) return;
+        setHits(results);
+        setSelected(0);
+      })
+      .catch(() => {
+        if (!cancelled) setHits([]);
+      });
+    return () => {
+      cancelled = true;
+    };
+  }, [open, debouncedQuery]);
+
+  if (!open) return null;
+
+  const launch = (hit: SearchHit) => {
+    const url = hit.service.open_url || hit.service.url;
+    if (!url) return;
+    if (trackOpens) recordOpen(hit.service.id);
+    window.open(url, '_blank', 'noopener,noreferrer');
+    setOpen(false);
+  };
+
+  const handleKeyDown = (e: KeyboardEvent<HTMLInputElement>) => {
+    if (e.key === 'ArrowDown') {
+      e.preventDefault();
+      setSelected((index) => Math.min(index + 1, hits.length - 1));
+    } else if (e.key === 'ArrowUp') {
+      e.preventDefault();
+      setSelected((index) => Math.max(index - 1, 0));
+    } else if (e.key === 'Enter' && hits[selected]) {
+      e.preventDefault();
+      launch(hits[selected]);
+    } else if (e.key === 'Escape') {
+      setOpen(false);
+    }
+  };
+
+  return (
+    <div className="modal-backdrop palette-backdrop" onClick={() => setOpen(false)}>
+      <section
+        className="palette"
+        role="dialog"
+        aria-modal="true"
+        aria-label={t('launcher.title')}
+        onClick={(e) => e.stopPropagation()}
+      >
+        <input
+          ref={inputRef}
+          className="palette-input"
+          value={query}
+          onChange={(e) => setQuery(e.target.value)}
+          onKeyDown={handleKeyDown}
+          placeholder={t('launcher.placeholder')}
+          aria-label={t('launcher.placeholder')}
+        />
+        {hits.length === 0 ? (
+          <p className="palette-empty">{t('launcher.noResults')}</p>
+        ) : (
+          <ul className="palette-results" role="listbox">
+            {hits.map((hit, index) => {
+              const name = hit.matches.find((match) => match.field === 'display_name');
+              const other = hit.matches.find((match) => match.field !== 'display_name');
+              return (
+                <li
+                  key={hit.service.id}
+                  role="option"
+                  aria-selected={index === selected}
+                  className={index === selected ? 'palette-item active' : 'palette-item'}
+                  onMouseEnter={() => setSelected(index)}
+                  onClick={() => launch(hit)}
+                >
+                  <span className="palette-name">
+                    {hit.service.icon && <span aria-hidden="true">{hit.service.icon} </span>}
+                    {highlight(name, hit.service.display_name)}
+                  </span>
+                  {other && <span className="palette-detail">{highlight(other, other.value)}</span>}
+                </li>
+              );
+            })}
+          </ul>
+        )}
+        <footer className="palette-hint">{t('launcher.hint')}</footer>
+      </section>
+    </div>
+  );
+}
@@ -1,6 +1,6 @@
 import { type FormEvent, useEffect, useMemo, useState } from 'react';
 import { useTranslation } from '../i18n';
-import type { ServiceEntry, ServiceProtocol, UpdateServiceRequest } from '../types';
+import type { ServiceEntry, ServiceLink, ServiceProtocol, UpdateServiceRequest } from '../types';
 
 interface EditServiceModalProps {
   service: ServiceEntry | null;
@@ -23,9 +23,11 @@ const useLockableFields = () => {
     { key: 'url', label: 'URL' },
     { key: 'group', label: t('modal.group') },
     { key: 'tags', label: t('modal.tags') },
+    { key: 'aliases', label: t('modal.aliases') },
     { key: 'icon', label: t('modal.icon') },
     { key: 'hidden', label: t('modal.hidden') },
     { key: 'favorite', label: t('modal.favorite') },
+    { key: 'links', label: t('modal.links') },
   ] as const;
 };
 
@@ -43,9 +45,11 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
   const [url, setUrl] = useState('');
   const [group, setGroup] = useState('');
   const [tags, setTags] = useState('');
+  const [aliases, setAliases] = useState('');
   const [icon, setIcon] = useState('');
   const [hidden, setHidden] = useState(false);
   const [favorite, setFavorite] = useState(false);
+  const [links, setLinks] = useState<ServiceLink[]>([]);
   const [lockedFields, setLockedFields] = useState<string[]>([]);
   const [saving, setSaving] = useState(false);
 
@@ -62,9 +66,11 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
     setUrl(service.url ?? '');
     setGroup(service.group ?? '');
     setTags(service.tags.join(', '));
+    setAliases((service.aliases ?? []).join(', '));
     setIcon(service.icon ?? '');
     setHidden(service.hidden);
     setFavorite(service.favorite);
+    setLinks(service.links ?? []);
     setLockedFields(service.locked_fields);
   }, [service]);
 
@@ -111,6 +117,18 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
     );
   };
 
+  const updateLink = (index: number, changes: Partial<ServiceLink>) => {
+    setLinks((current) =>
+      current.map((link, position) => (position === index ? { ...link, ...changes } : link))
+    );
+  };
+
+  // A full URL replaces the built one; anything else is a path on the service
+  const setLinkTarget = (index: number, value: string) => {
+    const isUrl = /^[a-z][a-z0-9+.-]*:\/\//i.test(value.trim());
+    updateLink(index, isUrl ? { url: value, path: null } : { url: null, path: value });
+  };
+
   const handleSubmit = async (event: FormEvent<HTMLFormElement>) => {
     event.preventDefault();
     setSaving(true);
@@ -132,9 +150,16 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
           .split(',')
           .map((value) => value.trim())
           .filter(Boolean),
+        aliases: aliases
+          .split(',')
+          .map((value) => value.trim())
+          .filter(Boolean),
         icon: icon.trim() || null,
         hidden,
         favorite,
+        links: links
+          .filter((link) => link.name.trim())
+          .map(({ open_url: _openUrl, ...link }) => link),
         locked_fields: lockedFields,
         auto_lock: false,
       };
@@ -270,6 +295,16 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
             />
           </label>
 
+          {/* Aliases */}
+          <label>
+            {t('modal.aliases')}
+            <input
+              value={aliases}
+              onChange={(e) => setAliases(e.target.value)}
+              placeholder={t('modal.aliasesPlaceholder')}
+            />
+          </label>
+
           {/* Icon */}
           <label>
             {t('modal.icon')}
@@ -280,6 +315,49 @@ export function EditServiceModal({ service, onClose, onSave }: EditServiceModalP
             />
           </label>
 
+          {/* Links */}
+          <fieldset className="link-editor">
+            <legend>🔗 {t('modal.links')}</legend>
+            {links.map((link, index) => (
+              <div key={index} className="link-row">
+                <input
+                  value={link.name}
+                  onChange={(e) => updateLink(index, { name: e.target.value })}
+                  placeholder={t('modal.linkName')}
+                  aria-label={t('modal.linkName')}
+                />
+                <input
+                  value={link.url ?? link.path ?? ''}
+                  onChange={(e) => setLinkTarget(index, e.target.value)}
+                  placeholder={t('modal.linkTarget')}
+                  aria-label={t('modal.linkTarget')}
+                />
+                <input
+                  value={link.port?.toString() ?? ''}
+                  onChange={(e) => {
+                    const value = Number(e.target.value);
+                    updateLink(index, {
+                      port: e.target.value.trim() && value > 0 ? Math.floor(value) : null,
+                    });
+                  }}
+                  inputMode="numeric"
+                  placeholder={t('modal.port')}
+                  aria-label={t('modal.port')}
+                />
+                <button
+                  type="button"
+                  onClick={() => setLinks((current) => current.filter((_, position) => position !== index))}
+                  aria-label={t('modal.removeLink')}
+                >
+                  ✕
+                </button>
+              </div>
+            ))}
+            <button type="button" onClick={() => setLinks((current) => [...current, { name: '' }])}>
+              + {t('modal.addLink')}
+            </button>
+          </fieldset>
+
           {/* Favorite Toggle */}
           <label className="toggle-row">
             <input 
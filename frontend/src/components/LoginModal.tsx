@@ -0,0 +1,103 @@
+import { type FormEvent, useState } from 'react';
+import { login, UnauthorizedError } from '../api/client';
+import { useTranslation } from '../i18n';
+import type { SessionInfo } from '../types';
+
+interface LoginModalProps {
+  open: boolean;
+  // Omitted when the page cannot be used without signing in
+  onClose?: () => void;
+  onLogin: (session: SessionInfo) => void;
+}
+
+export function LoginModal({ open, onClose, onLogin }: LoginModalProps) {
+  const { t } = useTranslation();
+  const [username, setUsername] = useState('');
+  const [password, setPassword] = useState('');
+  const [error, setError] = useState<string | null>(null);
+  const [submitting, setSubmitting] = useState(false);
+
+  if (!open) return null;
+
+  const handleSubmit = async (event: FormEvent<HTMLFormElement>) => {
+    event.preventDefault();
+    setSubmitting(true);
+    setError(null);
+    try {
+      const session = await login(username.trim(), password);
+      setPassword('');
+      onLogin(session);
+    } catch (loginError) {
+      setError(
+        loginError instanceof UnauthorizedError
+          ? t('auth.invalidCredentials')
+          : loginError instanceof Error
+            ? loginError.message
+            : t('auth.invalidCredentials')
+      );
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  return (
+    <div className="modal-backdrop" role="dialog" aria-modal="true" aria-labelledby="login-title">
+      <section className="modal-panel" onClick={(e) => e.stopPropagation()}>
+        <header className="modal-header">
+          <h3 id="login-title">{t('auth.signIn')}</h3>
+          {onClose && (
+            <button
+              type="button"
+              className="modal-close-btn"
+              onClick={onClose}
+              aria-label={t('a11y.closeDialog')}
+            >
+              ✕
+            </button>
+          )}
+        </header>
+
+        <form className="modal-form" onSubmit={handleSubmit}>
+          <label>
+            {t('auth.username')}
+            <input
+              value={username}
+              onChange={(e) => setUsername(e.target.value)}
+              autoComplete="username"
+              autoFocus
+              required
+            />
+          </label>
+
+          <label>
+            {t('auth.password')}
+            <input
+              type="password"
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              autoComplete="current-password"
+              required
+            />
+          </label>
+
+          {error && (
+            <p className="error-note" role="alert">
+              {error}
+            </p>
+          )}
+
+          <div className="modal-actions">
+            {onClose && (
+              <button type="button" onClick={onClose}>
+                {t('actions.cancel')}
+              </button>
+            )}
+            <button type="submit" disabled={submitting}>
+              {submitting ? t('auth.signingIn') : t('auth.signIn')}
+            </button>
+          </div>
+        </form>
+      </section>
+    </div>
+  );
+}
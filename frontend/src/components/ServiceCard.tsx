@@ -1,10 +1,16 @@
 import { memo, useMemo } from 'react';
+import { recordOpen } from '../api/client';
 import { useTranslation } from '../i18n';
-import type { ServiceEntry } from '../types';
+import type { ServiceEntry, ServiceLink, ServiceProtocol } from '../types';
 
 interface ServiceCardProps {
   service: ServiceEntry;
-  onEdit: (service: ServiceEntry) => void;
+  // Group name in the current language, from /api/groups
+  groupLabel?: string;
+  // Omitted for viewers, who cannot edit
+  onEdit?: (service: ServiceEntry) => void;
+  // Record opens; off where the server refuses them, e.g. read-only tablets
+  trackOpens?: boolean;
 }
 
 // Status colors mapped to CSS classes
@@ -69,19 +75,49 @@ function getServiceIcon(service: ServiceEntry): string {
   return '📎';
 }
 
-function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
+// Same rules as the server's build_service_url
+function buildUrl(protocol: ServiceProtocol, host: string, port?: number | null, path?: string | null) {
+  if ((protocol === 'http' || protocol === 'https') && port) {
+    const suffix = path ? (path.startsWith('/') ? path : `/${path}`) : '';
+    return `${protocol}://${host}:${port}${suffix}`;
+  }
+  return undefined;
+}
+
+// Link fields left out fall back to the service's own
+function linkUrl(service: ServiceEntry, link: ServiceLink) {
+  if (link.open_url) {
+    return link.open_url;
+  }
+  if (link.url) {
+    return link.url;
+  }
+  return buildUrl(
+    link.protocol ?? service.protocol,
+    link.host ?? service.host,
+    link.port ?? service.port,
+    link.path,
+  );
+}
+
+function ServiceCardComponent({ service, groupLabel, onEdit, trackOpens }: ServiceCardProps) {
   const { t } = useTranslation();
+  const handleOpen = () => {
+    if (trackOpens) recordOpen(service.id);
+  };
   
-  const resolvedUrl = useMemo(() => {
-    if (service.url) {
-      return service.url;
-    }
-    if ((service.protocol === 'http' || service.protocol === 'https') && service.port) {
-      const path = service.path ? (service.path.startsWith('/') ? service.path : `/${service.path}`) : '';
-      return `${service.protocol}://${service.host}:${service.port}${path}`;
-    }
-    return undefined;
-  }, [service]);
+  const resolvedUrl = useMemo(
+    () => service.open_url || service.url || buildUrl(service.protocol, service.host, service.port, service.path),
+    [service]
+  );
+
+  const links = useMemo(
+    () => (service.links ?? []).flatMap((link) => {
+      const url = linkUrl(service, link);
+      return url ? [{ ...link, url }] : [];
+    }),
+    [service]
+  );
 
   const portDisplay = service.port ? `:${service.port}` : '';
   const icon = getServiceIcon(service);
@@ -124,6 +160,7 @@ function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
             rel="noreferrer"
             className="url-link"
             title={resolvedUrl}
+            onClick={handleOpen}
           >
             <svg width="14" height="14" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" aria-hidden="true">
               <path d="M18 13v6a2 2 0 0 1-2 2H5a2 2 0 0 1-2-2V8a2 2 0 0 1 2-2h6" />
@@ -152,7 +189,7 @@ function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
         <span className={`protocol-badge ${protocolClass}`}>
           {protocolLabel}
         </span>
-        {service.group && <span className="group-badge">{service.group}</span>}
+        {service.group && <span className="group-badge">{groupLabel ?? service.group}</span>}
         {service.favorite && (
           <span className="favorite-badge" title={t('a11y.favoriteBadge')}>
             ⭐
@@ -160,6 +197,24 @@ function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
         )}
       </div>
 
+      {links.length > 0 && (
+        <div className="link-list">
+          {links.map((link) => (
+            <a
+              key={`${link.name}-${link.url}`}
+              href={link.url}
+              target="_blank"
+              rel="noreferrer"
+              className="link-item"
+              title={link.url}
+              onClick={handleOpen}
+            >
+              {link.icon ? `${link.icon} ${link.name}` : link.name}
+            </a>
+          ))}
+        </div>
+      )}
+
       {service.tags.length > 0 ? (
         <div className="tag-list">
           {service.tags.slice(0, 4).map((tag) => (
@@ -174,18 +229,20 @@ function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
       ) : null}
 
       <div className="service-actions">
-        <button 
-          type="button" 
-          className="edit-btn" 
-          onClick={() => onEdit(service)} 
-          title={t('actions.edit')}
-        >
-          <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" aria-hidden="true">
-            <path d="M11 4H4a2 2 0 0 0-2 2v14a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2v-7" />
-            <path d="M18.5 2.5a2.121 2.121 0 0 1 3 3L12 15l-4 1 1-4 9.5-9.5z" />
-          </svg>
-          {t('actions.edit')}
-        </button>
+        {onEdit && (
+          <button 
+            type="button" 
+            className="edit-btn" 
+            onClick={() => onEdit(service)} 
+            title={t('actions.edit')}
+          >
+            <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" aria-hidden="true">
+              <path d="M11 4H4a2 2 0 0 0-2 2v14a2 2 0 0 0 2 2h14a2 2 0 0 0 2-2v-7" />
+              <path d="M18.5 2.5a2.121 2.121 0 0 1 3 3L12 15l-4 1 1-4 9.5-9.5z" />
+            </svg>
+            {t('actions.edit')}
+          </button>
+        )}
         <a
           className={`open-link ${resolvedUrl ? '' : 'disabled'}`}
           href={resolvedUrl || '#'}
@@ -194,7 +251,9 @@ function ServiceCardComponent({ service, onEdit }: ServiceCardProps) {
           onClick={(event) => {
             if (!resolvedUrl) {
               event.preventDefault();
+              return;
             }
+            handleOpen();
           }}
           aria-disabled={!resolvedUrl}
         >
@@ -53,6 +53,12 @@ export const en: TranslationSchema = {
     allStatus: 'All Status',
     filterByGroup: 'Filter by group',
     filterByStatus: 'Filter by status',
+    sortBy: 'Sort services',
+    sortManual: 'Manual order',
+    sortName: 'Name',
+    sortStatus: 'Status',
+    sortLastUsed: 'Last used',
+    sortRecentlyAdded: 'Recently added',
   },
   
   sections: {
@@ -94,12 +100,19 @@ export const en: TranslationSchema = {
     group: 'Group',
     tags: 'Tags',
     tagsPlaceholder: 'dashboard, internal (comma-separated)',
+    aliases: 'Aliases',
+    aliasesPlaceholder: 'jf, movies (comma-separated)',
     icon: 'Icon',
     iconPlaceholder: 'Emoji icon',
     favorite: 'Favorite',
     addToFavorites: 'Add to favorites',
     hidden: 'Hidden',
     hideFromView: 'Hide from main view',
+    links: 'Links',
+    linkName: 'Name',
+    linkTarget: 'URL or path',
+    addLink: 'Add link',
+    removeLink: 'Remove link',
     previewUrl: 'Preview URL',
     lockedFields: 'Locked Fields',
     lockedFieldsDescription: 'Prevent auto-discovery from overwriting these fields',
@@ -117,6 +130,23 @@ export const en: TranslationSchema = {
     saveFailed: 'Save failed',
   },
   
+  launcher: {
+    title: 'Quick launch',
+    placeholder: 'Search services…',
+    noResults: 'No matching services',
+    hint: '↑↓ to select · Enter to open · Esc to close',
+  },
+  
+  auth: {
+    signIn: 'Sign in',
+    signOut: 'Sign out',
+    signingIn: 'Signing in...',
+    username: 'Username',
+    password: 'Password',
+    invalidCredentials: 'Invalid username or password',
+    loginRequired: 'Please sign in to continue',
+  },
+  
   a11y: {
     closeDialog: 'Close dialog',
     clearSearch: 'Clear search',
@@ -53,6 +53,12 @@ export const zh: TranslationSchema = {
     allStatus: '全部状态',
     filterByGroup: '按分组筛选',
     filterByStatus: '按状态筛选',
+    sortBy: '排序方式',
+    sortManual: '手动排序',
+    sortName: '名称',
+    sortStatus: '状态',
+    sortLastUsed: '最近使用',
+    sortRecentlyAdded: '最近添加',
   },
   
   sections: {
@@ -94,12 +100,19 @@ export const zh: TranslationSchema = {
     group: '分组',
     tags: '标签',
     tagsPlaceholder: 'dashboard, internal（用逗号分隔）',
+    aliases: '别名',
+    aliasesPlaceholder: 'jf, movies（用逗号分隔）',
     icon: '图标',
     iconPlaceholder: 'Emoji 图标',
     favorite: '收藏',
     addToFavorites: '添加到收藏夹',
     hidden: '隐藏',
     hideFromView: '从主视图隐藏',
+    links: '链接',
+    linkName: '名称',
+    linkTarget: 'URL 或路径',
+    addLink: '添加链接',
+    removeLink: '删除链接',
     previewUrl: '预览 URL',
     lockedFields: '锁定字段',
     lockedFieldsDescription: '防止 Discovery 自动覆盖这些字段',
@@ -117,6 +130,23 @@ export const zh: TranslationSchema = {
     saveFailed: '保存失败',
   },
   
+  launcher: {
+    title: '快速启动',
+    placeholder: '搜索服务…',
+    noResults: '没有匹配的服务',
+    hint: '↑↓ 选择 · Enter 打开 · Esc 关闭',
+  },
+  
+  auth: {
+    signIn: '登录',
+    signOut: '退出登录',
+    signingIn: '登录中...',
+    username: '用户名',
+    password: '密码',
+    invalidCredentials: '用户名或密码错误',
+    loginRequired: '请先登录',
+  },
+  
   a11y: {
     closeDialog: '关闭对话框',
     clearSearch: '清除搜索',
@@ -61,6 +61,12 @@ export interface TranslationSchema {
     allStatus: string;
     filterByGroup: string;
     filterByStatus: string;
+    sortBy: string;
+    sortManual: string;
+    sortName: string;
+    sortStatus: string;
+    sortLastUsed: string;
+    sortRecentlyAdded: string;
   };
   
   // Service sections
@@ -106,12 +112,19 @@ export interface TranslationSchema {
     group: string;
     tags: string;
     tagsPlaceholder: string;
+    aliases: string;
+    aliasesPlaceholder: string;
     icon: string;
     iconPlaceholder: string;
     favorite: string;
     addToFavorites: string;
     hidden: string;
     hideFromView: string;
+    links: string;
+    linkName: string;
+    linkTarget: string;
+    addLink: string;
+    removeLink: string;
     previewUrl: string;
     lockedFields: string;
     lockedFieldsDescription: string;
@@ -130,6 +143,25 @@ export interface TranslationSchema {
     saveFailed: string;
   };
   
+  // Ctrl-K launcher
+  launcher: {
+    title: string;
+    placeholder: string;
+    noResults: string;
+    hint: string;
+  };
+  
+  // Login
+  auth: {
+    signIn: string;
+    signOut: string;
+    signingIn: string;
+    username: string;
+    password: string;
+    invalidCredentials: string;
+    loginRequired: string;
+  };
+
   // Accessibility
   a11y: {
     closeDialog: string;
@@ -1,14 +1,34 @@
 import { useCallback, useEffect, useMemo, useRef, useState } from 'react';
 
-import { fetchDiscoveryStatus, fetchServices, runDiscovery, updateService } from '../api/client';
+import {
+  fetchCapabilities,
+  fetchDiscoveryStatus,
+  fetchGroups,
+  fetchServices,
+  fetchSession,
+  logout,
+  runDiscovery,
+  UnauthorizedError,
+  updateService,
+} from '../api/client';
+import { CommandPalette } from '../components/CommandPalette';
 import { EditServiceModal } from '../components/EditServiceModal';
 import { LanguageSwitcher } from '../components/LanguageSwitcher';
+import { LoginModal } from '../components/LoginModal';
 import { ServiceCard } from '../components/ServiceCard';
 import { ToastContainer } from '../components/Toast';
 import { useTranslation } from '../i18n';
 import { useDebounce } from '../hooks/useDebounce';
 import { useToast } from '../hooks/useToast';
-import type { DiscoveryStatusInfo, ServiceEntry, ServiceStatus } from '../types';
+import type {
+  Capabilities,
+  DiscoveryStatusInfo,
+  GroupInfo,
+  ServiceEntry,
+  ServiceSort,
+  ServiceStatus,
+  SessionInfo,
+} from '../types';
 
 // Stat icon SVGs
 const statIcons = {
@@ -38,29 +58,37 @@ const statIcons = {
   ),
 };
 
-// Predefined groups
-const PREDEFINED_GROUPS = ['Media', 'Download', 'Sync', 'Photo', 'Monitor', 'Other'];
+// Shown in its own collapsible section rather than the group filter
+const SYSTEM_GROUP = 'system';
 
 export function HomePage() {
   // i18n - use ref to avoid re-renders
-  const { t } = useTranslation();
+  const { t, locale } = useTranslation();
   const tRef = useRef(t);
   tRef.current = t;
 
   // Data state
   const [services, setServices] = useState<ServiceEntry[]>([]);
   const [systemServices, setSystemServices] = useState<ServiceEntry[]>([]);
+  const [groups, setGroups] = useState<GroupInfo[]>([]);
   const [discoveryStatus, setDiscoveryStatus] = useState<DiscoveryStatusInfo | null>(null);
   
   // UI state
   const [systemExpanded, setSystemExpanded] = useState(false);
-  const [query, setQuery] = useState('');
+  // Go-links to unknown aliases land here as /?q=<alias>
+  const [query, setQuery] = useState(
+    () => new URLSearchParams(window.location.search).get('q') ?? ''
+  );
   const [groupFilter, setGroupFilter] = useState('');
   const [statusFilter, setStatusFilter] = useState<ServiceStatus | ''>('');
+  const [sort, setSort] = useState<ServiceSort>('manual');
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const [runningDiscovery, setRunningDiscovery] = useState(false);
   const [editingService, setEditingService] = useState<ServiceEntry | null>(null);
+  const [session, setSession] = useState<SessionInfo | null>(null);
+  const [capabilities, setCapabilities] = useState<Capabilities | null>(null);
+  const [loginOpen, setLoginOpen] = useState(false);
   
   // Hooks
   const debouncedQuery = useDebounce(query, 300);
@@ -81,31 +109,40 @@ export function HomePage() {
           group: groupFilter || undefined,
           status: statusFilter || undefined,
           include_hidden: false,
+          sort,
         }),
         fetchDiscoveryStatus(),
       ]);
       setServices(serviceData);
       setDiscoveryStatus(statusData);
     } catch (loadError) {
+      if (loadError instanceof UnauthorizedError) {
+        setLoginOpen(true);
+        return;
+      }
       const message = loadError instanceof Error ? loadError.message : 'Failed to load services';
       setError(message);
       showErrorRef.current(tRef.current('toast.loadServicesFailed'), message);
     } finally {
       setLoading(false);
     }
-  }, [debouncedQuery, groupFilter, statusFilter]); // Only depend on filter states
+  }, [debouncedQuery, groupFilter, statusFilter, sort]); // Only depend on filter states
 
   // Load system services - stable callback
   const loadSystemServices = useCallback(async () => {
     try {
       const data = await fetchServices({
         q: debouncedQuery || undefined,
-        group: '系统',
+        group: SYSTEM_GROUP,
         status: statusFilter || undefined,
         include_hidden: true,
       });
       setSystemServices(data);
     } catch (loadError) {
+      if (loadError instanceof UnauthorizedError) {
+        setLoginOpen(true);
+        return;
+      }
       const message = loadError instanceof Error ? loadError.message : 'Failed to load system services';
       showErrorRef.current(tRef.current('toast.loadSystemFailed'), message);
     }
@@ -116,16 +153,40 @@ export function HomePage() {
     void loadData();
   }, [loadData]);
 
+  // The server decides what this client may do, e.g. read-only wall tablets
+  const loadCapabilities = useCallback(() => {
+    fetchCapabilities()
+      .then(setCapabilities)
+      .catch(() => setCapabilities(null));
+  }, []);
+
+  useEffect(() => {
+    fetchSession()
+      .then(setSession)
+      .catch(() => setSession(null));
+    loadCapabilities();
+  }, [loadCapabilities]);
+
+  // Group names come from the server in the current language; reloaded
+  // with the services since edits change which groups are in use
+  useEffect(() => {
+    fetchGroups(locale)
+      .then(setGroups)
+      .catch(() => setGroups([]));
+  }, [locale, services]);
+
   // Computed values
-  const uniqueGroups = useMemo(() => {
-    const set = new Set<string>();
-    for (const service of services) {
-      if (service.group && !PREDEFINED_GROUPS.includes(service.group) && service.group !== '系统') {
-        set.add(service.group);
-      }
-    }
-    return Array.from(set).sort();
-  }, [services]);
+  const groupLabels = useMemo(
+    () => new Map(groups.map((group) => [group.key, group.label])),
+    [groups]
+  );
+
+  const filterGroups = useMemo(
+    () => groups.filter((group) =>
+      group.key !== SYSTEM_GROUP && (group.services > 0 || group.key === groupFilter)
+    ),
+    [groups, groupFilter]
+  );
 
   const favoriteServices = useMemo(() => 
     services.filter((service) => service.favorite),
@@ -159,6 +220,10 @@ export function HomePage() {
         await loadSystemServices();
       }
     } catch (runError) {
+      if (runError instanceof UnauthorizedError) {
+        setLoginOpen(true);
+        return;
+      }
       const message = runError instanceof Error ? runError.message : 'Failed to run discovery';
       showErrorRef.current(tRef.current('toast.discoveryFailed'), message);
     } finally {
@@ -189,12 +254,44 @@ export function HomePage() {
       );
       showSuccessRef.current(tRef.current('toast.serviceUpdated'), updated.display_name);
     } catch (saveError) {
+      if (saveError instanceof UnauthorizedError) {
+        setLoginOpen(true);
+      }
       const message = saveError instanceof Error ? saveError.message : 'Failed to save service';
       showErrorRef.current(tRef.current('toast.saveFailed'), message);
       throw saveError;
     }
   };
 
+  const handleLogin = (next: SessionInfo) => {
+    setSession(next);
+    setLoginOpen(false);
+    loadCapabilities();
+    void loadData();
+  };
+
+  const handleLogout = async () => {
+    await logout();
+    const next = await fetchSession().catch(() => null);
+    setSession(next);
+    loadCapabilities();
+    if (next?.auth_enabled && !next.anonymous_read) {
+      setServices([]);
+      setSystemServices([]);
+      setLoginOpen(true);
+    }
+  };
+
+  // Without anonymous reads there is nothing to show until the user signs in
+  const loginRequired = Boolean(session?.auth_enabled && !session.authenticated && !session.anonymous_read);
+  const canEdit = capabilities
+    ? capabilities.can_edit
+    : !session?.auth_enabled || session.role === 'editor' || session.role === 'admin';
+  const canDiscover = capabilities ? capabilities.can_run_discovery : canEdit;
+  const showHidden = capabilities?.show_hidden ?? true;
+  const editHandler = canEdit ? setEditingService : undefined;
+  const canRecordUse = capabilities?.can_record_use ?? false;
+
   const clearFilters = () => {
     setQuery('');
     setGroupFilter('');
@@ -222,7 +319,7 @@ export function HomePage() {
         <div className="empty-icon" aria-hidden="true">🧭</div>
         <h3>{t('empty.noServices')}</h3>
         <p>{t('empty.noServicesDescription')}</p>
-        <button type="button" onClick={handleRunDiscovery} disabled={runningDiscovery}>
+        <button type="button" onClick={handleRunDiscovery} disabled={runningDiscovery || !canDiscover}>
           {runningDiscovery ? t('actions.discovering') : t('actions.discover')}
         </button>
       </div>
@@ -242,27 +339,40 @@ export function HomePage() {
         </div>
         <div style={{ display: 'flex', alignItems: 'center', gap: '12px' }}>
           <LanguageSwitcher />
-          <button
-            type="button"
-            className="discovery-btn"
-            onClick={handleRunDiscovery}
-            disabled={runningDiscovery}
-          >
-            {runningDiscovery ? (
-              <>
-                <span className="spinner" aria-hidden="true" />
-                {t('actions.discovering')}
-              </>
+          {session?.auth_enabled && (
+            session.authenticated ? (
+              <button type="button" className="refresh-btn" onClick={() => void handleLogout()} title={t('auth.signOut')}>
+                {session.username} · {t('auth.signOut')}
+              </button>
             ) : (
-              <>
-                <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" aria-hidden="true">
-                  <circle cx="11" cy="11" r="8" />
-                  <path d="m21 21-4.35-4.35" />
-                </svg>
-                {t('actions.discover')}
-              </>
-            )}
-          </button>
+              <button type="button" className="refresh-btn" onClick={() => setLoginOpen(true)}>
+                {t('auth.signIn')}
+              </button>
+            )
+          )}
+          {!capabilities?.read_only && (
+            <button
+              type="button"
+              className="discovery-btn"
+              onClick={handleRunDiscovery}
+              disabled={runningDiscovery || !canDiscover}
+            >
+              {runningDiscovery ? (
+                <>
+                  <span className="spinner" aria-hidden="true" />
+                  {t('actions.discovering')}
+                </>
+              ) : (
+                <>
+                  <svg width="16" height="16" viewBox="0 0 24 24" fill="none" stroke="currentColor" strokeWidth="2" aria-hidden="true">
+                    <circle cx="11" cy="11" r="8" />
+                    <path d="m21 21-4.35-4.35" />
+                  </svg>
+                  {t('actions.discover')}
+                </>
+              )}
+            </button>
+          )}
         </div>
       </header>
 
@@ -332,11 +442,10 @@ export function HomePage() {
           aria-label={t('filters.filterByGroup')}
         >
           <option value="">{t('filters.allGroups')}</option>
-          {PREDEFINED_GROUPS.map((group) => (
-            <option key={group} value={group}>{group}</option>
-          ))}
-          {uniqueGroups.map((group) => (
-            <option key={group} value={group}>{group}</option>
+          {filterGroups.map((group) => (
+            <option key={group.key} value={group.key}>
+              {group.icon ? `${group.icon} ${group.label}` : group.label}
+            </option>
           ))}
         </select>
 
@@ -351,6 +460,18 @@ export function HomePage() {
           <option value="unknown">{t('status.unknown')}</option>
         </select>
 
+        <select
+          value={sort}
+          onChange={(e) => setSort(e.target.value as ServiceSort)}
+          aria-label={t('filters.sortBy')}
+        >
+          <option value="manual">{t('filters.sortManual')}</option>
+          <option value="name">{t('filters.sortName')}</option>
+          <option value="status">{t('filters.sortStatus')}</option>
+          <option value="last_used">{t('filters.sortLastUsed')}</option>
+          <option value="recently_added">{t('filters.sortRecentlyAdded')}</option>
+        </select>
+
         <button 
           type="button" 
           className="refresh-btn" 
@@ -409,7 +530,13 @@ export function HomePage() {
               </h2>
               <div className="card-grid">
                 {favoriteServices.map((service) => (
-                  <ServiceCard key={service.id} service={service} onEdit={setEditingService} />
+                  <ServiceCard
+                    key={service.id}
+                    service={service}
+                    groupLabel={service.group ? groupLabels.get(service.group) : undefined}
+                    onEdit={editHandler}
+                    trackOpens={canRecordUse}
+                  />
                 ))}
               </div>
             </section>
@@ -423,7 +550,13 @@ export function HomePage() {
               )}
               <div className="card-grid">
                 {nonFavoriteServices.map((service) => (
-                  <ServiceCard key={service.id} service={service} onEdit={setEditingService} />
+                  <ServiceCard
+                    key={service.id}
+                    service={service}
+                    groupLabel={service.group ? groupLabels.get(service.group) : undefined}
+                    onEdit={editHandler}
+                    trackOpens={canRecordUse}
+                  />
                 ))}
               </div>
             </section>
@@ -433,29 +566,37 @@ export function HomePage() {
           {services.length === 0 && renderEmptyState()}
 
           {/* System Services Toggle */}
-          <section className="system-services-section">
-            <button 
-              type="button" 
-              className="system-toggle" 
-              onClick={() => void toggleSystem()}
-              aria-expanded={systemExpanded}
-              aria-controls="system-services-grid"
-            >
-              <span aria-hidden="true">▶</span>
-              <span>{t('sections.systemServices')}</span>
-              <span className="system-count">{systemServices.length}</span>
-            </button>
-          </section>
+          {showHidden && (
+            <section className="system-services-section">
+              <button 
+                type="button" 
+                className="system-toggle" 
+                onClick={() => void toggleSystem()}
+                aria-expanded={systemExpanded}
+                aria-controls="system-services-grid"
+              >
+                <span aria-hidden="true">▶</span>
+                <span>{t('sections.systemServices')}</span>
+                <span className="system-count">{systemServices.length}</span>
+              </button>
+            </section>
+          )}
 
           {/* System Services Grid */}
-          {systemExpanded && (
+          {showHidden && systemExpanded && (
             <section 
               id="system-services-grid" 
               className="card-grid"
               aria-label="System services"
             >
               {systemServices.map((service) => (
-                <ServiceCard key={service.id} service={service} onEdit={setEditingService} />
+                <ServiceCard
+                  key={service.id}
+                  service={service}
+                  groupLabel={service.group ? groupLabels.get(service.group) : undefined}
+                  onEdit={editHandler}
+                  trackOpens={canRecordUse}
+                />
               ))}
               {systemServices.length === 0 && (
                 <p className="empty-note">{t('sections.noSystemServices')}</p>
@@ -472,6 +613,16 @@ export function HomePage() {
         onSave={handleSaveService}
       />
 
+      {/* Ctrl-K launcher */}
+      {!loginRequired && <CommandPalette trackOpens={canRecordUse} />}
+
+      {/* Login */}
+      <LoginModal
+        open={loginOpen || loginRequired}
+        onClose={loginRequired ? undefined : () => setLoginOpen(false)}
+        onLogin={handleLogin}
+      />
+
       {/* Toast Notifications */}
       <ToastContainer toasts={toasts} onRemove={removeToast} />
     </main>
@@ -1,6 +1,18 @@
 export type ServiceProtocol = 'http' | 'https' | 'tcp' | 'other';
 export type ServiceStatus = 'running' | 'stopped' | 'unknown';
-export type ServiceSource = 'auto' | 'manual' | 'merged';
+export type ServiceSource = 'auto' | 'manual' | 'merged' | 'declared';
+
+export interface ServiceLink {
+  name: string;
+  url?: string | null;
+  protocol?: ServiceProtocol | null;
+  host?: string | null;
+  port?: number | null;
+  path?: string | null;
+  icon?: string | null;
+  /** Set by the server for the network the dashboard was opened from */
+  open_url?: string | null;
+}
 
 export interface ServiceEntry {
   id: string;
@@ -12,18 +24,43 @@ export interface ServiceEntry {
   protocol: ServiceProtocol;
   path?: string;
   url?: string;
+  open_url?: string | null;
   status: ServiceStatus;
   group?: string;
   tags: string[];
+  aliases: string[];
   icon?: string;
   hidden: boolean;
   favorite: boolean;
+  links: ServiceLink[];
+  network_urls: Record<string, string>;
   source: ServiceSource;
   locked_fields: string[];
+  declared_fields: string[];
+  updated_by?: string | null;
   last_seen_at?: string;
+  position?: number | null;
+  created_at?: string | null;
+  last_used_at?: string | null;
+  launch_count: number;
   updated_at: string;
 }
 
+export interface SearchMatch {
+  field: string;
+  value: string;
+  /** [start, end) character offsets into value */
+  ranges: [number, number][];
+}
+
+export interface SearchHit {
+  service: ServiceEntry;
+  score: number;
+  matches: SearchMatch[];
+}
+
+export type ServiceSort = 'manual' | 'name' | 'status' | 'last_used' | 'recently_added';
+
 export interface UpdateServiceRequest {
   display_name?: string;
   description?: string | null;
@@ -35,9 +72,12 @@ export interface UpdateServiceRequest {
   status?: ServiceStatus;
   group?: string | null;
   tags?: string[];
+  aliases?: string[];
   icon?: string | null;
   hidden?: boolean;
   favorite?: boolean;
+  links?: ServiceLink[];
+  network_urls?: Record<string, string>;
   locked_fields?: string[];
   auto_lock?: boolean;
 }
@@ -58,3 +98,46 @@ export interface DiscoveryStatusInfo {
 export interface DiscoveryRunResponse {
   summary: DiscoveryStatusInfo;
 }
+
+export interface GroupInfo {
+  key: string;
+  label: string;
+  builtin: boolean;
+  services: number;
+  icon?: string | null;
+  description?: string | null;
+  collapsed: boolean;
+}
+
+export interface UpdateGroupRequest {
+  key?: string;
+  icon?: string | null;
+  description?: string | null;
+  collapsed?: boolean;
+}
+
+export interface GroupChange {
+  group?: GroupInfo;
+  moved: string[];
+  skipped: string[];
+}
+
+export type Role = 'viewer' | 'editor' | 'admin';
+
+export interface Capabilities {
+  read_only: boolean;
+  can_edit: boolean;
+  can_run_discovery: boolean;
+  can_import: boolean;
+  can_admin: boolean;
+  can_record_use: boolean;
+  show_hidden: boolean;
+}
+
+export interface SessionInfo {
+  auth_enabled: boolean;
+  anonymous_read: boolean;
+  authenticated: boolean;
+  username?: string | null;
+  role?: Role | null;
+}